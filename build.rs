@@ -0,0 +1,10 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Identificador de la compilación. El service worker se registra con él en la
+// URL (`sw.js?v=...`), así que cada despliegue instala un worker nuevo con su
+// propia caché y la aplicación muestra el aviso de actualización.
+fn main() {
+    let version = std::env::var("CARGO_PKG_VERSION").unwrap_or_default();
+    let segundos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    println!("cargo:rustc-env=AC_PWA_BUILD={}-{}", version, segundos);
+}
@@ -0,0 +1,97 @@
+// Service worker de AC PWA.
+// Precarga el "app shell" (HTML, bundle de Dioxus e íconos; el CSS va dentro
+// del wasm) para que la aplicación abra sin conexión. La aplicación registra
+// el worker como `sw.js?v=<compilación>` (ver build.rs), así que cada
+// despliegue instala un worker nuevo con su propia caché. El worker nuevo queda
+// en espera hasta que la aplicación le pide activarse (mensaje SKIP_WAITING
+// desde el aviso de actualización).
+
+const CACHE_VERSION = "ac-pwa-" + (new URL(self.location.href).searchParams.get("v") || "dev");
+
+// Sin estos la aplicación no abre sin conexión: si falta alguno, la
+// instalación falla y sigue activo el worker anterior.
+const ESENCIALES = ["./", "./index.html"];
+
+// Su ausencia no impide trabajar sin conexión; solo se registra el error.
+const OPCIONALES = [
+    "./manifest.webmanifest",
+    "./config.json",
+    "./icons/icon-192.png",
+    "./icons/icon-512.png",
+];
+
+// El bundle de Dioxus (JS y wasm) se toma de las referencias que la compilación
+// deja en index.html, en lugar de repetir aquí sus nombres.
+const BUNDLE = /assets\/dioxus\/[^"'\s)]+\.(?:js|wasm)/g;
+
+// `reload` evita que la caché HTTP entregue archivos de la versión anterior.
+const sinCacheHttp = (url) => new Request(url, { cache: "reload" });
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(
+        (async () => {
+            const cache = await caches.open(CACHE_VERSION);
+            const html = await fetch(sinCacheHttp("./index.html")).then((r) => {
+                if (!r.ok) throw new Error("index.html respondió " + r.status);
+                return r.text();
+            });
+            const bundle = [...new Set(html.match(BUNDLE) || [])].map((ruta) => "./" + ruta);
+            if (bundle.length === 0) {
+                throw new Error("index.html no referencia el bundle de Dioxus");
+            }
+            await cache.addAll([...ESENCIALES, ...bundle].map(sinCacheHttp));
+            await Promise.all(
+                OPCIONALES.map((url) =>
+                    cache.add(sinCacheHttp(url)).catch((error) => console.error("No se pudo precargar", url, error))
+                )
+            );
+        })()
+    );
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches.keys()
+            .then((claves) => Promise.all(claves.filter((c) => c !== CACHE_VERSION).map((c) => caches.delete(c))))
+            .then(() => self.clients.claim())
+    );
+});
+
+self.addEventListener("message", (event) => {
+    if (event.data === "SKIP_WAITING") {
+        self.skipWaiting();
+    }
+});
+
+self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+
+    // Solo se cachean peticiones GET del propio origen; el backend nunca pasa por la caché.
+    if (request.method !== "GET" || url.origin !== self.location.origin) {
+        return;
+    }
+
+    // config.json y la navegación: primero la red, con la caché como respaldo.
+    if (request.mode === "navigate" || url.pathname.endsWith("/config.json")) {
+        event.respondWith(
+            fetch(request)
+                .then((respuesta) => guardar(request, respuesta))
+                .catch(() => caches.match(request).then((r) => r || caches.match("./index.html")))
+        );
+        return;
+    }
+
+    // Resto de recursos estáticos: primero la caché y se guarda lo nuevo.
+    event.respondWith(
+        caches.match(request).then((enCache) => enCache || fetch(request).then((respuesta) => guardar(request, respuesta)))
+    );
+});
+
+function guardar(request, respuesta) {
+    if (respuesta.ok) {
+        const copia = respuesta.clone();
+        caches.open(CACHE_VERSION).then((cache) => cache.put(request, copia));
+    }
+    return respuesta;
+}
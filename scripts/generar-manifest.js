@@ -0,0 +1,43 @@
+// Genera public/manifest.webmanifest a partir de package.json y de los íconos
+// en public/icons (icon-<tamaño>.png). Se ejecuta con `npm run build:manifest`.
+
+const fs = require("fs");
+const path = require("path");
+
+const raiz = path.join(__dirname, "..");
+const paquete = require(path.join(raiz, "package.json"));
+const carpetaIconos = path.join(raiz, "public", "icons");
+
+const iconos = fs
+    .readdirSync(carpetaIconos)
+    .map((archivo) => ({ archivo, tamano: /^icon-(\d+)\.png$/.exec(archivo) }))
+    .filter(({ tamano }) => tamano)
+    .sort((a, b) => Number(a.tamano[1]) - Number(b.tamano[1]))
+    // Una entrada por propósito: "any maskable" en una sola entrada hace que
+    // algunos navegadores recorten el ícono también donde no corresponde.
+    .flatMap(({ archivo, tamano }) =>
+        ["any", "maskable"].map((purpose) => ({
+            src: `icons/${archivo}`,
+            sizes: `${tamano[1]}x${tamano[1]}`,
+            type: "image/png",
+            purpose,
+        }))
+    );
+
+const manifest = {
+    name: paquete.pwa.nombre,
+    short_name: paquete.pwa.nombre_corto,
+    description: paquete.description,
+    lang: "es",
+    start_url: "./",
+    scope: "./",
+    display: "standalone",
+    orientation: "portrait",
+    background_color: paquete.pwa.color_fondo,
+    theme_color: paquete.pwa.color_tema,
+    icons: iconos,
+};
+
+const destino = path.join(raiz, "public", "manifest.webmanifest");
+fs.writeFileSync(destino, JSON.stringify(manifest, null, 4) + "\n");
+console.log(`Manifest generado con ${iconos.length} ícono(s): ${destino}`);
@@ -0,0 +1,136 @@
+use std::f64::consts::PI;
+
+use web_sys::CanvasRenderingContext2d;
+
+use crate::imagen::{self, AjustesImagen, ImagenComprimida};
+use crate::model::Foto;
+
+// --- Anotaciones sobre fotos ---
+// Los trazos se guardan en píxeles de la imagen. El editor los dibuja como
+// SVG encima de la foto y, al guardar, se rasterizan sobre una copia de la
+// imagen; la original se conserva sin cambios.
+
+pub const COLOR: &str = "#ef4444";
+
+pub type Punto = (f64, f64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Herramienta {
+    Flecha,
+    Circulo,
+    Libre,
+    Texto,
+}
+
+impl Herramienta {
+    pub const TODAS: [Herramienta; 4] = [Herramienta::Flecha, Herramienta::Circulo, Herramienta::Libre, Herramienta::Texto];
+
+    pub fn etiqueta(&self) -> &'static str {
+        match self {
+            Herramienta::Flecha => "Flecha",
+            Herramienta::Circulo => "Círculo",
+            Herramienta::Libre => "Libre",
+            Herramienta::Texto => "Texto",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trazo {
+    Flecha { desde: Punto, hasta: Punto },
+    Circulo { centro: Punto, radio: f64 },
+    Libre(Vec<Punto>),
+    Texto { posicion: Punto, texto: String },
+}
+
+/// Grosor de línea proporcional al tamaño de la foto.
+pub fn grosor(ancho: f64, alto: f64) -> f64 {
+    (ancho.max(alto) / 150.0).max(2.0)
+}
+
+pub fn tamano_texto(ancho: f64, alto: f64) -> f64 {
+    grosor(ancho, alto) * 6.0
+}
+
+/// Vértices del triángulo de la punta de una flecha.
+pub fn punta_flecha(desde: Punto, hasta: Punto, grosor: f64) -> [Punto; 3] {
+    let angulo = (hasta.1 - desde.1).atan2(hasta.0 - desde.0);
+    let largo = grosor * 4.0;
+    let lado = |desvio: f64| (hasta.0 - largo * (angulo + desvio).cos(), hasta.1 - largo * (angulo + desvio).sin());
+    [hasta, lado(PI / 7.0), lado(-PI / 7.0)]
+}
+
+pub fn distancia(a: Punto, b: Punto) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Dibuja la foto con sus anotaciones y la codifica como una imagen nueva.
+pub async fn exportar(foto: &Foto, trazos: &[Trazo], ajustes: &AjustesImagen) -> Result<ImagenComprimida, String> {
+    let bitmap = imagen::decodificar(&foto.contenido).await?;
+    let (ancho, alto) = (bitmap.width(), bitmap.height());
+    let (canvas, contexto) = imagen::crear_canvas(ancho, alto)?;
+    contexto.draw_image_with_image_bitmap(&bitmap, 0.0, 0.0).map_err(imagen::describir)?;
+    bitmap.close();
+
+    let (ancho, alto) = (ancho as f64, alto as f64);
+    let grosor = grosor(ancho, alto);
+    contexto.set_stroke_style_str(COLOR);
+    contexto.set_fill_style_str(COLOR);
+    contexto.set_line_width(grosor);
+    contexto.set_line_cap("round");
+    contexto.set_line_join("round");
+    for trazo in trazos {
+        dibujar(&contexto, trazo, grosor, tamano_texto(ancho, alto))?;
+    }
+
+    let (contenido, formato) = imagen::codificar_canvas(&canvas, ajustes).await?;
+    // Cada exportación lleva su propio sufijo: anotar dos veces la misma foto
+    // no debe producir dos archivos con el mismo nombre.
+    let base = foto.nombre.rsplit_once('.').map(|(base, _)| base).unwrap_or(&foto.nombre);
+    let nombre = format!("{}-anotada-{}.{}", base, crate::outbox::nuevo_uuid(), formato.extension());
+    Ok(ImagenComprimida { nombre, contenido })
+}
+
+fn dibujar(contexto: &CanvasRenderingContext2d, trazo: &Trazo, grosor: f64, tamano_texto: f64) -> Result<(), String> {
+    match trazo {
+        Trazo::Flecha { desde, hasta } => {
+            contexto.begin_path();
+            contexto.move_to(desde.0, desde.1);
+            contexto.line_to(hasta.0, hasta.1);
+            contexto.stroke();
+            let [a, b, c] = punta_flecha(*desde, *hasta, grosor);
+            contexto.begin_path();
+            contexto.move_to(a.0, a.1);
+            contexto.line_to(b.0, b.1);
+            contexto.line_to(c.0, c.1);
+            contexto.close_path();
+            contexto.fill();
+        }
+        Trazo::Circulo { centro, radio } => {
+            contexto.begin_path();
+            contexto.arc(centro.0, centro.1, *radio, 0.0, 2.0 * PI).map_err(imagen::describir)?;
+            contexto.stroke();
+        }
+        Trazo::Libre(puntos) => {
+            let Some((primero, resto)) = puntos.split_first() else { return Ok(()) };
+            contexto.begin_path();
+            contexto.move_to(primero.0, primero.1);
+            for punto in resto {
+                contexto.line_to(punto.0, punto.1);
+            }
+            contexto.stroke();
+        }
+        Trazo::Texto { posicion, texto } => {
+            contexto.set_font(&format!("bold {}px sans-serif", tamano_texto.round()));
+            contexto.set_text_baseline("middle");
+            // Contorno blanco para que el texto se lea sobre cualquier fondo.
+            contexto.save();
+            contexto.set_stroke_style_str("#ffffff");
+            contexto.set_line_width(grosor * 1.5);
+            contexto.stroke_text(texto, posicion.0, posicion.1).map_err(imagen::describir)?;
+            contexto.restore();
+            contexto.fill_text(texto, posicion.0, posicion.1).map_err(imagen::describir)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{Array, Date, Function, Object, Reflect};
+use web_sys::{DomException, HtmlVideoElement, MediaStream, MediaStreamConstraints, MediaStreamTrack};
+
+use crate::imagen::{self, AjustesImagen, ImagenComprimida};
+
+// --- Cámara dentro de la aplicación ---
+// Visor en vivo con `getUserMedia` para fotografiar sin salir del formulario.
+// Solo funciona en contextos seguros (HTTPS o localhost); en los demás casos
+// el formulario ofrece el selector de archivos con `capture="environment"`.
+
+pub const FOTOS_POR_RAFAGA: usize = 5;
+pub const INTERVALO_RAFAGA_MS: u32 = 350;
+
+pub fn disponible() -> bool {
+    let Some(window) = web_sys::window() else { return false };
+    window.is_secure_context() && Reflect::has(&window.navigator(), &"mediaDevices".into()).unwrap_or(false)
+}
+
+fn elemento_video(id: &str) -> Result<HtmlVideoElement, String> {
+    web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id(id))
+        .and_then(|e| e.dyn_into::<HtmlVideoElement>().ok())
+        .ok_or_else(|| "Visor de cámara no disponible".to_string())
+}
+
+fn asignar(objeto: &Object, clave: &str, valor: &JsValue) {
+    let _ = Reflect::set(objeto, &clave.into(), valor);
+}
+
+/// Abre la cámara trasera y la muestra en el `<video>` indicado.
+pub async fn abrir(id_video: &str) -> Result<MediaStream, String> {
+    let dispositivos = web_sys::window()
+        .ok_or("Ventana no disponible")?
+        .navigator()
+        .media_devices()
+        .map_err(|_| "Este navegador no permite usar la cámara".to_string())?;
+
+    let ideal = |valor: f64| {
+        let objeto = Object::new();
+        asignar(&objeto, "ideal", &valor.into());
+        objeto
+    };
+    let video = Object::new();
+    asignar(&video, "facingMode", &"environment".into());
+    asignar(&video, "width", &ideal(1920.0));
+    asignar(&video, "height", &ideal(1080.0));
+    let restricciones = MediaStreamConstraints::new();
+    restricciones.set_video(&video);
+    restricciones.set_audio(&false.into());
+
+    let stream = JsFuture::from(dispositivos.get_user_media_with_constraints(&restricciones).map_err(imagen::describir)?)
+        .await
+        .map_err(|e| describir_error(&e))?
+        .unchecked_into::<MediaStream>();
+
+    let elemento = match elemento_video(id_video) {
+        Ok(elemento) => elemento,
+        Err(e) => {
+            detener(&stream);
+            return Err(e);
+        }
+    };
+    elemento.set_muted(true);
+    elemento.set_src_object(Some(&stream));
+    if let Ok(reproducir) = elemento.play() {
+        let _ = JsFuture::from(reproducir).await;
+    }
+    Ok(stream)
+}
+
+pub fn detener(stream: &MediaStream) {
+    for pista in stream.get_tracks().iter() {
+        pista.unchecked_into::<MediaStreamTrack>().stop();
+    }
+}
+
+fn pista_video(stream: &MediaStream) -> Option<MediaStreamTrack> {
+    stream.get_video_tracks().get(0).dyn_into::<MediaStreamTrack>().ok()
+}
+
+/// Indica si la cámara permite encender la linterna (no todos los navegadores lo exponen).
+pub fn tiene_linterna(stream: &MediaStream) -> bool {
+    let Some(pista) = pista_video(stream) else { return false };
+    // `getCapabilities` no está en la API estable de web-sys; se invoca por reflexión.
+    let capacidades = Reflect::get(&pista, &"getCapabilities".into())
+        .ok()
+        .and_then(|f| f.dyn_into::<Function>().ok())
+        .and_then(|f| f.call0(&pista).ok());
+    capacidades
+        .and_then(|c| Reflect::get(&c, &"torch".into()).ok())
+        .map(|v| v.is_truthy())
+        .unwrap_or(false)
+}
+
+pub async fn linterna(stream: &MediaStream, encendida: bool) -> Result<(), String> {
+    let pista = pista_video(stream).ok_or("La cámara no está activa")?;
+    let avanzado = Object::new();
+    asignar(&avanzado, "torch", &encendida.into());
+    let restricciones = Object::new();
+    asignar(&restricciones, "advanced", &Array::of1(&avanzado));
+    let promesa = pista.apply_constraints_with_constraints(restricciones.unchecked_ref()).map_err(imagen::describir)?;
+    JsFuture::from(promesa)
+        .await
+        .map(|_| ())
+        .map_err(|_| "No se pudo cambiar la linterna".to_string())
+}
+
+/// Toma el cuadro actual del visor y lo codifica como una foto.
+pub async fn capturar(id_video: &str, ajustes: &AjustesImagen) -> Result<ImagenComprimida, String> {
+    let video = elemento_video(id_video)?;
+    if video.video_width() == 0 {
+        return Err("La cámara aún no está lista".to_string());
+    }
+    let (ancho, alto) = imagen::dimensiones(video.video_width(), video.video_height(), ajustes.dimension_maxima);
+    let (canvas, contexto) = imagen::crear_canvas(ancho, alto)?;
+    contexto
+        .draw_image_with_html_video_element_and_dw_and_dh(&video, 0.0, 0.0, ancho as f64, alto as f64)
+        .map_err(imagen::describir)?;
+    let (contenido, formato) = imagen::codificar_canvas(&canvas, ajustes).await?;
+    Ok(ImagenComprimida { nombre: format!("camara-{}.{}", Date::now() as u64, formato.extension()), contenido })
+}
+
+fn describir_error(error: &JsValue) -> String {
+    match error.dyn_ref::<DomException>().map(|e| e.name()).as_deref() {
+        Some("NotAllowedError") => "Permiso de cámara denegado. Habilítelo en los ajustes del navegador.".to_string(),
+        Some("NotFoundError") | Some("OverconstrainedError") => "No se encontró una cámara disponible.".to_string(),
+        Some("NotReadableError") => "La cámara está siendo usada por otra aplicación.".to_string(),
+        _ => format!("No se pudo abrir la cámara: {:?}", error),
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use wasm_bindgen::{closure::Closure, JsCast};
+
+use crate::model::{Encuesta, Foto};
+
+// --- Deshacer y rehacer ---
+// Se guarda una instantánea de la encuesta y sus fotos después de cada
+// edición. Las ediciones seguidas (escribir en un campo o en la descripción de
+// una foto) se agrupan en un solo paso; agregar, quitar u ordenar fotos y
+// conexiones siempre es un paso aparte. Cada foto se comparte entre
+// instantáneas mientras no cambia, para no copiar las imágenes en cada tecla.
+
+pub const PROFUNDIDAD_POR_DEFECTO: usize = 50;
+const AGRUPAR_MS: f64 = 1_000.0;
+
+#[derive(Clone)]
+struct Instantanea {
+    encuesta: Encuesta,
+    fotos: Vec<Rc<Foto>>,
+}
+
+impl Instantanea {
+    fn restaurar(self) -> (Encuesta, Vec<Foto>) {
+        let fotos = self.fotos.iter().map(|f| f.as_ref().clone()).collect();
+        (self.encuesta, fotos)
+    }
+}
+
+pub struct HistorialCambios {
+    pasados: VecDeque<Instantanea>,
+    futuros: Vec<Instantanea>,
+    actual: Option<Instantanea>,
+    // Borrador al que pertenece el historial; al cambiar de borrador se empieza de cero.
+    borrador: u64,
+    ultimo_cambio: f64,
+    profundidad: usize,
+}
+
+// Reutiliza la instantánea anterior de cada foto que no cambió; solo las
+// fotos nuevas o editadas se copian. Se comparan los metadatos, no los bytes.
+fn compartir_fotos(anteriores: &[Rc<Foto>], fotos: &[Foto]) -> Vec<Rc<Foto>> {
+    fotos
+        .iter()
+        .map(|foto| match anteriores.iter().find(|a| a.mismos_metadatos(foto)) {
+            Some(anterior) => anterior.clone(),
+            None => Rc::new(foto.clone()),
+        })
+        .collect()
+}
+
+fn mismas_fotos(a: &[Rc<Foto>], b: &[Rc<Foto>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| Rc::ptr_eq(a, b))
+}
+
+// Mismas fotos en el mismo orden, aunque cambie su descripción.
+fn mismo_orden(a: &[Rc<Foto>], b: &[Rc<Foto>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.nombre == b.nombre)
+}
+
+impl HistorialCambios {
+    pub fn new(profundidad: usize) -> Self {
+        HistorialCambios {
+            pasados: VecDeque::new(),
+            futuros: Vec::new(),
+            actual: None,
+            borrador: 0,
+            ultimo_cambio: 0.0,
+            profundidad: profundidad.max(1),
+        }
+    }
+
+    /// Registra el estado después de una edición.
+    pub fn registrar(&mut self, borrador: u64, encuesta: &Encuesta, fotos: &[Foto], ahora: f64) {
+        if borrador != self.borrador {
+            self.pasados.clear();
+            self.futuros.clear();
+            self.actual = None;
+            self.borrador = borrador;
+        }
+        let Some(actual) = &self.actual else {
+            self.actual = Some(Instantanea { encuesta: encuesta.clone(), fotos: compartir_fotos(&[], fotos) });
+            return;
+        };
+        let fotos = compartir_fotos(&actual.fotos, fotos);
+        // Sin cambios: es el propio deshacer/rehacer que vuelve a escribir las señales.
+        if mismas_fotos(&actual.fotos, &fotos) && actual.encuesta == *encuesta {
+            return;
+        }
+
+        let agrupar = mismo_orden(&actual.fotos, &fotos)
+            && ahora - self.ultimo_cambio < AGRUPAR_MS
+            && actual.encuesta.lista_conexiones.len() == encuesta.lista_conexiones.len();
+        let nueva = Instantanea { encuesta: encuesta.clone(), fotos };
+        if let Some(anterior) = self.actual.replace(nueva) {
+            if !agrupar {
+                self.pasados.push_back(anterior);
+                if self.pasados.len() > self.profundidad {
+                    self.pasados.pop_front();
+                }
+            }
+        }
+        self.futuros.clear();
+        self.ultimo_cambio = ahora;
+    }
+
+    pub fn puede_deshacer(&self) -> bool {
+        !self.pasados.is_empty()
+    }
+
+    pub fn puede_rehacer(&self) -> bool {
+        !self.futuros.is_empty()
+    }
+
+    /// Estado anterior a la última edición, para volver a cargarlo en el formulario.
+    pub fn deshacer(&mut self) -> Option<(Encuesta, Vec<Foto>)> {
+        let anterior = self.pasados.pop_back()?;
+        self.futuros.extend(self.actual.replace(anterior.clone()));
+        // La siguiente edición no se agrupa con lo que se acaba de deshacer.
+        self.ultimo_cambio = 0.0;
+        Some(anterior.restaurar())
+    }
+
+    pub fn rehacer(&mut self) -> Option<(Encuesta, Vec<Foto>)> {
+        let siguiente = self.futuros.pop()?;
+        self.pasados.extend(self.actual.replace(siguiente.clone()));
+        self.ultimo_cambio = 0.0;
+        Some(siguiente.restaurar())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Atajo {
+    Deshacer,
+    Rehacer,
+}
+
+/// Escucha Ctrl+Z / Ctrl+Shift+Z / Ctrl+Y (Cmd en macOS). Dentro de un campo
+/// de texto se deja el deshacer propio del navegador.
+pub fn al_presionar_atajo(mut callback: impl FnMut(Atajo) + 'static) {
+    let Some(window) = web_sys::window() else { return };
+    let closure = Closure::<dyn FnMut(web_sys::KeyboardEvent)>::new(move |evento: web_sys::KeyboardEvent| {
+        if !(evento.ctrl_key() || evento.meta_key()) {
+            return;
+        }
+        let atajo = match (evento.key().to_lowercase().as_str(), evento.shift_key()) {
+            ("z", false) => Atajo::Deshacer,
+            ("z", true) | ("y", false) => Atajo::Rehacer,
+            _ => return,
+        };
+        let en_texto = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.active_element())
+            .map(|e| matches!(e.tag_name().as_str(), "INPUT" | "TEXTAREA"))
+            .unwrap_or(false);
+        if en_texto {
+            return;
+        }
+        evento.prevent_default();
+        callback(atajo);
+    });
+    if let Err(e) = window.add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref()) {
+        log::error!("No se pudo registrar el evento 'keydown': {:?}", e);
+    }
+    // El oyente vive durante toda la aplicación.
+    closure.forget();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{compartir_fotos, HistorialCambios, AGRUPAR_MS};
+    use crate::model::{ComponenteFoto, Encuesta, Foto};
+
+    fn encuesta(pozo: &str) -> Encuesta {
+        Encuesta { pozo_numero: pozo.to_string(), ..Default::default() }
+    }
+
+    fn foto(nombre: &str, descripcion: &str) -> Foto {
+        Foto {
+            nombre: nombre.to_string(),
+            componente: ComponenteFoto::General,
+            descripcion: descripcion.to_string(),
+            capturada_en: String::new(),
+            ubicacion: None,
+            tamano_original: None,
+            anotacion_de: None,
+            en_servidor: false,
+            contenido: vec![1, 2, 3],
+        }
+    }
+
+    fn pozo_al_deshacer(cambios: &mut HistorialCambios) -> Option<String> {
+        cambios.deshacer().map(|(encuesta, _)| encuesta.pozo_numero)
+    }
+
+    #[test]
+    fn las_ediciones_seguidas_se_agrupan() {
+        let mut cambios = HistorialCambios::new(10);
+        cambios.registrar(1, &encuesta(""), &[], 0.0);
+        cambios.registrar(1, &encuesta("P"), &[], 5_000.0);
+        cambios.registrar(1, &encuesta("P1"), &[], 5_000.0 + AGRUPAR_MS / 2.0);
+        cambios.registrar(1, &encuesta("P12"), &[], 5_000.0 + AGRUPAR_MS);
+        cambios.registrar(1, &encuesta("P123"), &[], 5_000.0 + AGRUPAR_MS * 3.0);
+        assert_eq!(pozo_al_deshacer(&mut cambios).as_deref(), Some("P12"));
+        assert_eq!(pozo_al_deshacer(&mut cambios).as_deref(), Some(""));
+        assert!(!cambios.puede_deshacer());
+        assert_eq!(cambios.rehacer().map(|(e, _)| e.pozo_numero).as_deref(), Some("P12"));
+    }
+
+    #[test]
+    fn agregar_una_foto_siempre_es_un_paso_aparte() {
+        let mut cambios = HistorialCambios::new(10);
+        let datos = encuesta("P1");
+        cambios.registrar(1, &datos, &[foto("a.jpg", "")], 0.0);
+        cambios.registrar(1, &datos, &[foto("a.jpg", ""), foto("b.jpg", "")], 10.0);
+        // Escribir la descripción sí se agrupa.
+        cambios.registrar(1, &datos, &[foto("a.jpg", "t"), foto("b.jpg", "")], 20.0);
+        cambios.registrar(1, &datos, &[foto("a.jpg", "tapa"), foto("b.jpg", "")], 30.0);
+        let (_, fotos) = cambios.deshacer().unwrap();
+        assert_eq!(fotos, vec![foto("a.jpg", "")]);
+        assert!(!cambios.puede_deshacer());
+    }
+
+    #[test]
+    fn la_profundidad_limita_los_pasos() {
+        let mut cambios = HistorialCambios::new(3);
+        for i in 0..10 {
+            cambios.registrar(1, &encuesta(&i.to_string()), &[], i as f64 * 10.0 * AGRUPAR_MS);
+        }
+        let mut pasos = 0;
+        while cambios.deshacer().is_some() {
+            pasos += 1;
+        }
+        assert_eq!(pasos, 3);
+    }
+
+    #[test]
+    fn cambiar_de_borrador_reinicia_el_historial() {
+        let mut cambios = HistorialCambios::new(10);
+        cambios.registrar(1, &encuesta(""), &[], 0.0);
+        cambios.registrar(1, &encuesta("P1"), &[], 10.0 * AGRUPAR_MS);
+        cambios.registrar(2, &encuesta("P2"), &[], 20.0 * AGRUPAR_MS);
+        assert!(!cambios.puede_deshacer());
+    }
+
+    #[test]
+    fn escribir_el_estado_restaurado_no_es_un_cambio() {
+        let mut cambios = HistorialCambios::new(10);
+        cambios.registrar(1, &encuesta(""), &[], 0.0);
+        cambios.registrar(1, &encuesta("P1"), &[], 10.0 * AGRUPAR_MS);
+        let (datos, fotos) = cambios.deshacer().unwrap();
+        cambios.registrar(1, &datos, &fotos, 11.0 * AGRUPAR_MS);
+        assert!(cambios.puede_rehacer());
+    }
+
+    #[test]
+    fn las_fotos_sin_cambios_se_comparten() {
+        let anteriores = compartir_fotos(&[], &[foto("a.jpg", ""), foto("b.jpg", "")]);
+        let nuevas = compartir_fotos(&anteriores, &[foto("a.jpg", ""), foto("b.jpg", "editada")]);
+        assert!(Rc::ptr_eq(&anteriores[0], &nuevas[0]));
+        assert!(!Rc::ptr_eq(&anteriores[1], &nuevas[1]));
+    }
+}
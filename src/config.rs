@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cambios;
+use crate::imagen::AjustesImagen;
+use crate::model::{opciones, Opcion};
+use crate::storage;
+
+// --- Configuración de la aplicación en tiempo de ejecución ---
+// La URL del backend se resuelve al iniciar, en este orden de prioridad:
+//   1. Parámetros de la URL de la página (`?perfil=staging` o `?backend=https://...`).
+//      `backend` solo puede nombrar la URL de un perfil configurado: un enlace no
+//      debe poder desviar el inicio de sesión y los envíos a otro servidor. Un
+//      servidor distinto solo se fija a mano en la pantalla de configuración.
+//   2. Ajustes guardados desde la pantalla de configuración (localStorage).
+//   3. `config.json` servido junto a la aplicación.
+//   4. El perfil `dev` incluido en el código.
+//
+// Formato de `config.json`:
+//   { "perfil": "production",
+//     "perfiles": { "dev": "http://...", "staging": "https://...", "production": "https://..." } }
+
+const CLAVE_AJUSTES: &str = "ac-pwa/configuracion";
+const ARCHIVO_CONFIGURACION: &str = "config.json";
+const BACKEND_URL_DEV: &str = "http://192.168.128.15:5000";
+
+opciones!(Perfil {
+    Dev => "dev",
+    Staging => "staging",
+    Produccion => "production",
+});
+
+#[derive(Debug, Default, Deserialize)]
+struct ArchivoConfiguracion {
+    #[serde(default)]
+    perfil: Option<Perfil>,
+    #[serde(default)]
+    perfiles: HashMap<Perfil, String>,
+}
+
+// Lo que el usuario elige en la pantalla de configuración.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AjustesLocales {
+    pub perfil: Option<Perfil>,
+    pub backend_url: Option<String>,
+    #[serde(default)]
+    pub imagenes: AjustesImagen,
+    /// Pasos que se pueden deshacer en el formulario; `None` usa el valor por defecto.
+    #[serde(default)]
+    pub pasos_deshacer: Option<usize>,
+}
+
+impl AjustesLocales {
+    pub fn profundidad_deshacer(&self) -> usize {
+        self.pasos_deshacer.unwrap_or(cambios::PROFUNDIDAD_POR_DEFECTO)
+    }
+}
+
+// De dónde salió la URL activa; se muestra en la pantalla de configuración.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origen {
+    PorDefecto,
+    Archivo,
+    Ajustes,
+    ParametroUrl,
+}
+
+impl std::fmt::Display for Origen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Origen::PorDefecto => "valor por defecto",
+            Origen::Archivo => ARCHIVO_CONFIGURACION,
+            Origen::Ajustes => "ajustes del dispositivo",
+            Origen::ParametroUrl => "parámetro de la URL",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Configuracion {
+    pub perfil: Perfil,
+    pub backend_url: String,
+    pub origen: Origen,
+    pub perfiles: HashMap<Perfil, String>,
+    /// `?backend=` de la URL que no corresponde a ningún perfil y no se usó.
+    pub backend_ignorado: Option<String>,
+}
+
+impl Default for Configuracion {
+    fn default() -> Self {
+        Configuracion {
+            perfil: Perfil::Dev,
+            backend_url: BACKEND_URL_DEV.to_string(),
+            origen: Origen::PorDefecto,
+            perfiles: HashMap::from([(Perfil::Dev, BACKEND_URL_DEV.to_string())]),
+            backend_ignorado: None,
+        }
+    }
+}
+
+impl Configuracion {
+    /// URL del endpoint indicado, p. ej. `url("/login")`.
+    pub fn url(&self, ruta: &str) -> String {
+        format!("{}{}", self.backend_url.trim_end_matches('/'), ruta)
+    }
+
+    /// Verdadero si los datos no van al servidor del perfil activo (URL
+    /// personalizada en los ajustes).
+    pub fn url_personalizada(&self) -> bool {
+        perfil_de_url(&self.perfiles, &self.backend_url) != Some(self.perfil)
+    }
+}
+
+fn url_pagina() -> Option<reqwest::Url> {
+    let href = web_sys::window()?.location().href().ok()?;
+    reqwest::Url::parse(&href).ok()
+}
+
+async fn leer_archivo(pagina: &reqwest::Url) -> Option<ArchivoConfiguracion> {
+    let url = pagina.join(ARCHIVO_CONFIGURACION).ok()?;
+    let response = match reqwest::get(url.clone()).await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            log::info!("Sin {} ({}); se usa la configuración por defecto.", url, response.status());
+            return None;
+        }
+        Err(e) => {
+            log::info!("No se pudo descargar {}: {}", url, e);
+            return None;
+        }
+    };
+    match response.json::<ArchivoConfiguracion>().await {
+        Ok(archivo) => Some(archivo),
+        Err(e) => {
+            log::error!("{} no es válido: {}", ARCHIVO_CONFIGURACION, e);
+            None
+        }
+    }
+}
+
+pub async fn cargar() -> Configuracion {
+    let pagina = url_pagina();
+    let archivo = match &pagina {
+        Some(pagina) => leer_archivo(pagina).await,
+        None => None,
+    };
+    let parametros: Vec<(String, String)> = pagina
+        .as_ref()
+        .map(|p| p.query_pairs().map(|(clave, valor)| (clave.into_owned(), valor.into_owned())).collect())
+        .unwrap_or_default();
+    let configuracion = resolver(archivo, leer_ajustes(), &parametros);
+    log::info!("Configuración: perfil {} -> {} ({})", configuracion.perfil, configuracion.backend_url, configuracion.origen);
+    configuracion
+}
+
+/// Perfil configurado cuyo servidor es `url`, sin distinguir mayúsculas ni la
+/// barra final. `?backend=` solo se acepta si nombra uno de estos.
+fn perfil_de_url(perfiles: &HashMap<Perfil, String>, url: &str) -> Option<Perfil> {
+    let normalizar = |u: &str| u.trim().trim_end_matches('/').to_lowercase();
+    perfiles.iter().find(|(_, u)| normalizar(u) == normalizar(url)).map(|(perfil, _)| *perfil)
+}
+
+// Aplica las fuentes en el orden de prioridad descrito al inicio del módulo.
+fn resolver(archivo: Option<ArchivoConfiguracion>, ajustes: AjustesLocales, parametros: &[(String, String)]) -> Configuracion {
+    let mut configuracion = Configuracion::default();
+    if let Some(archivo) = archivo {
+        configuracion.perfiles.extend(archivo.perfiles);
+        if let Some(perfil) = archivo.perfil {
+            configuracion.perfil = perfil;
+            configuracion.origen = Origen::Archivo;
+        }
+    }
+
+    let mut url_forzada = None;
+    if let Some(perfil) = ajustes.perfil {
+        configuracion.perfil = perfil;
+        configuracion.origen = Origen::Ajustes;
+    }
+    if let Some(url) = ajustes.backend_url.filter(|u| !u.trim().is_empty()) {
+        url_forzada = Some(url);
+        configuracion.origen = Origen::Ajustes;
+    }
+
+    let parametro = |nombre: &str| parametros.iter().find(|(clave, _)| clave == nombre).map(|(_, valor)| valor.clone());
+    if let Some(valor) = parametro("perfil") {
+        match Perfil::desde_etiqueta(&valor) {
+            Some(perfil) => {
+                // Un perfil explícito en la URL manda sobre la URL personalizada guardada.
+                configuracion.perfil = perfil;
+                configuracion.origen = Origen::ParametroUrl;
+                url_forzada = None;
+            }
+            None => log::error!("Perfil desconocido en la URL: {}", valor),
+        }
+    }
+    if let Some(url) = parametro("backend") {
+        match perfil_de_url(&configuracion.perfiles, &url) {
+            Some(perfil) => {
+                configuracion.perfil = perfil;
+                configuracion.origen = Origen::ParametroUrl;
+                url_forzada = None;
+            }
+            None => {
+                log::error!("Se ignora backend={} en la URL: no es el servidor de ningún perfil configurado.", url);
+                configuracion.backend_ignorado = Some(url);
+            }
+        }
+    }
+
+    configuracion.backend_url = match url_forzada {
+        Some(url) => url,
+        None => match configuracion.perfiles.get(&configuracion.perfil) {
+            Some(url) => url.clone(),
+            None => {
+                log::error!("El perfil '{}' no tiene URL configurada; se usa dev.", configuracion.perfil);
+                configuracion.perfil = Perfil::Dev;
+                configuracion.perfiles.get(&Perfil::Dev).cloned().unwrap_or_else(|| BACKEND_URL_DEV.to_string())
+            }
+        },
+    };
+    configuracion
+}
+
+pub fn leer_ajustes() -> AjustesLocales {
+    storage::leer(CLAVE_AJUSTES).unwrap_or_default()
+}
+
+pub fn guardar_ajustes(ajustes: &AjustesLocales) {
+    if let Err(e) = storage::guardar(CLAVE_AJUSTES, ajustes) {
+        log::error!("No se pudieron guardar los ajustes: {}", e);
+    }
+}
+
+pub fn borrar_ajustes() {
+    storage::borrar(CLAVE_AJUSTES);
+}
+
+/// Recarga la página para aplicar una configuración nueva desde cero.
+pub fn recargar() {
+    if let Some(window) = web_sys::window() {
+        let _ = window.location().reload();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{perfil_de_url, resolver, AjustesLocales, ArchivoConfiguracion, Origen, Perfil};
+
+    const STAGING: &str = "https://staging.ejemplo.co";
+
+    fn archivo() -> Option<ArchivoConfiguracion> {
+        Some(ArchivoConfiguracion {
+            perfil: Some(Perfil::Produccion),
+            perfiles: HashMap::from([(Perfil::Staging, STAGING.to_string()), (Perfil::Produccion, "https://ejemplo.co".to_string())]),
+        })
+    }
+
+    fn parametros(pares: &[(&str, &str)]) -> Vec<(String, String)> {
+        pares.iter().map(|(clave, valor)| (clave.to_string(), valor.to_string())).collect()
+    }
+
+    #[test]
+    fn backend_solo_acepta_urls_de_perfiles() {
+        let perfiles = archivo().unwrap().perfiles;
+        assert_eq!(perfil_de_url(&perfiles, STAGING), Some(Perfil::Staging));
+        assert_eq!(perfil_de_url(&perfiles, " HTTPS://Staging.Ejemplo.co/ "), Some(Perfil::Staging));
+        assert_eq!(perfil_de_url(&perfiles, "https://atacante.example"), None);
+        assert_eq!(perfil_de_url(&perfiles, "https://staging.ejemplo.co.atacante.example"), None);
+    }
+
+    #[test]
+    fn backend_de_un_perfil_lo_selecciona() {
+        let configuracion = resolver(archivo(), AjustesLocales::default(), &parametros(&[("backend", "https://staging.ejemplo.co/")]));
+        assert_eq!(configuracion.perfil, Perfil::Staging);
+        assert_eq!(configuracion.backend_url, STAGING);
+        assert_eq!(configuracion.origen, Origen::ParametroUrl);
+        assert_eq!(configuracion.backend_ignorado, None);
+    }
+
+    #[test]
+    fn backend_desconocido_se_ignora() {
+        let configuracion = resolver(archivo(), AjustesLocales::default(), &parametros(&[("backend", "https://atacante.example")]));
+        assert_eq!(configuracion.perfil, Perfil::Produccion);
+        assert_eq!(configuracion.backend_url, "https://ejemplo.co");
+        assert_eq!(configuracion.origen, Origen::Archivo);
+        assert_eq!(configuracion.backend_ignorado.as_deref(), Some("https://atacante.example"));
+    }
+
+    #[test]
+    fn la_url_personalizada_guardada_cede_ante_un_perfil_de_la_url() {
+        let ajustes = AjustesLocales { backend_url: Some("http://10.0.0.5:5000".to_string()), ..Default::default() };
+        let guardada = resolver(archivo(), ajustes.clone(), &[]);
+        assert_eq!(guardada.backend_url, "http://10.0.0.5:5000");
+        assert_eq!(guardada.origen, Origen::Ajustes);
+        assert!(guardada.url_personalizada());
+
+        let con_perfil = resolver(archivo(), ajustes, &parametros(&[("perfil", "staging")]));
+        assert_eq!(con_perfil.backend_url, STAGING);
+        assert_eq!(con_perfil.origen, Origen::ParametroUrl);
+        assert!(!con_perfil.url_personalizada());
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fotos_db;
+use crate::model::{Foto, Encuesta};
+use crate::storage;
+
+// --- Borradores de inspecciones en curso ---
+// Cada borrador guarda la encuesta y los metadatos de sus fotos en claves
+// separadas; las imágenes van en IndexedDB (ver `fotos_db`) y quien guarda
+// las fotos debe haberlas escrito allí antes. El índice mantiene un resumen
+// de todos los borradores para el selector de inicio.
+
+const CLAVE_INDICE: &str = "ac-pwa/borradores";
+
+fn clave_encuesta(id: u64) -> String {
+    format!("ac-pwa/borrador/{}", id)
+}
+
+fn clave_fotos(id: u64) -> String {
+    format!("ac-pwa/borrador/{}/fotos", id)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResumenBorrador {
+    pub id: u64,
+    pub pozo_numero: String,
+    pub actualizado_en: f64,
+    pub fotos: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Borradores {
+    resumenes: Vec<ResumenBorrador>,
+}
+
+impl Borradores {
+    pub fn cargar() -> Self {
+        storage::leer(CLAVE_INDICE).unwrap_or_default()
+    }
+
+    fn guardar_indice(&self) {
+        if let Err(e) = storage::guardar(CLAVE_INDICE, self) {
+            log::error!("No se pudo guardar el índice de borradores: {}", e);
+        }
+    }
+
+    /// Borradores ordenados del más reciente al más antiguo.
+    pub fn lista(&self) -> Vec<ResumenBorrador> {
+        let mut lista = self.resumenes.clone();
+        lista.sort_by(|a, b| b.actualizado_en.total_cmp(&a.actualizado_en));
+        lista
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resumenes.is_empty()
+    }
+
+    // Devuelve el resumen del borrador, creándolo si aún no existe.
+    fn resumen_mut(&mut self, id: u64) -> &mut ResumenBorrador {
+        let posicion = match self.resumenes.iter().position(|r| r.id == id) {
+            Some(posicion) => posicion,
+            None => {
+                self.resumenes.push(ResumenBorrador { id, pozo_numero: String::new(), actualizado_en: 0.0, fotos: 0 });
+                self.resumenes.len() - 1
+            }
+        };
+        &mut self.resumenes[posicion]
+    }
+
+    pub fn guardar_encuesta(&mut self, id: u64, encuesta: &Encuesta, ahora: f64) {
+        // Un formulario vacío no merece un borrador nuevo.
+        let existe = self.resumenes.iter().any(|r| r.id == id);
+        if !existe && encuesta.esta_vacia() {
+            return;
+        }
+        if let Err(e) = storage::guardar(&clave_encuesta(id), encuesta) {
+            log::error!("No se pudo guardar el borrador {}: {}", id, e);
+            return;
+        }
+        let resumen = self.resumen_mut(id);
+        resumen.pozo_numero = encuesta.pozo_numero.clone();
+        resumen.actualizado_en = ahora;
+        self.guardar_indice();
+    }
+
+    pub fn guardar_fotos(&mut self, id: u64, adjuntos: &[Foto], ahora: f64) {
+        let existe = self.resumenes.iter().any(|r| r.id == id);
+        if !existe && adjuntos.is_empty() {
+            return;
+        }
+        if let Err(e) = storage::guardar(&clave_fotos(id), &adjuntos) {
+            log::error!("No se pudieron guardar las fotos del borrador {}: {}", id, e);
+            return;
+        }
+        let resumen = self.resumen_mut(id);
+        resumen.fotos = adjuntos.len();
+        resumen.actualizado_en = ahora;
+        self.guardar_indice();
+    }
+
+    /// Encuesta y fotos del borrador. Las fotos traen solo los metadatos; la
+    /// imagen se completa con `fotos_db::cargar_contenido`.
+    pub fn abrir(&self, id: u64) -> Option<(Encuesta, Vec<Foto>)> {
+        let encuesta = storage::leer(&clave_encuesta(id)).unwrap_or_default();
+        let mut adjuntos: Vec<Foto> = storage::leer(&clave_fotos(id)).unwrap_or_default();
+        fotos_db::renombrar_anteriores(&mut adjuntos);
+        self.resumenes.iter().any(|r| r.id == id).then_some((encuesta, adjuntos))
+    }
+
+    /// Nombres de las imágenes que usa algún borrador.
+    pub fn fotos_referenciadas(&self) -> BTreeSet<String> {
+        self.resumenes
+            .iter()
+            .filter_map(|r| storage::leer::<Vec<Foto>>(&clave_fotos(r.id)))
+            .flatten()
+            .filter(|f| !f.en_servidor)
+            .map(|f| f.nombre)
+            .collect()
+    }
+
+    /// Encuestas de todos los borradores con su última modificación, del más
+    /// reciente al más antiguo, sin sus fotos.
+    pub fn encuestas(&self) -> Vec<(f64, Encuesta)> {
+        self.lista().iter().filter_map(|r| Some((r.actualizado_en, storage::leer(&clave_encuesta(r.id))?))).collect()
+    }
+
+    pub fn descartar(&mut self, id: u64) {
+        storage::borrar(&clave_encuesta(id));
+        storage::borrar(&clave_fotos(id));
+        self.resumenes.retain(|r| r.id != id);
+        self.guardar_indice();
+    }
+}
+
+/// Identificador para un borrador nuevo (milisegundos desde la época).
+pub fn nuevo_id(ahora: f64) -> u64 {
+    ahora as u64
+}
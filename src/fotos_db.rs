@@ -0,0 +1,190 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{Array, Promise, Uint8Array};
+use web_sys::{IdbDatabase, IdbObjectStore, IdbRequest, IdbTransaction, IdbTransactionMode};
+
+use crate::imagen::{self, describir};
+use crate::model::Foto;
+
+// --- Imágenes de las fotos en IndexedDB ---
+// localStorage solo admite unos pocos MB de texto, así que borradores y
+// bandeja guardan allí únicamente los metadatos de cada foto. Los bytes van
+// en IndexedDB bajo el nombre de la foto, que es único (`imagen::nombre_unico`)
+// y nunca cambia: cada imagen se escribe una sola vez y la comparten el
+// borrador y la bandeja. Las que ya nadie referencia se eliminan con
+// `conservar_solo`.
+
+const BASE: &str = "ac-pwa";
+const VERSION: u32 = 1;
+const ALMACEN: &str = "fotos";
+
+thread_local! {
+    static CONEXION: RefCell<Option<IdbDatabase>> = const { RefCell::new(None) };
+    // Imágenes que ya se escribieron durante esta sesión.
+    static GUARDADAS: RefCell<BTreeSet<String>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+async fn abrir() -> Result<IdbDatabase, String> {
+    if let Some(base) = CONEXION.with(|c| c.borrow().clone()) {
+        return Ok(base);
+    }
+    let fabrica = web_sys::window()
+        .and_then(|w| w.indexed_db().ok().flatten())
+        .ok_or("IndexedDB no disponible en este navegador")?;
+    let solicitud = fabrica.open_with_u32(BASE, VERSION).map_err(describir)?;
+    // La primera vez (o al subir `VERSION`) se crea el almacén de imágenes.
+    let al_actualizar = {
+        let solicitud = solicitud.clone();
+        Closure::<dyn FnMut(JsValue)>::new(move |_: JsValue| {
+            let Ok(base) = solicitud.result().and_then(|r| r.dyn_into::<IdbDatabase>()) else {
+                return;
+            };
+            if !base.object_store_names().contains(ALMACEN) {
+                if let Err(e) = base.create_object_store(ALMACEN) {
+                    log::error!("No se pudo crear el almacén de fotos: {:?}", e);
+                }
+            }
+        })
+    };
+    solicitud.set_onupgradeneeded(Some(al_actualizar.as_ref().unchecked_ref()));
+    let base = esperar(&solicitud).await?;
+    solicitud.set_onupgradeneeded(None);
+    let base: IdbDatabase = base.dyn_into().map_err(|_| "No se pudo abrir la base de fotos".to_string())?;
+    CONEXION.with(|c| *c.borrow_mut() = Some(base.clone()));
+    Ok(base)
+}
+
+// Resultado de una solicitud de IndexedDB como futuro.
+async fn esperar(solicitud: &IdbRequest) -> Result<JsValue, String> {
+    let promesa = Promise::new(&mut |resolver, rechazar| {
+        let exitosa = solicitud.clone();
+        let al_terminar = Closure::once_into_js(move |_: JsValue| {
+            let _ = resolver.call1(&JsValue::NULL, &exitosa.result().unwrap_or(JsValue::UNDEFINED));
+        });
+        let fallida = solicitud.clone();
+        let al_fallar = Closure::once_into_js(move |_: JsValue| {
+            let error = fallida.error().ok().flatten().map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+            let _ = rechazar.call1(&JsValue::NULL, &error);
+        });
+        solicitud.set_onsuccess(Some(al_terminar.unchecked_ref()));
+        solicitud.set_onerror(Some(al_fallar.unchecked_ref()));
+    });
+    JsFuture::from(promesa).await.map_err(describir)
+}
+
+// Espera a que la transacción se confirme. Una solicitud fallida (p. ej. por
+// falta de espacio) termina abortando la transacción completa.
+async fn completar(transaccion: &IdbTransaction) -> Result<(), String> {
+    let promesa = Promise::new(&mut |resolver, rechazar| {
+        let al_completar = Closure::once_into_js(move |_: JsValue| {
+            let _ = resolver.call0(&JsValue::NULL);
+        });
+        let abortada = transaccion.clone();
+        let al_abortar = Closure::once_into_js(move |_: JsValue| {
+            let error = abortada.error().map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+            let _ = rechazar.call1(&JsValue::NULL, &error);
+        });
+        transaccion.set_oncomplete(Some(al_completar.unchecked_ref()));
+        transaccion.set_onabort(Some(al_abortar.unchecked_ref()));
+    });
+    JsFuture::from(promesa).await.map(|_| ()).map_err(describir)
+}
+
+async fn almacen(modo: IdbTransactionMode) -> Result<(IdbTransaction, IdbObjectStore), String> {
+    let base = abrir().await?;
+    let transaccion = base.transaction_with_str_and_mode(ALMACEN, modo).map_err(describir)?;
+    let almacen = transaccion.object_store(ALMACEN).map_err(describir)?;
+    Ok((transaccion, almacen))
+}
+
+pub fn ya_guardada(nombre: &str) -> bool {
+    GUARDADAS.with(|g| g.borrow().contains(nombre))
+}
+
+/// Escribe las imágenes que aún no están en la base. Los metadatos que las
+/// referencian solo deben persistirse después de que esto termine bien.
+pub async fn guardar_fotos(fotos: &[Foto]) -> Result<(), String> {
+    let nuevas: Vec<&Foto> = fotos.iter().filter(|f| !f.contenido.is_empty() && !ya_guardada(&f.nombre)).collect();
+    if nuevas.is_empty() {
+        return Ok(());
+    }
+    let (transaccion, almacen) = almacen(IdbTransactionMode::Readwrite).await?;
+    for foto in &nuevas {
+        almacen
+            .put_with_key(&Uint8Array::from(foto.contenido.as_slice()), &JsValue::from_str(&foto.nombre))
+            .map_err(describir)?;
+    }
+    completar(&transaccion).await.map_err(|e| format!("No se pudieron guardar las fotos en el dispositivo: {}", e))?;
+    GUARDADAS.with(|g| g.borrow_mut().extend(nuevas.iter().map(|f| f.nombre.clone())));
+    Ok(())
+}
+
+/// Completa la imagen de las fotos que solo traen metadatos. Las que ya están
+/// en el backend no se buscan; las que no aparecen quedan sin contenido.
+pub async fn cargar_contenido(fotos: &mut [Foto]) -> Result<(), String> {
+    if !fotos.iter().any(le_falta_contenido) {
+        return Ok(());
+    }
+    let (_transaccion, almacen) = almacen(IdbTransactionMode::Readonly).await?;
+    for foto in fotos.iter_mut().filter(|f| le_falta_contenido(f)) {
+        let solicitud = almacen.get(&JsValue::from_str(&foto.nombre)).map_err(describir)?;
+        let valor = esperar(&solicitud).await?;
+        match valor.dyn_into::<Uint8Array>() {
+            Ok(bytes) => {
+                foto.contenido = bytes.to_vec();
+                GUARDADAS.with(|g| g.borrow_mut().insert(foto.nombre.clone()));
+            }
+            Err(_) => log::warn!("La imagen de la foto {} no está en el dispositivo", foto.nombre),
+        }
+    }
+    Ok(())
+}
+
+pub fn le_falta_contenido(foto: &Foto) -> bool {
+    !foto.en_servidor && foto.contenido.is_empty()
+}
+
+/// Elimina las imágenes que ya no usa ningún borrador ni la bandeja.
+/// `referenciadas` se evalúa después de leer las claves, de modo que una
+/// imagen escrita mientras tanto nunca se borra.
+pub async fn conservar_solo(referenciadas: impl FnOnce() -> BTreeSet<String>) -> Result<usize, String> {
+    let claves = {
+        let (_transaccion, almacen) = almacen(IdbTransactionMode::Readonly).await?;
+        let solicitud = almacen.get_all_keys().map_err(describir)?;
+        esperar(&solicitud).await?
+    };
+    let claves: Vec<String> = claves.dyn_into::<Array>().map(|a| a.iter().filter_map(|c| c.as_string()).collect()).unwrap_or_default();
+    // Entre calcular las referencias y pedir los borrados no hay ninguna espera.
+    let (transaccion, almacen) = almacen(IdbTransactionMode::Readwrite).await?;
+    let referenciadas = referenciadas();
+    let sobrantes: Vec<String> = claves.into_iter().filter(|c| !referenciadas.contains(c)).collect();
+    for nombre in &sobrantes {
+        almacen.delete(&JsValue::from_str(nombre)).map_err(describir)?;
+    }
+    completar(&transaccion).await?;
+    GUARDADAS.with(|g| {
+        let mut guardadas = g.borrow_mut();
+        for nombre in &sobrantes {
+            guardadas.remove(nombre);
+        }
+    });
+    Ok(sobrantes.len())
+}
+
+/// Las fotos leídas de versiones anteriores traen la imagen en base64 y un
+/// nombre que puede repetirse; antes de pasarlas a la base reciben uno único.
+pub fn renombrar_anteriores(fotos: &mut [Foto]) {
+    let mut nuevos = BTreeMap::new();
+    for foto in fotos.iter_mut().filter(|f| !f.contenido.is_empty()) {
+        let nombre = imagen::nombre_unico(&foto.nombre);
+        nuevos.insert(std::mem::replace(&mut foto.nombre, nombre.clone()), nombre);
+    }
+    for foto in fotos.iter_mut() {
+        if let Some(nuevo) = foto.anotacion_de.as_ref().and_then(|original| nuevos.get(original)) {
+            foto.anotacion_de = Some(nuevo.clone());
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{Date, Promise};
+
+use crate::model::{OrigenUbicacion, Ubicacion};
+use crate::storage;
+
+// --- Geolocalización del pozo ---
+
+const TIEMPO_MAXIMO_GPS_MS: u32 = 30_000;
+// Última ubicación registrada en este dispositivo, para centrar el mapa.
+const CLAVE_ULTIMA_UBICACION: &str = "ac-pwa/ultima-ubicacion";
+// Umbrales de precisión (en metros) para orientar al inspector.
+pub const PRECISION_BUENA_M: f64 = 10.0;
+pub const PRECISION_ACEPTABLE_M: f64 = 30.0;
+
+/// Pide una posición de alta precisión al navegador.
+pub async fn capturar() -> Result<Ubicacion, String> {
+    let geolocalizacion = web_sys::window()
+        .ok_or("Ventana no disponible")?
+        .navigator()
+        .geolocation()
+        .map_err(|_| "Este dispositivo no permite obtener la ubicación".to_string())?;
+
+    let opciones = web_sys::PositionOptions::new();
+    opciones.set_enable_high_accuracy(true);
+    opciones.set_timeout(TIEMPO_MAXIMO_GPS_MS);
+    opciones.set_maximum_age(0);
+
+    let promesa = Promise::new(&mut |resolver, rechazar| {
+        if let Err(e) = geolocalizacion.get_current_position_with_error_callback_and_options(&resolver, Some(&rechazar), &opciones) {
+            let _ = rechazar.call1(&JsValue::NULL, &e);
+        }
+    });
+
+    match JsFuture::from(promesa).await {
+        Ok(posicion) => {
+            let posicion = posicion.unchecked_into::<web_sys::Position>();
+            let coordenadas = posicion.coords();
+            Ok(Ubicacion {
+                latitud: coordenadas.latitude(),
+                longitud: coordenadas.longitude(),
+                precision_m: Some(coordenadas.accuracy()),
+                capturada_en: fecha_iso(posicion.timestamp()),
+                origen: OrigenUbicacion::Gps,
+            })
+        }
+        Err(e) => Err(describir_error(&e)),
+    }
+}
+
+/// Ubicación marcada a mano sobre el mapa.
+pub fn manual(latitud: f64, longitud: f64) -> Ubicacion {
+    Ubicacion {
+        latitud,
+        longitud,
+        precision_m: None,
+        capturada_en: fecha_iso(Date::now()),
+        origen: OrigenUbicacion::Manual,
+    }
+}
+
+pub fn recordar(ubicacion: &Ubicacion) {
+    if let Err(e) = storage::guardar(CLAVE_ULTIMA_UBICACION, ubicacion) {
+        log::error!("No se pudo guardar la última ubicación: {}", e);
+    }
+}
+
+pub fn ultima_conocida() -> Option<Ubicacion> {
+    storage::leer(CLAVE_ULTIMA_UBICACION)
+}
+
+/// Interpreta coordenadas escritas o pegadas, p. ej. "4.6097, -74.0817"
+/// (formato de los mapas) o "4,6097 -74,0817".
+pub fn parsear_coordenadas(texto: &str) -> Result<(f64, f64), String> {
+    let texto = texto.trim();
+    let partes: Vec<&str> = if texto.contains(';') {
+        texto.split(';').collect()
+    } else if texto.matches(',').count() == 1 && texto.contains('.') {
+        texto.split(',').collect()
+    } else {
+        texto.split_whitespace().collect()
+    };
+    let numeros: Vec<f64> = partes
+        .iter()
+        .map(|p| p.trim().replace(',', ".").parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| "Escriba latitud y longitud, p. ej. 4.6097, -74.0817".to_string())?;
+    let [latitud, longitud] = numeros[..] else {
+        return Err("Escriba latitud y longitud, p. ej. 4.6097, -74.0817".to_string());
+    };
+    if !(-90.0..=90.0).contains(&latitud) || !(-180.0..=180.0).contains(&longitud) {
+        return Err("Coordenadas fuera de rango".to_string());
+    }
+    Ok((latitud, longitud))
+}
+
+pub fn fecha_iso(ms: f64) -> String {
+    Date::new(&JsValue::from_f64(ms)).to_iso_string().into()
+}
+
+/// Distancia en metros entre dos ubicaciones (fórmula del haversine).
+pub fn distancia_m(a: &Ubicacion, b: &Ubicacion) -> f64 {
+    const RADIO_TIERRA_M: f64 = 6_371_000.0;
+    let (lat_a, lat_b) = (a.latitud.to_radians(), b.latitud.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitud - a.longitud).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * RADIO_TIERRA_M * h.sqrt().asin()
+}
+
+fn describir_error(error: &JsValue) -> String {
+    match error.dyn_ref::<web_sys::PositionError>().map(|e| e.code()) {
+        Some(1) => "Permiso de ubicación denegado. Habilítelo en los ajustes del navegador.".to_string(),
+        Some(2) => "No se pudo determinar la posición. Intente al aire libre.".to_string(),
+        Some(3) => "Se agotó el tiempo esperando al GPS.".to_string(),
+        _ => format!("Error de geolocalización: {:?}", error),
+    }
+}
+
+// --- Conversión entre coordenadas y teselas del mapa (proyección Web Mercator) ---
+
+pub const TAMANO_TESELA: f64 = 256.0;
+
+/// Posición global en píxeles de una coordenada para el nivel de zoom dado.
+pub fn a_pixeles(latitud: f64, longitud: f64, zoom: u32) -> (f64, f64) {
+    let escala = TAMANO_TESELA * 2f64.powi(zoom as i32);
+    let x = (longitud + 180.0) / 360.0 * escala;
+    let lat = latitud.to_radians();
+    let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / std::f64::consts::PI) / 2.0 * escala;
+    (x, y)
+}
+
+/// Inversa de `a_pixeles`.
+pub fn desde_pixeles(x: f64, y: f64, zoom: u32) -> (f64, f64) {
+    let escala = TAMANO_TESELA * 2f64.powi(zoom as i32);
+    let longitud = x / escala * 360.0 - 180.0;
+    let n = std::f64::consts::PI * (1.0 - 2.0 * y / escala);
+    let latitud = n.sinh().atan().to_degrees();
+    (latitud, longitud)
+}
@@ -0,0 +1,37 @@
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::Date;
+
+use crate::model::TipoSistema;
+use crate::outbox::EntradaOutbox;
+
+// --- Historial de inspecciones ---
+// Las encuestas enviadas permanecen en la bandeja de salida (sin sus fotos),
+// así que el historial es una vista filtrada de esas entradas.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FiltroHistorial {
+    /// Texto a buscar dentro del número de pozo.
+    pub texto: String,
+    pub tipo_sistema: Option<TipoSistema>,
+    /// Día en formato `AAAA-MM-DD`, como lo entrega `<input type="date">`.
+    pub fecha: String,
+}
+
+impl FiltroHistorial {
+    pub fn coincide(&self, entrada: &EntradaOutbox) -> bool {
+        let texto = self.texto.trim().to_lowercase();
+        if !texto.is_empty() && !entrada.encuesta.pozo_numero.to_lowercase().contains(&texto) {
+            return false;
+        }
+        if self.tipo_sistema.is_some() && entrada.encuesta.tipo_sistema != self.tipo_sistema {
+            return false;
+        }
+        self.fecha.is_empty() || fecha_local(entrada.creada_en) == self.fecha
+    }
+}
+
+/// Día local (`AAAA-MM-DD`) de una marca de tiempo en milisegundos.
+pub fn fecha_local(ms: f64) -> String {
+    let fecha = Date::new(&JsValue::from_f64(ms));
+    format!("{:04}-{:02}-{:02}", fecha.get_full_year(), fecha.get_month() + 1, fecha.get_date())
+}
@@ -0,0 +1,362 @@
+use ::base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{Array, Promise, Uint8Array};
+use web_sys::{Blob, BlobPropertyBag, CanvasRenderingContext2d, HtmlCanvasElement, ImageBitmap};
+
+use crate::model::{opciones, Opcion};
+
+// --- Compresión de fotos antes de guardarlas ---
+// Las fotos del teléfono pesan varios MB; se reducen con un canvas del
+// navegador y se vuelven a codificar. Al redibujar se pierden los metadatos
+// EXIF: si la política es conservarlos, se copian del original (solo JPEG).
+
+opciones!(FormatoImagen {
+    Jpeg => "JPEG",
+    Webp => "WebP",
+});
+
+opciones!(PoliticaExif {
+    Eliminar => "Eliminar",
+    Conservar => "Conservar",
+});
+
+impl FormatoImagen {
+    fn mime(&self) -> &'static str {
+        match self {
+            FormatoImagen::Jpeg => "image/jpeg",
+            FormatoImagen::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FormatoImagen::Jpeg => "jpg",
+            FormatoImagen::Webp => "webp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AjustesImagen {
+    /// Lado mayor de la imagen resultante, en píxeles.
+    pub dimension_maxima: u32,
+    /// Calidad de codificación entre 0 y 1.
+    pub calidad: f64,
+    pub formato: FormatoImagen,
+    pub exif: PoliticaExif,
+}
+
+impl Default for AjustesImagen {
+    fn default() -> Self {
+        AjustesImagen {
+            dimension_maxima: 1600,
+            calidad: 0.8,
+            formato: FormatoImagen::Jpeg,
+            exif: PoliticaExif::Eliminar,
+        }
+    }
+}
+
+pub struct ImagenComprimida {
+    pub nombre: String,
+    pub contenido: Vec<u8>,
+}
+
+/// Reduce y recodifica la imagen según los ajustes.
+pub async fn comprimir(nombre: &str, original: &[u8], ajustes: &AjustesImagen) -> Result<ImagenComprimida, String> {
+    let bitmap = decodificar(original).await?;
+    let (ancho, alto) = dimensiones(bitmap.width(), bitmap.height(), ajustes.dimension_maxima);
+    let (canvas, contexto) = crear_canvas(ancho, alto)?;
+    contexto
+        .draw_image_with_image_bitmap_and_dw_and_dh(&bitmap, 0.0, 0.0, ancho as f64, alto as f64)
+        .map_err(describir)?;
+    bitmap.close();
+
+    let (mut contenido, formato) = codificar_canvas(&canvas, ajustes).await?;
+
+    if ajustes.exif == PoliticaExif::Conservar && formato == FormatoImagen::Jpeg {
+        if let Some(exif) = segmento_exif(original) {
+            contenido = insertar_exif(&contenido, exif);
+        }
+    }
+
+    Ok(ImagenComprimida { nombre: cambiar_extension(nombre, formato.extension()), contenido })
+}
+
+/// URL `data:` para mostrar la imagen directamente desde sus bytes.
+pub fn url_datos(nombre: &str, contenido: &[u8]) -> String {
+    let extension = nombre.rsplit_once('.').map(|(_, e)| e.to_lowercase()).unwrap_or_default();
+    let mime = match extension.as_str() {
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        _ => "image/jpeg",
+    };
+    format!("data:{};base64,{}", mime, STANDARD.encode(contenido))
+}
+
+/// Decodifica los bytes de una imagen para dibujarla en un canvas.
+pub async fn decodificar(bytes: &[u8]) -> Result<ImageBitmap, String> {
+    let window = web_sys::window().ok_or("Ventana no disponible")?;
+    let blob = crear_blob(bytes)?;
+    JsFuture::from(window.create_image_bitmap_with_blob(&blob).map_err(describir)?)
+        .await
+        .map(|bitmap| bitmap.unchecked_into::<ImageBitmap>())
+        .map_err(|_| "El archivo no es una imagen válida".to_string())
+}
+
+/// Tamaño final respetando la proporción y el lado máximo configurado.
+pub fn dimensiones(ancho: u32, alto: u32, maxima: u32) -> (u32, u32) {
+    let escala = (maxima as f64 / ancho.max(alto).max(1) as f64).min(1.0);
+    (((ancho as f64 * escala).round() as u32).max(1), ((alto as f64 * escala).round() as u32).max(1))
+}
+
+pub fn crear_canvas(ancho: u32, alto: u32) -> Result<(HtmlCanvasElement, CanvasRenderingContext2d), String> {
+    let canvas = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or("Documento no disponible")?
+        .create_element("canvas")
+        .map_err(describir)?
+        .unchecked_into::<HtmlCanvasElement>();
+    canvas.set_width(ancho);
+    canvas.set_height(alto);
+    let contexto = canvas
+        .get_context("2d")
+        .map_err(describir)?
+        .ok_or("Canvas 2D no disponible")?
+        .unchecked_into::<CanvasRenderingContext2d>();
+    Ok((canvas, contexto))
+}
+
+/// Codifica el contenido del canvas con el formato y la calidad configurados.
+pub async fn codificar_canvas(canvas: &HtmlCanvasElement, ajustes: &AjustesImagen) -> Result<(Vec<u8>, FormatoImagen), String> {
+    let mut formato = ajustes.formato;
+    let mut resultado = codificar(canvas, formato, ajustes.calidad).await?;
+    // Safari no codifica WebP y devuelve PNG en su lugar; en ese caso se usa JPEG.
+    if resultado.type_() != formato.mime() {
+        formato = FormatoImagen::Jpeg;
+        resultado = codificar(canvas, formato, ajustes.calidad).await?;
+    }
+    let buffer = JsFuture::from(resultado.array_buffer()).await.map_err(describir)?;
+    Ok((Uint8Array::new(&buffer).to_vec(), formato))
+}
+
+fn crear_blob(bytes: &[u8]) -> Result<Blob, String> {
+    let partes = Array::of1(&Uint8Array::from(bytes));
+    Blob::new_with_u8_array_sequence_and_options(&partes, &BlobPropertyBag::new()).map_err(describir)
+}
+
+async fn codificar(canvas: &HtmlCanvasElement, formato: FormatoImagen, calidad: f64) -> Result<Blob, String> {
+    let promesa = Promise::new(&mut |resolver, rechazar| {
+        let al_terminar = Closure::once_into_js(move |blob: JsValue| {
+            let _ = resolver.call1(&JsValue::NULL, &blob);
+        });
+        if let Err(e) = canvas.to_blob_with_type_and_encoder_options(al_terminar.unchecked_ref(), formato.mime(), &JsValue::from_f64(calidad)) {
+            let _ = rechazar.call1(&JsValue::NULL, &e);
+        }
+    });
+    let blob = JsFuture::from(promesa).await.map_err(describir)?;
+    blob.dyn_into::<Blob>().map_err(|_| "No se pudo codificar la imagen".to_string())
+}
+
+/// Nombre con el que se guarda una foto nueva: un UUID con la extensión del
+/// archivo original. El selector de iOS llama "image.jpg" a todas las fotos,
+/// así que el nombre original no sirve para distinguirlas.
+pub fn nombre_unico(original: &str) -> String {
+    cambiar_extension(&crate::outbox::nuevo_uuid(), &extension_de(original))
+}
+
+fn extension_de(nombre: &str) -> String {
+    nombre
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .filter(|e| !e.is_empty() && e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "jpg".to_string())
+}
+
+fn cambiar_extension(nombre: &str, extension: &str) -> String {
+    let base = nombre.rsplit_once('.').map(|(base, _)| base).unwrap_or(nombre);
+    format!("{}.{}", base, extension)
+}
+
+pub(crate) fn describir(error: JsValue) -> String {
+    format!("{:?}", error)
+}
+
+// --- Metadatos EXIF en JPEG ---
+// Un JPEG empieza con SOI (FF D8) seguido de segmentos `FF xx <longitud>`.
+// El EXIF va en un segmento APP1 (FF E1) cuyo contenido empieza con "Exif\0\0".
+
+const ETIQUETA_ORIENTACION: u16 = 0x0112;
+
+/// Segmento APP1 completo (marcador incluido) con el EXIF del original.
+fn segmento_exif(jpeg: &[u8]) -> Option<&[u8]> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut i = 2;
+    while i + 4 <= jpeg.len() && jpeg[i] == 0xFF {
+        let marcador = jpeg[i + 1];
+        // SOS: empiezan los datos de la imagen, ya no hay más metadatos.
+        if marcador == 0xDA {
+            return None;
+        }
+        // La longitud incluye sus propios dos bytes; menos que eso es un JPEG dañado.
+        let longitud = u16::from_be_bytes([jpeg[i + 2], jpeg[i + 3]]) as usize;
+        let fin = i + 2 + longitud;
+        if longitud < 2 || fin > jpeg.len() {
+            return None;
+        }
+        if marcador == 0xE1 && jpeg[i + 4..fin].starts_with(b"Exif\0\0") {
+            return Some(&jpeg[i..fin]);
+        }
+        i = fin;
+    }
+    None
+}
+
+/// Inserta el segmento EXIF después del SOI. El navegador ya aplicó la
+/// orientación al dibujar, así que se fija en 1 para no rotar dos veces.
+fn insertar_exif(jpeg: &[u8], exif: &[u8]) -> Vec<u8> {
+    let mut exif = exif.to_vec();
+    normalizar_orientacion(&mut exif[10..]);
+    let mut resultado = Vec::with_capacity(jpeg.len() + exif.len());
+    resultado.extend_from_slice(&jpeg[..2]);
+    resultado.extend_from_slice(&exif);
+    resultado.extend_from_slice(&jpeg[2..]);
+    resultado
+}
+
+/// Pone la orientación del IFD0 en 1 dentro de un bloque TIFF.
+fn normalizar_orientacion(tiff: &mut [u8]) {
+    let intel = tiff.starts_with(b"II");
+    let leer16 = |b: &[u8], i: usize| {
+        let par = [b[i], b[i + 1]];
+        if intel { u16::from_le_bytes(par) } else { u16::from_be_bytes(par) }
+    };
+    if tiff.len() < 8 {
+        return;
+    }
+    let ifd = if intel {
+        u32::from_le_bytes([tiff[4], tiff[5], tiff[6], tiff[7]])
+    } else {
+        u32::from_be_bytes([tiff[4], tiff[5], tiff[6], tiff[7]])
+    } as usize;
+    if ifd.checked_add(2).is_none_or(|fin| fin > tiff.len()) {
+        return;
+    }
+    let entradas = leer16(tiff, ifd) as usize;
+    for n in 0..entradas {
+        let entrada = ifd + 2 + n * 12;
+        if entrada + 12 > tiff.len() {
+            return;
+        }
+        if leer16(tiff, entrada) == ETIQUETA_ORIENTACION {
+            let uno = if intel { 1u16.to_le_bytes() } else { 1u16.to_be_bytes() };
+            tiff[entrada + 8..entrada + 10].copy_from_slice(&uno);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insertar_exif, normalizar_orientacion, segmento_exif, ETIQUETA_ORIENTACION};
+
+    // Bloque TIFF mínimo con un IFD0 de una sola entrada: la orientación.
+    fn tiff(intel: bool, orientacion: u16) -> Vec<u8> {
+        let a16 = |v: u16| if intel { v.to_le_bytes() } else { v.to_be_bytes() };
+        let a32 = |v: u32| if intel { v.to_le_bytes() } else { v.to_be_bytes() };
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if intel { b"II" } else { b"MM" });
+        tiff.extend_from_slice(&a16(42));
+        tiff.extend_from_slice(&a32(8));
+        tiff.extend_from_slice(&a16(1));
+        tiff.extend_from_slice(&a16(ETIQUETA_ORIENTACION));
+        tiff.extend_from_slice(&a16(3));
+        tiff.extend_from_slice(&a32(1));
+        tiff.extend_from_slice(&a16(orientacion));
+        tiff.extend_from_slice(&[0, 0]);
+        tiff.extend_from_slice(&a32(0));
+        tiff
+    }
+
+    fn orientacion(tiff: &[u8], intel: bool) -> u16 {
+        let par = [tiff[18], tiff[19]];
+        if intel { u16::from_le_bytes(par) } else { u16::from_be_bytes(par) }
+    }
+
+    fn app1(tiff: &[u8]) -> Vec<u8> {
+        let mut segmento = vec![0xFF, 0xE1];
+        segmento.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+        segmento.extend_from_slice(b"Exif\0\0");
+        segmento.extend_from_slice(tiff);
+        segmento
+    }
+
+    fn jpeg(segmentos: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend_from_slice(segmentos);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn encuentra_el_segmento_exif() {
+        let segmento = app1(&tiff(true, 6));
+        let original = jpeg(&segmento);
+        assert_eq!(segmento_exif(&original), Some(segmento.as_slice()));
+    }
+
+    #[test]
+    fn app1_truncado_no_tiene_exif() {
+        let segmento = app1(&tiff(true, 6));
+        let mut original = jpeg(&segmento);
+        original.truncate(2 + segmento.len() - 4);
+        assert_eq!(segmento_exif(&original), None);
+    }
+
+    #[test]
+    fn longitud_de_segmento_menor_que_dos_no_tiene_exif() {
+        for longitud in [0u8, 1] {
+            let original = jpeg(&[0xFF, 0xE0, 0x00, longitud]);
+            assert_eq!(segmento_exif(&original), None);
+        }
+    }
+
+    #[test]
+    fn ifd_fuera_del_bloque_no_modifica_nada() {
+        let mut bloque = tiff(false, 6);
+        bloque[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        let antes = bloque.clone();
+        normalizar_orientacion(&mut bloque);
+        assert_eq!(bloque, antes);
+
+        bloque[4..8].copy_from_slice(&(antes.len() as u32 - 1).to_be_bytes());
+        normalizar_orientacion(&mut bloque);
+        assert_eq!(bloque[8..], antes[8..]);
+    }
+
+    #[test]
+    fn toda_orientacion_queda_en_uno() {
+        for intel in [true, false] {
+            for valor in 1..=8 {
+                let mut bloque = tiff(intel, valor);
+                normalizar_orientacion(&mut bloque);
+                assert_eq!(orientacion(&bloque, intel), 1, "orientación {} ({})", valor, if intel { "II" } else { "MM" });
+            }
+        }
+    }
+
+    #[test]
+    fn insertar_exif_normaliza_la_copia() {
+        let segmento = app1(&tiff(true, 8));
+        let destino = jpeg(&[]);
+        let resultado = insertar_exif(&destino, &segmento);
+        let copiado = segmento_exif(&resultado).expect("el resultado debe conservar el EXIF");
+        assert_eq!(orientacion(&copiado[10..], true), 1);
+        assert_eq!(resultado.len(), destino.len() + segmento.len());
+    }
+}
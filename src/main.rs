@@ -1,24 +1,43 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use dioxus::prelude::*;
+use futures_util::future::{select, Either};
 use futures_util::StreamExt;
+use gloo_timers::future::TimeoutFuture;
 use reqwest::multipart::Part;
+use serde::Serialize;
 
+mod anotacion;
+mod camara;
+mod cambios;
+mod config;
+mod drafts;
+mod fotos_db;
+mod geo;
+mod historial;
+mod imagen;
+mod medidas;
 mod model;
-use model::{Conexion, Encuesta};
-
-// --- Opciones para los menús de selección (sin cambios) ---
-const TIPO_SISTEMA: &[&str] = &["Aguas Lluvia", "Aguas Residuales", "Combinado"];
-const TIPO_POZO: &[&str] = &["Pozo", "Camara", "Alivio"];
-const SI_NO: &[&str] = &["Si", "No"];
-const TAPA_TIPO: &[&str] = &["Ferroconcreto", "Concreto", "Hierro sin Bisagra", "Hierro con bisagra", "Tapa Seguridad", "Tapa en fibra"];
-const ESTADO_BUENO_REGULAR_MALO: &[&str] = &["Bueno", "Regular", "Malo"];
-const DIAGNOSTICO_CAMBIAR_REPARAR: &[&str] = &["Cambiar", "Reparar", "No Requiere"];
-const CARGUE_ESTADO: &[&str] = &["Bueno", "Regular", "Malo", "Grietas", "Partido", "Hundido"];
-const CILINDRO_MATERIAL: &[&str] = &["Mamposteria", "Concreto", "GRP"];
-const CILINDRO_ESTADO: &[&str] = &["Bueno", "Regular", "Malo", "Grietas", "Partido", "Huecos", "Sin Pañete", "Otro"];
-const CANUELA_ESTADO: &[&str] = &["Bueno", "Regular", "Malo", "Sedimentada", "Desgastada", "Socavacion"];
-const ESCALONES_TIPO: &[&str] = &["Escalones", "Ladrillos"];
-const ESCALONES_ESTADO: &[&str] = &["Bueno", "Regular", "Malo", "Doblados", "Faltan", "Corroidos"];
-const ESTADO_GENERAL_POZO: &[&str] = &["Infiltracion", "Represado", "Con basura", "Raices", "Fuera de Servicio", "Lleno de tierra"];
+mod outbox;
+mod pasos;
+mod pwa;
+mod red;
+mod rules;
+mod storage;
+mod validation;
+use anotacion::{Herramienta, Punto, Trazo};
+use cambios::{Atajo, HistorialCambios};
+use config::{Configuracion, Perfil};
+use drafts::{Borradores, ResumenBorrador};
+use historial::FiltroHistorial;
+use imagen::{FormatoImagen, ImagenComprimida, PoliticaExif};
+use medidas::Unidad;
+use model::{CalculoConexion, ComponenteFoto, Conexion, DireccionConexion, Encuesta, Foto, Opcion, SentidoFlujo, Ubicacion};
+use outbox::{EntradaOutbox, EstadoEnvio, Outbox};
+use pasos::Paso;
+use validation::{error_de, Campo, CampoConexion};
 
 const TAILWIND_CSS: &str = include_str!("../public/css/tailwind.css");
 
@@ -27,15 +46,57 @@ const TAILWIND_CSS: &str = include_str!("../public/css/tailwind.css");
 enum AuthStatus {
     Authenticated,
     Unauthenticated,
+    // Sin conexión no se puede verificar la sesión; se permite trabajar
+    // y las encuestas esperan en la bandeja de salida.
+    Offline,
+}
+
+// --- Errores posibles al enviar una encuesta ---
+#[derive(Clone, Debug, PartialEq)]
+enum SubmitError {
+    // No se pudo contactar al backend (sin señal, DNS, CORS...).
+    Network(String),
+    // El backend respondió 401: la sesión expiró o fue revocada.
+    Unauthorized,
+    // El backend rechazó los datos (4xx salvo 408/429); reintentar no servirá.
+    Rejected { status: u16, mensaje: String },
+    // El backend pidió esperar (408/429); se reintenta respetando `Retry-After`.
+    Busy { status: u16, espera_ms: Option<f64> },
+    // Error interno del backend (5xx); se puede reintentar.
+    Server { status: u16, mensaje: String },
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Network(e) => write!(f, "Sin conexión con el servidor: {}", e),
+            SubmitError::Unauthorized => write!(f, "La sesión expiró. Inicie sesión nuevamente."),
+            SubmitError::Rejected { status, mensaje } => write!(f, "El servidor rechazó la encuesta ({}): {}", status, mensaje),
+            SubmitError::Busy { status, .. } => write!(f, "El servidor está ocupado ({})", status),
+            SubmitError::Server { status, mensaje } => write!(f, "Error del servidor ({}): {}", status, mensaje),
+        }
+    }
 }
 
-const BACKEND_URL: &str = "http://192.168.128.15:5000";
+// --- Estado del envío en curso, visible en la interfaz ---
+#[derive(Clone, Debug, PartialEq)]
+enum SubmissionState {
+    Idle,
+    Sending,
+    Sent,
+    Failed(SubmitError),
+}
 
 // --- Función para verificar el estado de autenticación (con cache buster) ---
-async fn check_auth_status() -> AuthStatus {
+async fn check_auth_status(configuracion: Configuracion) -> AuthStatus {
+    if !outbox::navegador_en_linea() {
+        log::info!("Sin conexión: se omite la verificación de autenticación.");
+        return AuthStatus::Offline;
+    }
+
     // Usamos la hora actual para evitar la caché del navegador
     let now = web_sys::js_sys::Date::new_0().get_time();
-    let url = format!("{}/auth/status?_={}", BACKEND_URL, now);
+    let url = format!("{}?_={}", configuracion.url("/auth/status"), now);
     
     log::info!("Verificando estado de autenticación en: {}", url);
     match reqwest::get(&url).await {
@@ -47,129 +108,3291 @@ async fn check_auth_status() -> AuthStatus {
                 log::info!("Estado: No Autenticado");
                 AuthStatus::Unauthenticated
             }
-        }
-        Err(e) => {
-            log::error!("No se pudo conectar al backend para verificar la autenticación: {}. Asumiendo no autenticado.", e);
-            AuthStatus::Unauthenticated
+        }
+        Err(e) => {
+            log::error!("No se pudo conectar al backend para verificar la autenticación: {}. Se continúa sin conexión.", e);
+            AuthStatus::Offline
+        }
+    }
+}
+
+// Metadatos de cada foto, en el mismo orden que las partes "fotos" del formulario.
+// Las fotos `en_servidor` de una corrección no llevan parte: el backend conserva
+// el archivo del envío anterior con ese nombre, que es único (ver
+// `imagen::nombre_unico`), así que la referencia no es ambigua.
+#[derive(Serialize)]
+struct MetadatoFoto<'a> {
+    archivo: &'a str,
+    componente: ComponenteFoto,
+    descripcion: &'a str,
+    capturada_en: &'a str,
+    ubicacion: Option<&'a Ubicacion>,
+    anotacion_de: Option<&'a str>,
+    en_servidor: bool,
+}
+
+async fn send_survey_request(configuracion: &Configuracion, encuesta: Encuesta, files_content: Vec<Foto>) -> Result<String, SubmitError> {
+    let client = reqwest::Client::new();
+    // Una corrección se envía con PUT sobre el UUID para reemplazar el registro anterior.
+    let (metodo, url) = if encuesta.es_correccion() {
+        (reqwest::Method::PUT, configuracion.url(&format!("/ingestar-encuesta/{}", encuesta.uuid)))
+    } else {
+        (reqwest::Method::POST, configuracion.url("/ingestar-encuesta"))
+    };
+    let encuesta_clave = encuesta.clave_idempotencia();
+    let json_data = serde_json::to_string(&encuesta).expect("No se pudo serializar la encuesta a JSON");
+
+    log::info!("Conexión: Intentando enviar a URL: {}", url);
+    log::info!("Conexión: JSON de la encuesta: {}", json_data);
+
+    let metadatos: Vec<MetadatoFoto> = files_content
+        .iter()
+        .map(|foto| MetadatoFoto {
+            archivo: &foto.nombre,
+            componente: foto.componente,
+            descripcion: &foto.descripcion,
+            capturada_en: &foto.capturada_en,
+            ubicacion: foto.ubicacion.as_ref(),
+            anotacion_de: foto.anotacion_de.as_deref(),
+            en_servidor: foto.en_servidor,
+        })
+        .collect();
+    let json_fotos = serde_json::to_string(&metadatos).expect("No se pudieron serializar los metadatos de las fotos");
+
+    let mut form = reqwest::multipart::Form::new().text("data", json_data).text("fotos_metadata", json_fotos);
+
+    for foto in files_content.into_iter().filter(|f| !f.en_servidor) {
+        log::info!("Adjuntando archivo: {} ({})", foto.nombre, foto.componente);
+        let part = Part::bytes(foto.contenido).file_name(foto.nombre);
+        form = form.part("fotos", part);
+    }
+
+    // El backend descarta los envíos repetidos con la misma clave (reintentos tras un corte).
+    let response = client
+        .request(metodo, url)
+        .header("Idempotency-Key", encuesta_clave)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| SubmitError::Network(e.to_string()))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::REQUEST_TIMEOUT || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let espera_ms = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|valor| valor.to_str().ok())
+            .and_then(|valor| espera_retry_after(valor, outbox::ahora()));
+        log::warn!("El servidor respondió {}; se reintentará (Retry-After: {:?} ms).", status, espera_ms);
+        return Err(SubmitError::Busy { status: status.as_u16(), espera_ms });
+    }
+    if status.is_success() {
+        return Ok(response.text().await.unwrap_or_default());
+    }
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        log::error!("Error 401: No autorizado. El token puede haber expirado o sido revocado. Se necesita re-autenticación.");
+        return Err(SubmitError::Unauthorized);
+    }
+
+    let mensaje = response.text().await.unwrap_or_default();
+    log::error!("El servidor respondió {}: {}", status, mensaje);
+    if status.is_client_error() {
+        Err(SubmitError::Rejected { status: status.as_u16(), mensaje })
+    } else {
+        Err(SubmitError::Server { status: status.as_u16(), mensaje })
+    }
+}
+
+// `Retry-After` viene en segundos o como fecha HTTP; se devuelve la espera en milisegundos.
+fn espera_retry_after(valor: &str, ahora: f64) -> Option<f64> {
+    let valor = valor.trim();
+    if !valor.is_empty() && valor.bytes().all(|b| b.is_ascii_digit()) {
+        return valor.parse::<u64>().ok().map(|segundos| segundos as f64 * 1_000.0);
+    }
+    fecha_http_ms(valor).map(|fecha| (fecha - ahora).max(0.0))
+}
+
+const MESES_HTTP: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Fecha HTTP ("Sun, 06 Nov 1994 08:49:37 GMT") en milisegundos desde la época.
+// Los formatos obsoletos que también admite la norma no se interpretan.
+fn fecha_http_ms(valor: &str) -> Option<f64> {
+    let (_, fecha) = valor.split_once(", ")?;
+    let partes: Vec<&str> = fecha.split_whitespace().collect();
+    let [dia, mes, anio, hora, "GMT"] = partes.as_slice() else { return None };
+    let mes = MESES_HTTP.iter().position(|m| m == mes)? as i64 + 1;
+    let dia: i64 = dia.parse().ok().filter(|d| (1..=31).contains(d))?;
+    let anio: i64 = anio.parse().ok()?;
+    let hms: Vec<i64> = hora.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let [h, m, s] = hms.as_slice() else { return None };
+    if !(0..24).contains(h) || !(0..60).contains(m) || !(0..=60).contains(s) {
+        return None;
+    }
+    let segundos = dias_desde_epoca(anio, mes, dia) * 86_400 + h * 3_600 + m * 60 + s;
+    Some(segundos as f64 * 1_000.0)
+}
+
+// Días entre el 1970-01-01 y la fecha del calendario gregoriano indicada.
+fn dias_desde_epoca(anio: i64, mes: i64, dia: i64) -> i64 {
+    let anio = if mes <= 2 { anio - 1 } else { anio };
+    let era = anio.div_euclid(400);
+    let anio_de_era = anio - era * 400;
+    let dia_del_anio = (153 * ((mes + 9) % 12) + 2) / 5 + dia - 1;
+    let dia_de_era = anio_de_era * 365 + anio_de_era / 4 - anio_de_era / 100 + dia_del_anio;
+    era * 146_097 + dia_de_era - 719_468
+}
+
+// --- Acciones que recibe la corrutina de la bandeja de salida ---
+enum AccionOutbox {
+    // La encuesta, sus fotos y el borrador del que sale, que se descarta al quedar guardada.
+    Encolar(Box<Encuesta>, Vec<Foto>, u64),
+    Procesar,
+    ReintentarFallidas,
+}
+
+// Pausa sin ediciones tras la que se guarda el borrador.
+const ESPERA_AUTOGUARDADO_MS: u32 = 400;
+
+// --- Notificaciones emergentes (toasts) ---
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TipoToast {
+    Exito,
+    Error,
+    Info,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Toast {
+    id: u64,
+    tipo: TipoToast,
+    mensaje: String,
+}
+
+const DURACION_TOAST_MS: u32 = 6_000;
+
+// Muestra un toast y lo retira automáticamente pasado un tiempo.
+fn notificar(mut toasts: Signal<Vec<Toast>>, tipo: TipoToast, mensaje: impl Into<String>) {
+    let id = toasts.peek().iter().map(|t| t.id + 1).max().unwrap_or(0);
+    toasts.write().push(Toast { id, tipo, mensaje: mensaje.into() });
+    spawn(async move {
+        TimeoutFuture::new(DURACION_TOAST_MS).await;
+        toasts.write().retain(|t| t.id != id);
+    });
+}
+
+// Persiste la bandeja de salida y avisa si el navegador no tiene espacio.
+fn guardar_bandeja(mut outbox: Signal<Outbox>, toasts: Signal<Vec<Toast>>) {
+    let guardado = outbox.write().guardar();
+    if let Err(e) = guardado {
+        log::error!("{}", e);
+        notificar(toasts, TipoToast::Error, format!("{}. Libere espacio o se podrían perder encuestas pendientes.", e));
+    }
+}
+
+// Borra de IndexedDB las imágenes que ya no usan la bandeja, los borradores
+// ni el formulario abierto.
+async fn limpiar_fotos(outbox: Signal<Outbox>, borradores: Signal<Borradores>, fotos: Signal<Vec<Foto>>) {
+    let referenciadas = move || {
+        let mut nombres = outbox.peek().fotos_referenciadas();
+        nombres.extend(borradores.peek().fotos_referenciadas());
+        nombres.extend(fotos.peek().iter().map(|f| f.nombre.clone()));
+        nombres
+    };
+    match fotos_db::conservar_solo(referenciadas).await {
+        Ok(0) => {}
+        Ok(borradas) => log::info!("Se liberaron {} imágenes que ya no se usan", borradas),
+        Err(e) => log::error!("No se pudieron limpiar las fotos guardadas: {}", e),
+    }
+}
+
+// Pide confirmación al usuario con el diálogo nativo del navegador.
+fn confirmar(mensaje: &str) -> bool {
+    web_sys::window()
+        .and_then(|w| w.confirm_with_message(mensaje).ok())
+        .unwrap_or(false)
+}
+
+// Lleva la vista hasta el elemento indicado y le da el foco.
+fn desplazar_a(id: &str) {
+    use wasm_bindgen::JsCast;
+    let Some(elemento) = web_sys::window().and_then(|w| w.document()).and_then(|d| d.get_element_by_id(id)) else {
+        return;
+    };
+    let opciones = web_sys::ScrollIntoViewOptions::new();
+    opciones.set_behavior(web_sys::ScrollBehavior::Smooth);
+    opciones.set_block(web_sys::ScrollLogicalPosition::Center);
+    elemento.scroll_into_view_with_scroll_into_view_options(&opciones);
+    if let Ok(html) = elemento.dyn_into::<web_sys::HtmlElement>() {
+        let _ = html.focus();
+    }
+}
+
+// Fecha y hora local legible a partir de milisegundos desde la época.
+fn formatear_fecha(ms: f64) -> String {
+    let fecha = web_sys::js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(ms));
+    fecha.to_locale_string("es-CO", &wasm_bindgen::JsValue::UNDEFINED).into()
+}
+
+fn main() {
+    wasm_logger::init(wasm_logger::Config::default());
+    launch(App);
+}
+
+#[allow(non_snake_case)]
+fn App() -> Element {
+    let configuracion = use_resource(config::cargar);
+    // La verificación de sesión espera a que la configuración esté cargada.
+    let mut auth_status = use_resource(move || async move {
+        let configuracion = configuracion.read().clone()?;
+        Some(check_auth_status(configuracion).await)
+    });
+    let mut mostrar_ajustes = use_signal(|| false);
+    let mut encuesta = use_signal(|| Encuesta::nueva(outbox::nuevo_uuid()));
+    let mut uploaded_files_content = use_signal(Vec::<Foto>::new);
+    let mut outbox = use_signal(Outbox::cargar);
+    let mut estado_envio = use_signal(|| SubmissionState::Idle);
+    let toasts = use_signal(Vec::<Toast>::new);
+    // Se activa cuando el backend responde 401 durante un envío.
+    let mut sesion_expirada = use_signal(|| false);
+    let mut borradores = use_signal(Borradores::cargar);
+    let mut borrador_id = use_signal(|| drafts::nuevo_id(outbox::ahora()));
+    // Si hay borradores guardados, al iniciar se ofrece continuarlos.
+    let mut mostrar_borradores = use_signal(move || !borradores.peek().is_empty());
+    let mut mostrar_historial = use_signal(|| false);
+    let mut detalle_historial = use_signal(|| None::<u64>);
+    // Antes de enviar se muestra un resumen para revisar la encuesta completa,
+    // con los valores calculados de las conexiones.
+    let mut resumen = use_signal(|| None::<Encuesta>);
+    let mut seccion_a_editar = use_signal(|| None::<Paso>);
+    // Se recalcula con cada cambio en los borradores o la bandeja, para que la
+    // red de pozos del formulario no quede desactualizada.
+    let guardadas = use_memo(move || Rc::new(red::encuestas_guardadas(&borradores.read(), &outbox.read())));
+
+    // Autoguardado: las ediciones de la encuesta o de las fotos se persisten en
+    // el borrador activo cuando el inspector deja de escribir un momento. Cada
+    // edición adelanta el turno y cancela el guardado anterior que aún espera.
+    let mut turno_encuesta = use_signal(|| 0u64);
+    use_effect(move || {
+        let _ = (encuesta.read(), borrador_id.read());
+        let turno = *turno_encuesta.peek() + 1;
+        turno_encuesta.set(turno);
+        spawn(async move {
+            TimeoutFuture::new(ESPERA_AUTOGUARDADO_MS).await;
+            if *turno_encuesta.peek() != turno {
+                return;
+            }
+            let datos = encuesta.peek().clone();
+            borradores.write().guardar_encuesta(*borrador_id.peek(), &datos, outbox::ahora());
+        });
+    });
+    // Las imágenes nuevas van a IndexedDB antes que los metadatos, que son lo
+    // único que se reescribe al editar una descripción.
+    let mut turno_fotos = use_signal(|| 0u64);
+    use_effect(move || {
+        let _ = (uploaded_files_content.read(), borrador_id.read());
+        let turno = *turno_fotos.peek() + 1;
+        turno_fotos.set(turno);
+        spawn(async move {
+            TimeoutFuture::new(ESPERA_AUTOGUARDADO_MS).await;
+            if *turno_fotos.peek() != turno {
+                return;
+            }
+            let id = *borrador_id.peek();
+            let (nuevas, metadatos): (Vec<Foto>, Vec<Foto>) = {
+                let fotos = uploaded_files_content.peek();
+                let nuevas = fotos.iter().filter(|f| !f.contenido.is_empty() && !fotos_db::ya_guardada(&f.nombre)).cloned().collect();
+                (nuevas, fotos.iter().map(Foto::sin_contenido).collect())
+            };
+            if let Err(e) = fotos_db::guardar_fotos(&nuevas).await {
+                log::error!("{}", e);
+                notificar(toasts, TipoToast::Error, format!("{}. Libere espacio para no perder las fotos del borrador.", e));
+                return;
+            }
+            if *turno_fotos.peek() == turno {
+                borradores.write().guardar_fotos(id, &metadatos, outbox::ahora());
+            }
+        });
+    });
+
+    // Deshacer/rehacer sobre la encuesta y sus fotos.
+    let mut cambios = use_signal(|| HistorialCambios::new(config::leer_ajustes().profundidad_deshacer()));
+    use_effect(move || {
+        let datos = encuesta.read().clone();
+        let fotos = uploaded_files_content.read();
+        cambios.write().registrar(borrador_id(), &datos, &fotos, outbox::ahora());
+    });
+    let mut aplicar_cambio = move |atajo: Atajo| {
+        let estado = match atajo {
+            Atajo::Deshacer => cambios.write().deshacer(),
+            Atajo::Rehacer => cambios.write().rehacer(),
+        };
+        if let Some((datos, fotos)) = estado {
+            encuesta.set(datos);
+            uploaded_files_content.set(fotos);
+        }
+    };
+
+    // Los atajos de teclado solo actúan con el formulario en pantalla.
+    let atajos = use_coroutine(move |mut rx: UnboundedReceiver<Atajo>| async move {
+        while let Some(atajo) = rx.next().await {
+            let formulario_visible = !mostrar_ajustes()
+                && detalle_historial().is_none()
+                && !mostrar_historial()
+                && !mostrar_borradores()
+                && resumen.read().is_none();
+            if formulario_visible {
+                aplicar_cambio(atajo);
+            }
+        }
+    });
+    use_hook(move || cambios::al_presionar_atajo(move |atajo| atajos.send(atajo)));
+
+    let mut nueva_inspeccion = move || {
+        borrador_id.set(drafts::nuevo_id(outbox::ahora()));
+        encuesta.set(Encuesta::nueva(outbox::nuevo_uuid()));
+        uploaded_files_content.set(Vec::new());
+        seccion_a_editar.set(None);
+    };
+
+    // Vacía la bandeja de salida: envía lo pendiente y duerme hasta el
+    // próximo reintento o hasta recibir una nueva acción.
+    let send_survey = use_coroutine(move |mut rx: UnboundedReceiver<AccionOutbox>| async move {
+        // Las bandejas de versiones anteriores traen las imágenes en localStorage;
+        // se pasan a IndexedDB antes de volver a guardar la bandeja.
+        let anteriores = outbox.peek().fotos_sin_migrar();
+        if !anteriores.is_empty() {
+            match fotos_db::guardar_fotos(&anteriores).await {
+                Ok(()) => {
+                    outbox.write().liberar_contenido();
+                    guardar_bandeja(outbox, toasts);
+                }
+                Err(e) => log::error!("No se pudieron migrar las fotos de la bandeja: {}", e),
+            }
+        }
+        limpiar_fotos(outbox, borradores, uploaded_files_content).await;
+
+        // Tras un 401 se deja de enviar hasta que el usuario vuelva a iniciar sesión.
+        let mut autorizado = true;
+        loop {
+            let mut enviadas = false;
+            while autorizado && outbox::navegador_en_linea() {
+                let siguiente = outbox.read().siguiente_lista(outbox::ahora());
+                let Some(id) = siguiente else { break };
+                let Some(entrada) = outbox.read().entrada(id).cloned() else { break };
+                let pozo = entrada.encuesta.pozo_numero.clone();
+
+                let mut adjuntos = entrada.adjuntos;
+                if let Err(e) = fotos_db::cargar_contenido(&mut adjuntos).await {
+                    log::error!("No se pudieron leer las fotos de la encuesta {}: {}", id, e);
+                    outbox.write().marcar_fallo(id, e, outbox::ahora(), None);
+                    guardar_bandeja(outbox, toasts);
+                    continue;
+                }
+                if let Some(faltante) = adjuntos.iter().find(|f| fotos_db::le_falta_contenido(f)) {
+                    let error = format!("La foto {} ya no está en el dispositivo", faltante.nombre);
+                    log::error!("{}", error);
+                    outbox.write().marcar_rechazada(id, error.clone());
+                    notificar(toasts, TipoToast::Error, error);
+                    guardar_bandeja(outbox, toasts);
+                    continue;
+                }
+
+                log::info!("Enviando encuesta {} (intento {})...", id, entrada.intentos + 1);
+                estado_envio.set(SubmissionState::Sending);
+                let destino = configuracion.peek().clone().unwrap_or_default();
+                match send_survey_request(&destino, entrada.encuesta, adjuntos).await {
+                    Ok(respuesta) => {
+                        enviadas = true;
+                        log::info!("¡Encuesta enviada con éxito!");
+                        outbox.write().marcar_enviada(id, respuesta, outbox::ahora());
+                        estado_envio.set(SubmissionState::Sent);
+                        notificar(toasts, TipoToast::Exito, format!("Encuesta del pozo {} enviada con éxito.", pozo));
+                    }
+                    Err(SubmitError::Unauthorized) => {
+                        // La entrada queda pendiente y se enviará después de iniciar sesión.
+                        autorizado = false;
+                        estado_envio.set(SubmissionState::Failed(SubmitError::Unauthorized));
+                        notificar(toasts, TipoToast::Error, SubmitError::Unauthorized.to_string());
+                        sesion_expirada.set(true);
+                    }
+                    Err(e @ SubmitError::Rejected { .. }) => {
+                        log::error!("Error al enviar la encuesta: {:?}", e);
+                        outbox.write().marcar_rechazada(id, e.to_string());
+                        notificar(toasts, TipoToast::Error, e.to_string());
+                        estado_envio.set(SubmissionState::Failed(e));
+                    }
+                    Err(e @ SubmitError::Network(_)) => {
+                        log::error!("Error al enviar la encuesta: {:?}", e);
+                        outbox.write().marcar_fallo_de_red(id, e.to_string(), outbox::ahora());
+                        notificar(toasts, TipoToast::Error, format!("{}. Se reintentará automáticamente.", e));
+                        estado_envio.set(SubmissionState::Failed(e));
+                    }
+                    Err(e) => {
+                        log::error!("Error al enviar la encuesta: {:?}", e);
+                        let espera_minima = match e {
+                            SubmitError::Busy { espera_ms, .. } => espera_ms,
+                            _ => None,
+                        };
+                        outbox.write().marcar_fallo(id, e.to_string(), outbox::ahora(), espera_minima);
+                        notificar(toasts, TipoToast::Error, format!("{}. Se reintentará automáticamente.", e));
+                        estado_envio.set(SubmissionState::Failed(e));
+                    }
+                }
+                guardar_bandeja(outbox, toasts);
+            }
+            if enviadas {
+                limpiar_fotos(outbox, borradores, uploaded_files_content).await;
+            }
+
+            // Sin conexión no se programa ningún reintento: el aviso `online`
+            // envía `Procesar` en cuanto vuelve la señal.
+            let espera = if autorizado && outbox::navegador_en_linea() {
+                outbox.read().proxima_espera(outbox::ahora())
+            } else {
+                None
+            };
+            let accion = match espera {
+                Some(ms) => match select(rx.next(), TimeoutFuture::new(ms.ceil() as u32)).await {
+                    Either::Left((accion, _)) => accion,
+                    Either::Right(_) => Some(AccionOutbox::Procesar),
+                },
+                None => rx.next().await,
+            };
+
+            match accion {
+                Some(AccionOutbox::Encolar(encuesta_data, mut files, borrador)) => {
+                    // Las imágenes se escriben primero; la bandeja guarda solo sus metadatos.
+                    if let Err(e) = fotos_db::guardar_fotos(&files).await {
+                        log::error!("{}", e);
+                        notificar(toasts, TipoToast::Error, format!("{}. La encuesta sigue en Borradores; libere espacio e intente de nuevo.", e));
+                        continue;
+                    }
+                    for foto in files.iter_mut() {
+                        foto.contenido = Vec::new();
+                    }
+                    let encolada = outbox.write().encolar(*encuesta_data, files, outbox::ahora());
+                    let id = match encolada {
+                        Ok(id) => id,
+                        Err(e) => {
+                            log::error!("{}", e);
+                            notificar(toasts, TipoToast::Error, format!("{}. La corrección sigue en Borradores.", e));
+                            continue;
+                        }
+                    };
+                    let guardado = outbox.write().guardar();
+                    if let Err(e) = guardado {
+                        // Sin copia en el dispositivo no se intenta enviar: se perdería al recargar.
+                        log::error!("{}", e);
+                        outbox.write().retirar(id);
+                        notificar(toasts, TipoToast::Error, format!("{}. La encuesta sigue en Borradores; libere espacio e intente de nuevo.", e));
+                        continue;
+                    }
+                    // Ya está a salvo en la bandeja; el borrador ya no hace falta.
+                    borradores.write().descartar(borrador);
+                    if !outbox::navegador_en_linea() {
+                        let error = SubmitError::Network("el dispositivo está sin conexión".to_string());
+                        notificar(toasts, TipoToast::Info, "Sin conexión: la encuesta quedó en la bandeja de salida y se enviará al recuperar la señal.");
+                        estado_envio.set(SubmissionState::Failed(error));
+                    }
+                }
+                Some(AccionOutbox::Procesar) => {
+                    let adelantadas = outbox.write().adelantar_pendientes(outbox::ahora());
+                    if adelantadas {
+                        guardar_bandeja(outbox, toasts);
+                    }
+                }
+                Some(AccionOutbox::ReintentarFallidas) => {
+                    let reactivadas = outbox.write().reactivar_fallidas(outbox::ahora());
+                    if reactivadas {
+                        guardar_bandeja(outbox, toasts);
+                    }
+                }
+                None => break,
+            }
+        }
+    });
+
+    // Al recuperar la conexión se vacía la bandeja y, si se arrancó sin red, se verifica la sesión.
+    let reconexion = use_coroutine(move |mut rx: UnboundedReceiver<()>| async move {
+        while rx.next().await.is_some() {
+            send_survey.send(AccionOutbox::Procesar);
+            if auth_status.peek().clone().flatten() == Some(AuthStatus::Offline) {
+                auth_status.restart();
+            }
+        }
+    });
+    use_hook(move || outbox::al_recuperar_conexion(move || reconexion.send(())));
+
+    // Registro del service worker; avisa cuando hay una versión nueva de la aplicación.
+    let mut actualizacion = use_signal(|| None::<web_sys::ServiceWorkerRegistration>);
+    let aviso_actualizacion = use_coroutine(move |mut rx: UnboundedReceiver<web_sys::ServiceWorkerRegistration>| async move {
+        while let Some(registro) = rx.next().await {
+            actualizacion.set(Some(registro));
+        }
+    });
+    use_hook(move || spawn(pwa::registrar(move |registro| aviso_actualizacion.send(registro))));
+
+    // Un `?backend=` desconocido no se usa; se avisa para que se revise en Configuración.
+    use_effect(move || {
+        if let Some(url) = configuracion.read().as_ref().and_then(|c| c.backend_ignorado.clone()) {
+            notificar(toasts, TipoToast::Error, format!("Se ignoró el servidor {} indicado en el enlace. Revíselo en Configuración.", url));
+        }
+    });
+
+    let estado_auth = if sesion_expirada() {
+        Some(AuthStatus::Unauthenticated)
+    } else {
+        auth_status.read().clone().flatten()
+    };
+    let configuracion_activa = configuracion.read().clone();
+
+    rsx! {
+        style { "{TAILWIND_CSS}" }
+
+        main {
+            class: "bg-gradient-to-br from-blue-50 to-indigo-100 min-h-screen font-sans flex items-center justify-center py-8",
+            
+            Toasts { toasts: toasts }
+
+            if let Some(registro) = actualizacion.read().clone() {
+                UpdatePrompt {
+                    on_update: move |_| pwa::activar_actualizacion(&registro),
+                    on_dismiss: move |_| actualizacion.set(None),
+                }
+            }
+
+            if let Some(configuracion) = configuracion_activa.clone() {
+                EnvironmentBadge {
+                    configuracion: configuracion.clone(),
+                    on_open: move |_| mostrar_ajustes.set(true),
+                }
+                if mostrar_ajustes() {
+                    SettingsPage {
+                        configuracion: configuracion,
+                        on_close: move |_| mostrar_ajustes.set(false),
+                    }
+                }
+            }
+
+            match estado_auth {
+                Some(estado @ (AuthStatus::Authenticated | AuthStatus::Offline)) => rsx! {
+                    div {
+                        class: "w-full",
+                        if estado == AuthStatus::Offline {
+                            div {
+                                class: "container mx-auto px-4 sm:px-8 mb-4",
+                                div {
+                                    class: "bg-yellow-100 text-yellow-900 rounded-xl p-4 w-full max-w-4xl mx-auto border border-yellow-300 text-sm",
+                                    "Sin conexión: puede seguir inspeccionando. Las encuestas se enviarán al recuperar la señal."
+                                }
+                            }
+                        }
+                        OutboxStatus {
+                            outbox: outbox,
+                            estado_envio: estado_envio(),
+                            on_retry: move |_| send_survey.send(AccionOutbox::ReintentarFallidas),
+                            on_show_history: move |_| mostrar_historial.set(true),
+                        }
+                        if let Some(entrada) = detalle_historial().and_then(|id| outbox.read().entrada(id).cloned()) {
+                            HistoryDetail {
+                                es_ultima_revision: outbox.read().ultima_revision(&entrada.encuesta.uuid) == entrada.encuesta.revision,
+                                guardadas: guardadas(),
+                                entrada: entrada,
+                                on_close: move |_| detalle_historial.set(None),
+                                on_edit: move |entrada: EntradaOutbox| {
+                                    // La corrección se edita como un borrador más y se envía como nueva revisión.
+                                    // Solo se reabren encuestas del historial de este dispositivo.
+                                    borrador_id.set(drafts::nuevo_id(outbox::ahora()));
+                                    let ultima = outbox.read().ultima_revision(&entrada.encuesta.uuid);
+                                    let mut correccion = entrada.encuesta.nueva_revision(ultima);
+                                    correccion.completar_ids_conexiones(outbox::nuevo_uuid);
+                                    encuesta.set(correccion);
+                                    uploaded_files_content.set(entrada.adjuntos);
+                                    detalle_historial.set(None);
+                                    mostrar_historial.set(false);
+                                    mostrar_borradores.set(false);
+                                },
+                            }
+                        } else if mostrar_historial() {
+                            HistoryList {
+                                entradas: outbox.read().historial(),
+                                on_open: move |id| detalle_historial.set(Some(id)),
+                                on_close: move |_| mostrar_historial.set(false),
+                            }
+                        } else if mostrar_borradores() {
+                            DraftPicker {
+                                borradores: borradores.read().lista(),
+                                on_continue: move |id| {
+                                    let abierto = borradores.read().abrir(id);
+                                    mostrar_borradores.set(false);
+                                    if let Some((mut datos, mut fotos)) = abierto {
+                                        spawn(async move {
+                                            // Sin acceso a las imágenes no se abre: el autoguardado
+                                            // dejaría el borrador sin fotos.
+                                            if let Err(e) = fotos_db::cargar_contenido(&mut fotos).await {
+                                                log::error!("{}", e);
+                                                notificar(toasts, TipoToast::Error, format!("No se pudieron leer las fotos del borrador: {}", e));
+                                                mostrar_borradores.set(true);
+                                                return;
+                                            }
+                                            let antes = fotos.len();
+                                            fotos.retain(|f| !fotos_db::le_falta_contenido(f));
+                                            if fotos.len() < antes {
+                                                notificar(toasts, TipoToast::Error, format!("No se encontraron {} foto(s) del borrador en el dispositivo.", antes - fotos.len()));
+                                            }
+                                            datos.completar_ids_conexiones(outbox::nuevo_uuid);
+                                            borrador_id.set(id);
+                                            encuesta.set(datos);
+                                            uploaded_files_content.set(fotos);
+                                        });
+                                    }
+                                },
+                                on_discard: move |id| {
+                                    if confirmar("¿Descartar este borrador? Se perderán sus datos y fotos.") {
+                                        borradores.write().descartar(id);
+                                        if id == borrador_id() {
+                                            nueva_inspeccion();
+                                        }
+                                    }
+                                },
+                                on_new: move |_| {
+                                    nueva_inspeccion();
+                                    mostrar_borradores.set(false);
+                                },
+                            }
+                        } else if let Some(revisada) = resumen() {
+                            ResumenEncuesta {
+                                encuesta: revisada,
+                                fotos: uploaded_files_content.read().clone(),
+                                enviando: estado_envio() == SubmissionState::Sending,
+                                on_edit: move |paso| {
+                                    seccion_a_editar.set(Some(paso));
+                                    resumen.set(None);
+                                },
+                                on_confirm: move |_| {
+                                    resumen.set(None);
+                                    let mut current_survey = encuesta.read().clone();
+                                    red::calcular_conexiones(&mut current_survey, &guardadas.read());
+                                    current_survey.asignar_identidad(outbox::nuevo_uuid);
+                                    let files_to_send = uploaded_files_content.read().clone();
+                                    // El borrador se descarta cuando la bandeja confirme que la guardó.
+                                    send_survey.send(AccionOutbox::Encolar(Box::new(current_survey), files_to_send, borrador_id()));
+                                    nueva_inspeccion();
+                                },
+                            }
+                        } else {
+                            BarraCambios {
+                                puede_deshacer: cambios.read().puede_deshacer(),
+                                puede_rehacer: cambios.read().puede_rehacer(),
+                                on_change: aplicar_cambio,
+                            }
+                            SurveyForm {
+                                encuesta: encuesta,
+                                uploaded_files_content: uploaded_files_content,
+                                paso_inicial: seccion_a_editar(),
+                                guardadas: guardadas(),
+                                on_submit: move |_| {
+                                    let mut revisada = encuesta.read().clone();
+                                    red::calcular_conexiones(&mut revisada, &guardadas.read());
+                                    seccion_a_editar.set(None);
+                                    resumen.set(Some(revisada));
+                                    if let Some(window) = web_sys::window() {
+                                        window.scroll_to_with_x_and_y(0.0, 0.0);
+                                    }
+                                },
+                                on_discard: move |_| {
+                                    if confirmar("¿Descartar el borrador actual? Se perderán sus datos y fotos.") {
+                                        borradores.write().descartar(borrador_id());
+                                        nueva_inspeccion();
+                                    }
+                                },
+                                on_show_drafts: move |_| mostrar_borradores.set(true),
+                            }
+                        }
+                    }
+                },
+                Some(AuthStatus::Unauthenticated) => rsx! {
+                    LoginPage { login_url: configuracion_activa.clone().unwrap_or_default().url("/login") }
+                },
+                None => rsx! {
+                     LoadingScreen {}
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct BarraCambiosProps {
+    puede_deshacer: bool,
+    puede_rehacer: bool,
+    on_change: EventHandler<Atajo>,
+}
+
+// Botones flotantes de deshacer/rehacer sobre el formulario.
+#[allow(non_snake_case)]
+fn BarraCambios(props: BarraCambiosProps) -> Element {
+    rsx! {
+        div {
+            class: "fixed bottom-4 left-4 z-40 flex gap-2",
+            button {
+                r#type: "button",
+                title: "Deshacer (Ctrl+Z)",
+                disabled: !props.puede_deshacer,
+                class: "bg-white border border-gray-300 shadow rounded-full px-4 py-2 text-sm font-semibold text-gray-700 hover:bg-gray-100 disabled:opacity-40 disabled:cursor-not-allowed",
+                onclick: move |_| props.on_change.call(Atajo::Deshacer),
+                "↶ Deshacer"
+            }
+            button {
+                r#type: "button",
+                title: "Rehacer (Ctrl+Shift+Z)",
+                disabled: !props.puede_rehacer,
+                class: "bg-white border border-gray-300 shadow rounded-full px-4 py-2 text-sm font-semibold text-gray-700 hover:bg-gray-100 disabled:opacity-40 disabled:cursor-not-allowed",
+                onclick: move |_| props.on_change.call(Atajo::Rehacer),
+                "↷ Rehacer"
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct OutboxStatusProps {
+    outbox: Signal<Outbox>,
+    estado_envio: SubmissionState,
+    on_retry: EventHandler<()>,
+    on_show_history: EventHandler<()>,
+}
+
+#[allow(non_snake_case)]
+fn OutboxStatus(props: OutboxStatusProps) -> Element {
+    let conteo = props.outbox.read().conteo();
+    rsx! {
+        div {
+            class: "container mx-auto px-4 sm:px-8 mb-4",
+            div {
+                class: "bg-white rounded-xl shadow p-4 w-full max-w-4xl mx-auto border border-gray-200 flex flex-wrap items-center gap-3 text-sm",
+                span { class: "font-semibold text-gray-700 mr-2", "Bandeja de salida:" }
+                span { class: "px-3 py-1 rounded-full bg-yellow-100 text-yellow-800", "Pendientes: {conteo.pendientes}" }
+                span { class: "px-3 py-1 rounded-full bg-green-100 text-green-800", "Enviadas: {conteo.enviadas}" }
+                span { class: "px-3 py-1 rounded-full bg-red-100 text-red-800", "Fallidas: {conteo.fallidas}" }
+                match props.estado_envio {
+                    SubmissionState::Idle => rsx! {},
+                    SubmissionState::Sending => rsx! {
+                        span { class: "text-blue-700 font-medium", "Enviando encuesta..." }
+                    },
+                    SubmissionState::Sent => rsx! {
+                        span { class: "text-green-700 font-medium", "Última encuesta enviada" }
+                    },
+                    SubmissionState::Failed(error) => rsx! {
+                        span { class: "text-red-700 font-medium", "Último envío falló: {error}" }
+                    },
+                }
+                div {
+                    class: "ml-auto flex gap-2",
+                    if conteo.fallidas > 0 {
+                        button {
+                            r#type: "button",
+                            class: "bg-red-500 text-white font-semibold py-1 px-3 rounded-lg hover:bg-red-600 transition",
+                            onclick: move |_| props.on_retry.call(()),
+                            "Reintentar fallidas"
+                        }
+                    }
+                    button {
+                        r#type: "button",
+                        class: "bg-gray-200 text-gray-700 font-semibold py-1 px-3 rounded-lg hover:bg-gray-300 transition",
+                        onclick: move |_| props.on_show_history.call(()),
+                        "Historial"
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct ToastsProps {
+    toasts: Signal<Vec<Toast>>,
+}
+
+#[allow(non_snake_case)]
+fn Toasts(props: ToastsProps) -> Element {
+    let mut toasts = props.toasts;
+    rsx! {
+        div {
+            class: "fixed bottom-4 right-4 z-50 flex flex-col gap-2 w-full max-w-sm px-4 sm:px-0",
+            for toast in toasts.read().iter() {
+                div {
+                    key: "toast-{toast.id}",
+                    class: match toast.tipo {
+                        TipoToast::Exito => "flex items-start gap-3 p-4 rounded-lg shadow-lg text-sm text-white bg-green-600",
+                        TipoToast::Error => "flex items-start gap-3 p-4 rounded-lg shadow-lg text-sm text-white bg-red-600",
+                        TipoToast::Info => "flex items-start gap-3 p-4 rounded-lg shadow-lg text-sm text-white bg-blue-600",
+                    },
+                    p { class: "flex-1", "{toast.mensaje}" }
+                    button {
+                        r#type: "button",
+                        class: "font-bold opacity-80 hover:opacity-100",
+                        onclick: {
+                            let id = toast.id;
+                            move |_| toasts.write().retain(|t| t.id != id)
+                        },
+                        "X"
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct DraftPickerProps {
+    borradores: Vec<ResumenBorrador>,
+    on_continue: EventHandler<u64>,
+    on_discard: EventHandler<u64>,
+    on_new: EventHandler<()>,
+}
+
+#[allow(non_snake_case)]
+fn DraftPicker(props: DraftPickerProps) -> Element {
+    rsx! {
+        div {
+            class: "container mx-auto p-4 sm:p-8",
+            div {
+                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-2xl mx-auto border border-gray-200",
+                header {
+                    class: "mb-6 border-b pb-4 border-blue-200",
+                    h1 { class: "text-3xl font-extrabold text-gray-800 text-center mb-2", "Continuar borrador" }
+                    p { class: "text-md text-gray-600 text-center", "Tiene inspecciones sin terminar en este dispositivo." }
+                }
+                if props.borradores.is_empty() {
+                    p { class: "text-center text-gray-500 mb-6", "No hay borradores guardados." }
+                }
+                ul {
+                    class: "space-y-3 mb-6",
+                    for borrador in props.borradores.iter() {
+                        li {
+                            key: "borrador-{borrador.id}",
+                            class: "flex items-center justify-between gap-4 p-4 border rounded-lg bg-gray-50",
+                            div {
+                                p {
+                                    class: "font-semibold text-gray-800",
+                                    if borrador.pozo_numero.is_empty() { "Pozo sin número" } else { "Pozo {borrador.pozo_numero}" }
+                                }
+                                p { class: "text-xs text-gray-500", "{formatear_fecha(borrador.actualizado_en)} · {borrador.fotos} foto(s)" }
+                            }
+                            div {
+                                class: "flex gap-2",
+                                button {
+                                    r#type: "button",
+                                    class: "bg-blue-500 text-white font-semibold py-2 px-4 rounded-lg hover:bg-blue-600 transition",
+                                    onclick: {
+                                        let id = borrador.id;
+                                        move |_| props.on_continue.call(id)
+                                    },
+                                    "Continuar"
+                                }
+                                button {
+                                    r#type: "button",
+                                    class: "bg-red-500 text-white font-semibold py-2 px-4 rounded-lg hover:bg-red-600 transition",
+                                    onclick: {
+                                        let id = borrador.id;
+                                        move |_| props.on_discard.call(id)
+                                    },
+                                    "Descartar"
+                                }
+                            }
+                        }
+                    }
+                }
+                button {
+                    r#type: "button",
+                    class: "w-full bg-green-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-green-700 transition",
+                    onclick: move |_| props.on_new.call(()),
+                    "Nueva inspección"
+                }
+            }
+        }
+    }
+}
+
+fn estado_historial(estado: EstadoEnvio) -> (&'static str, &'static str) {
+    match estado {
+        EstadoEnvio::Pendiente => ("Pendiente", "bg-yellow-100 text-yellow-800"),
+        EstadoEnvio::Enviado => ("Enviada", "bg-green-100 text-green-800"),
+        EstadoEnvio::Fallido => ("Fallida", "bg-red-100 text-red-800"),
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct HistoryListProps {
+    entradas: Vec<EntradaOutbox>,
+    on_open: EventHandler<u64>,
+    on_close: EventHandler<()>,
+}
+
+#[allow(non_snake_case)]
+fn HistoryList(props: HistoryListProps) -> Element {
+    let mut filtro = use_signal(FiltroHistorial::default);
+    let visibles: Vec<EntradaOutbox> = props.entradas.iter().filter(|e| filtro.read().coincide(e)).cloned().collect();
+    rsx! {
+        div {
+            class: "container mx-auto p-4 sm:p-8",
+            div {
+                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-4xl mx-auto border border-gray-200 space-y-4",
+                div {
+                    class: "flex items-center justify-between",
+                    h2 { class: "text-2xl font-bold text-gray-800", "Historial de inspecciones" }
+                    button {
+                        r#type: "button",
+                        class: "bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition",
+                        onclick: move |_| props.on_close.call(()),
+                        "Volver"
+                    }
+                }
+                div {
+                    class: "grid grid-cols-1 md:grid-cols-3 gap-4",
+                    FormInput {
+                        label: "Número de pozo".to_string(),
+                        value: filtro.read().texto.clone(),
+                        on_input: move |v| filtro.write().texto = v,
+                    }
+                    FormSelect {
+                        label: "Tipo de Sistema".to_string(),
+                        vacio: "Todos",
+                        value: filtro.read().tipo_sistema,
+                        on_change: move |v| filtro.write().tipo_sistema = v,
+                    }
+                    div {
+                        class: "flex flex-col",
+                        label { r#for: "filtro-fecha", class: "text-sm font-medium text-gray-600 mb-1", "Fecha" }
+                        input {
+                            id: "filtro-fecha",
+                            r#type: "date",
+                            class: "px-3 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition text-sm",
+                            value: "{filtro.read().fecha}",
+                            oninput: move |evt| filtro.write().fecha = evt.value(),
+                        }
+                    }
+                }
+                if visibles.is_empty() {
+                    p { class: "text-sm text-gray-500", "No hay encuestas que coincidan con el filtro." }
+                }
+                ul {
+                    class: "divide-y border rounded-lg",
+                    for entrada in visibles {
+                        li {
+                            key: "historial-{entrada.id}",
+                            class: "p-4 flex flex-col sm:flex-row sm:items-center gap-3 hover:bg-gray-50 cursor-pointer",
+                            onclick: {
+                                let id = entrada.id;
+                                move |_| props.on_open.call(id)
+                            },
+                            div {
+                                class: "flex-1",
+                                p {
+                                    class: "font-semibold text-gray-800",
+                                    if entrada.encuesta.pozo_numero.trim().is_empty() { "Pozo sin número" } else { "Pozo {entrada.encuesta.pozo_numero}" }
+                                }
+                                p {
+                                    class: "text-xs text-gray-500",
+                                    "{formatear_fecha(entrada.creada_en)}"
+                                    if let Some(tipo) = entrada.encuesta.tipo_sistema { " · {tipo}" }
+                                    if entrada.encuesta.es_correccion() { " · Revisión {entrada.encuesta.revision}" }
+                                }
+                                if let Some(error) = &entrada.ultimo_error {
+                                    p { class: "text-xs text-red-600", "{error}" }
+                                }
+                            }
+                            span {
+                                class: "px-3 py-1 rounded-full text-xs {estado_historial(entrada.estado).1}",
+                                "{estado_historial(entrada.estado).0}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct HistoryDetailProps {
+    entrada: EntradaOutbox,
+    // Solo se corrige la revisión más reciente; corregir una anterior repetiría su número.
+    es_ultima_revision: bool,
+    guardadas: Rc<Vec<Encuesta>>,
+    on_close: EventHandler<()>,
+    on_edit: EventHandler<EntradaOutbox>,
+}
+
+// Detalle de una encuesta registrada, con el mismo formato del formulario.
+#[allow(non_snake_case)]
+fn HistoryDetail(props: HistoryDetailProps) -> Element {
+    let entrada = props.entrada.clone();
+    let encuesta = use_signal(|| entrada.encuesta.clone());
+    let mut fotos = use_signal(|| entrada.adjuntos.clone());
+    // Las fotos pendientes de envío tienen la imagen en IndexedDB.
+    use_hook(move || {
+        spawn(async move {
+            let mut lista = fotos.peek().clone();
+            match fotos_db::cargar_contenido(&mut lista).await {
+                Ok(()) => fotos.set(lista),
+                Err(e) => log::error!("{}", e),
+            }
+        })
+    });
+    let (estado, clase_estado) = estado_historial(entrada.estado);
+    rsx! {
+        div {
+            class: "w-full",
+            div {
+                class: "container mx-auto px-4 sm:px-8",
+                div {
+                    class: "bg-white rounded-xl shadow p-4 w-full max-w-4xl mx-auto border border-gray-200 text-sm space-y-2",
+                    div {
+                        class: "flex items-center gap-3",
+                        span { class: "px-3 py-1 rounded-full {clase_estado}", "{estado}" }
+                        span { class: "text-gray-600", "Registrada: {formatear_fecha(entrada.creada_en)}" }
+                        if entrada.encuesta.revision > 0 {
+                            span { class: "text-gray-600", "Revisión {entrada.encuesta.revision}" }
+                        }
+                        div {
+                            class: "ml-auto flex gap-2",
+                            // Solo se corrige lo que ya llegó al backend; lo pendiente aún está en la bandeja.
+                            if entrada.estado == EstadoEnvio::Enviado && props.es_ultima_revision {
+                                button {
+                                    r#type: "button",
+                                    class: "bg-blue-600 text-white font-semibold py-1 px-3 rounded-lg hover:bg-blue-700 transition",
+                                    onclick: {
+                                        let entrada = entrada.clone();
+                                        move |_| props.on_edit.call(entrada.clone())
+                                    },
+                                    "Corregir y reenviar"
+                                }
+                            }
+                            button {
+                                r#type: "button",
+                                class: "bg-gray-200 text-gray-700 font-semibold py-1 px-3 rounded-lg hover:bg-gray-300 transition",
+                                onclick: move |_| props.on_close.call(()),
+                                "Volver al historial"
+                            }
+                        }
+                    }
+                    if !props.es_ultima_revision {
+                        p { class: "text-gray-600", "Hay una revisión más reciente de esta encuesta; corrija esa en su lugar." }
+                    }
+                    if let Some(enviada) = entrada.enviada_en {
+                        p { class: "text-gray-600", "Enviada: {formatear_fecha(enviada)} · {entrada.fotos_enviadas} foto(s)" }
+                    }
+                    p { class: "text-gray-600", "Intentos de envío: {entrada.intentos}" }
+                    if let Some(respuesta) = &entrada.respuesta {
+                        p { class: "text-gray-600 break-all", "Respuesta del servidor: {respuesta}" }
+                    }
+                    if let Some(error) = &entrada.ultimo_error {
+                        p { class: "text-red-600 break-all", "Último error: {error}" }
+                    }
+                }
+            }
+            SurveyForm {
+                encuesta: encuesta,
+                uploaded_files_content: fotos,
+                guardadas: props.guardadas.clone(),
+                on_submit: move |_| {},
+                on_discard: move |_| {},
+                on_show_drafts: move |_| {},
+                solo_lectura: true,
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct ResumenEncuestaProps {
+    encuesta: Encuesta,
+    fotos: Vec<Foto>,
+    enviando: bool,
+    on_edit: EventHandler<Paso>,
+    on_confirm: EventHandler<()>,
+}
+
+fn texto_opcion<T: Opcion>(valor: Option<T>) -> String {
+    valor.map(|v| v.etiqueta().to_string()).unwrap_or_else(|| "—".to_string())
+}
+
+fn texto_libre(valor: &str) -> String {
+    if valor.trim().is_empty() { "—".to_string() } else { valor.to_string() }
+}
+
+fn texto_medida(valor: Option<f64>, unidad: Unidad) -> String {
+    match valor {
+        Some(_) => format!("{} {}", medidas::formatear(valor), unidad.simbolo()),
+        None => "—".to_string(),
+    }
+}
+
+fn texto_pendiente(pendiente: Option<f64>) -> String {
+    match pendiente {
+        Some(_) => format!("{} %", medidas::formatear(pendiente)),
+        None => "—".to_string(),
+    }
+}
+
+// Filas de cada sección del resumen, en el mismo orden de los pasos.
+fn filas_resumen(encuesta: &Encuesta) -> Vec<(Paso, Vec<(&'static str, String)>)> {
+    let ubicacion = match &encuesta.ubicacion {
+        Some(u) => match u.precision_m {
+            Some(precision) => format!("{:.6}, {:.6} (±{:.0} m)", u.latitud, u.longitud, precision),
+            None => format!("{:.6}, {:.6}", u.latitud, u.longitud),
+        },
+        None => "—".to_string(),
+    };
+    vec![
+        (
+            Paso::DatosGenerales,
+            vec![
+                ("Número de Pozo", texto_libre(&encuesta.pozo_numero)),
+                ("Tipo de Sistema", texto_opcion(encuesta.tipo_sistema)),
+                ("Tipo de Pozo", texto_opcion(encuesta.tipo_pozo)),
+                ("Ubicación", ubicacion),
+            ],
+        ),
+        (
+            Paso::Tapa,
+            vec![
+                ("Existe", texto_opcion(encuesta.tapa_existe)),
+                ("Tipo", texto_opcion(encuesta.tapa_tipo)),
+                ("Estado", texto_opcion(encuesta.tapa_estado)),
+                ("Diagnóstico", texto_opcion(encuesta.tapa_diagnostico)),
+            ],
+        ),
+        (
+            Paso::Cargue,
+            vec![
+                ("Existe", texto_opcion(encuesta.cargue_existe)),
+                ("Estado", texto_opcion(encuesta.cargue_estado)),
+                ("Diagnóstico", texto_opcion(encuesta.cargue_diagnostico)),
+            ],
+        ),
+        (
+            Paso::Cono,
+            vec![
+                ("Existe", texto_opcion(encuesta.cono_existe)),
+                ("Estado", texto_opcion(encuesta.cono_estado)),
+                ("Diagnóstico", texto_opcion(encuesta.cono_diagnostico)),
+            ],
+        ),
+        (
+            Paso::Cilindro,
+            vec![
+                ("Material", texto_opcion(encuesta.cilindro_material)),
+                ("Estado", texto_opcion(encuesta.cilindro_estado)),
+                ("Diagnóstico", texto_opcion(encuesta.cilindro_diagnostico)),
+            ],
+        ),
+        (
+            Paso::Canuela,
+            vec![
+                ("Estado", texto_opcion(encuesta.canuela_estado)),
+                ("Diagnóstico", texto_opcion(encuesta.canuela_diagnostico)),
+            ],
+        ),
+        (
+            Paso::Escalones,
+            vec![
+                ("Existen", texto_opcion(encuesta.escalones_existe)),
+                ("Tipo", texto_opcion(encuesta.escalones_tipo)),
+                ("Estado", texto_opcion(encuesta.escalones_estado)),
+                ("Diagnóstico", texto_opcion(encuesta.escalones_diagnostico)),
+            ],
+        ),
+        (
+            Paso::EvaluacionFinal,
+            vec![
+                ("Estado General del Pozo", texto_opcion(encuesta.estado_general_pozo)),
+                ("Observaciones", texto_libre(&encuesta.observaciones)),
+            ],
+        ),
+    ]
+}
+
+// Resumen de solo lectura que se revisa antes de confirmar el envío.
+#[allow(non_snake_case)]
+fn ResumenEncuesta(props: ResumenEncuestaProps) -> Element {
+    let encuesta = &props.encuesta;
+    let errores = validation::validar(encuesta, &props.fotos);
+    let advertencias = rules::advertencias(encuesta);
+    let avisos: Vec<(Paso, String)> = errores
+        .iter()
+        .chain(advertencias.iter())
+        .map(|e| (Paso::de_campo(e.campo), e.mensaje.clone()))
+        .collect();
+    let bloqueado = props.enviando || !errores.is_empty();
+
+    rsx! {
+        div {
+            class: "container mx-auto p-4 sm:p-8",
+            div {
+                id: "resumen-encuesta",
+                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-4xl mx-auto border border-gray-200 space-y-6",
+                header {
+                    class: "border-b pb-4 border-blue-200",
+                    h1 { class: "text-3xl font-extrabold text-gray-800 text-center mb-2", "Revisión del Reporte" }
+                    p {
+                        class: "text-md text-gray-600 text-center",
+                        "Verifique los datos antes de enviarlos. Use \"Editar sección\" para corregir algo."
+                    }
+                }
+                if !avisos.is_empty() {
+                    div {
+                        class: "p-4 rounded-lg bg-yellow-50 border border-yellow-200 text-sm text-yellow-800 space-y-1",
+                        p { class: "font-semibold", "Avisos" }
+                        for (i, (paso, mensaje)) in avisos.into_iter().enumerate() {
+                            div {
+                                key: "aviso-{i}",
+                                class: "flex gap-2",
+                                span { class: "font-semibold", "{paso.titulo()}:" }
+                                span { class: "flex-1", "{mensaje}" }
+                                button {
+                                    r#type: "button",
+                                    class: "text-blue-600 hover:underline",
+                                    onclick: move |_| props.on_edit.call(paso),
+                                    "Corregir"
+                                }
+                            }
+                        }
+                    }
+                }
+                for (paso, filas) in filas_resumen(encuesta) {
+                    SeccionResumen {
+                        key: "{paso.titulo()}",
+                        paso: paso,
+                        on_edit: props.on_edit,
+                        dl {
+                            class: "grid grid-cols-1 sm:grid-cols-2 gap-x-6 gap-y-2 text-sm",
+                            for (etiqueta, valor) in filas {
+                                div {
+                                    key: "{etiqueta}",
+                                    class: "flex justify-between gap-4 border-b border-gray-100 py-1",
+                                    dt { class: "text-gray-500", "{etiqueta}" }
+                                    dd { class: "text-gray-800 font-medium text-right break-words", "{valor}" }
+                                }
+                            }
+                        }
+                    }
+                }
+                SeccionResumen {
+                    paso: Paso::Conexiones,
+                    on_edit: props.on_edit,
+                    if encuesta.lista_conexiones.is_empty() {
+                        p { class: "text-sm text-gray-500", "Sin conexiones registradas." }
+                    } else {
+                        div {
+                            class: "overflow-x-auto",
+                            table {
+                                class: "w-full text-sm text-left",
+                                thead {
+                                    tr {
+                                        class: "text-gray-500 border-b",
+                                        th { class: "py-1 pr-3", "#" }
+                                        th { class: "py-1 pr-3", "Dirección" }
+                                        th { class: "py-1 pr-3", "Posición" }
+                                        th { class: "py-1 pr-3", "Cota Razante" }
+                                        th { class: "py-1 pr-3", "Cota Clave" }
+                                        th { class: "py-1 pr-3", "Diámetro" }
+                                        th { class: "py-1 pr-3", "Profundidad" }
+                                        th { class: "py-1 pr-3", "Pendiente" }
+                                        th { class: "py-1 pr-3", "Material" }
+                                        th { class: "py-1 pr-3", "Estado" }
+                                        th { class: "py-1 pr-3", "Conecta A" }
+                                    }
+                                }
+                                tbody {
+                                    for (i, conexion) in encuesta.lista_conexiones.iter().enumerate() {
+                                        tr {
+                                            key: "conexion-{i}",
+                                            class: "border-b border-gray-100",
+                                            td { class: "py-1 pr-3 text-gray-500", "{i + 1}" }
+                                            td { class: "py-1 pr-3", "{texto_opcion(conexion.direccion)}" }
+                                            td { class: "py-1 pr-3", {conexion.posicion_reloj.map(|h| format!("{} h", h)).unwrap_or_else(|| "—".to_string())} }
+                                            td { class: "py-1 pr-3", "{texto_medida(conexion.cota_razante, Unidad::Metros)}" }
+                                            td { class: "py-1 pr-3", "{texto_medida(conexion.cota_clave, Unidad::Metros)}" }
+                                            td { class: "py-1 pr-3", "{texto_medida(conexion.diametro_pulgadas, Unidad::Pulgadas)}" }
+                                            td { class: "py-1 pr-3", "{texto_medida(conexion.calculo.profundidad_m, Unidad::Metros)}" }
+                                            td { class: "py-1 pr-3", "{texto_pendiente(conexion.calculo.pendiente_pct)}" }
+                                            td { class: "py-1 pr-3", "{texto_opcion(conexion.material)}" }
+                                            td { class: "py-1 pr-3", "{texto_opcion(conexion.estado)}" }
+                                            td { class: "py-1 pr-3", "{texto_libre(&conexion.conecta_a)}" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                SeccionResumen {
+                    paso: Paso::Fotografias,
+                    on_edit: props.on_edit,
+                    if props.fotos.is_empty() {
+                        p { class: "text-sm text-gray-500", "Sin fotografías." }
+                    } else {
+                        div {
+                            class: "grid grid-cols-3 sm:grid-cols-4 gap-3",
+                            for (i, foto) in props.fotos.iter().enumerate() {
+                                div {
+                                    key: "foto-{i}",
+                                    class: "text-xs text-gray-600 space-y-1",
+                                    Miniatura { nombre: foto.nombre.clone(), contenido: foto.contenido.clone(), class: "w-full h-24 object-cover rounded border".to_string() }
+                                    p { class: "font-semibold", "{foto.componente.etiqueta()}" }
+                                    if !foto.descripcion.is_empty() {
+                                        p { class: "truncate", "{foto.descripcion}" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                div {
+                    class: "pt-6 border-t space-y-3",
+                    button {
+                        r#type: "button",
+                        disabled: bloqueado,
+                        class: "w-full bg-green-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-green-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-green-500 transition disabled:opacity-50 disabled:cursor-not-allowed",
+                        onclick: move |_| {
+                            if !bloqueado {
+                                props.on_confirm.call(());
+                            }
+                        },
+                        if props.enviando { "Enviando..." } else { "Confirmar y generar reporte" }
+                    }
+                    button {
+                        r#type: "button",
+                        class: "w-full bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition",
+                        onclick: move |_| props.on_edit.call(Paso::DatosGenerales),
+                        "Volver al formulario"
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct SeccionResumenProps {
+    paso: Paso,
+    on_edit: EventHandler<Paso>,
+    children: Element,
+}
+
+#[allow(non_snake_case)]
+fn SeccionResumen(props: SeccionResumenProps) -> Element {
+    let paso = props.paso;
+    rsx! {
+        section {
+            class: "p-4 border rounded-lg",
+            div {
+                class: "flex items-center justify-between mb-3 border-b pb-2",
+                h3 { class: "text-lg font-semibold text-gray-700", "{paso.titulo()}" }
+                button {
+                    r#type: "button",
+                    class: "text-sm text-blue-600 hover:underline",
+                    onclick: move |_| props.on_edit.call(paso),
+                    "Editar sección"
+                }
+            }
+            {props.children}
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct UpdatePromptProps {
+    on_update: EventHandler<()>,
+    on_dismiss: EventHandler<()>,
+}
+
+#[allow(non_snake_case)]
+fn UpdatePrompt(props: UpdatePromptProps) -> Element {
+    rsx! {
+        div {
+            class: "fixed top-2 right-2 z-50 bg-blue-700 text-white rounded-lg shadow-lg p-4 max-w-xs text-sm space-y-3",
+            p { "Hay una nueva versión de la aplicación disponible." }
+            div {
+                class: "flex gap-2",
+                button {
+                    r#type: "button",
+                    class: "flex-1 bg-white text-blue-700 font-semibold py-1 px-3 rounded-lg hover:bg-blue-50 transition",
+                    onclick: move |_| props.on_update.call(()),
+                    "Actualizar"
+                }
+                button {
+                    r#type: "button",
+                    class: "flex-1 border border-white font-semibold py-1 px-3 rounded-lg hover:bg-blue-600 transition",
+                    onclick: move |_| props.on_dismiss.call(()),
+                    "Más tarde"
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct EnvironmentBadgeProps {
+    configuracion: Configuracion,
+    on_open: EventHandler<()>,
+}
+
+// Indicador permanente del perfil activo; al tocarlo se abren los ajustes.
+// Con una URL personalizada el perfil no dice adónde van los datos, así que
+// se muestra "personalizado" (la URL queda en el título).
+#[allow(non_snake_case)]
+fn EnvironmentBadge(props: EnvironmentBadgeProps) -> Element {
+    let personalizada = props.configuracion.url_personalizada();
+    let color = match props.configuracion.perfil {
+        _ if personalizada => "bg-purple-600 text-white",
+        Perfil::Dev => "bg-yellow-400 text-yellow-900",
+        Perfil::Staging => "bg-orange-500 text-white",
+        Perfil::Produccion => "bg-green-600 text-white",
+    };
+    let etiqueta = if personalizada { "personalizado".to_string() } else { props.configuracion.perfil.to_string() };
+    rsx! {
+        button {
+            r#type: "button",
+            class: "fixed top-2 left-2 z-40 px-3 py-1 rounded-full shadow text-xs font-semibold {color}",
+            title: "{props.configuracion.backend_url}",
+            onclick: move |_| props.on_open.call(()),
+            "{etiqueta}"
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct SettingsPageProps {
+    configuracion: Configuracion,
+    on_close: EventHandler<()>,
+}
+
+#[allow(non_snake_case)]
+fn SettingsPage(props: SettingsPageProps) -> Element {
+    let mut ajustes = use_signal(config::leer_ajustes);
+    let perfil_elegido = ajustes.read().perfil.unwrap_or(props.configuracion.perfil);
+    let url_del_perfil = props.configuracion.perfiles.get(&perfil_elegido).cloned().unwrap_or_default();
+    rsx! {
+        div {
+            class: "fixed inset-0 z-50 bg-black bg-opacity-40 flex items-center justify-center p-4",
+            div {
+                class: "bg-white p-6 rounded-xl shadow-2xl w-full max-w-md border border-gray-200 space-y-4",
+                h2 { class: "text-2xl font-bold text-gray-800", "Configuración" }
+                p {
+                    class: "text-sm text-gray-600",
+                    "Servidor activo: {props.configuracion.backend_url} (perfil {props.configuracion.perfil}, según {props.configuracion.origen})."
+                }
+                FormSelect {
+                    label: "Perfil".to_string(),
+                    value: Some(perfil_elegido),
+                    on_change: move |v| ajustes.write().perfil = v,
+                }
+                FormInput {
+                    label: "URL personalizada del backend (opcional)".to_string(),
+                    value: ajustes.read().backend_url.clone().unwrap_or_default(),
+                    on_input: move |v: String| ajustes.write().backend_url = Some(v).filter(|v| !v.trim().is_empty()),
+                }
+                if let Some(ignorado) = props.configuracion.backend_ignorado.clone() {
+                    div {
+                        class: "text-xs bg-yellow-100 text-yellow-900 border border-yellow-300 rounded-lg p-3 space-y-2",
+                        p { "El enlace indicaba el servidor {ignorado}, que no es de ningún perfil, y se ignoró. Úselo solo si confía en él." }
+                        button {
+                            r#type: "button",
+                            class: "font-semibold underline",
+                            onclick: move |_| ajustes.write().backend_url = Some(ignorado.clone()),
+                            "Usar como URL personalizada"
+                        }
+                    }
+                }
+                if url_del_perfil.is_empty() {
+                    p { class: "text-xs text-red-600", "El perfil {perfil_elegido} no tiene URL en config.json; se usará la URL personalizada o dev." }
+                } else {
+                    p { class: "text-xs text-gray-500", "URL del perfil {perfil_elegido}: {url_del_perfil}" }
+                }
+                h3 { class: "text-lg font-semibold text-gray-700 border-t pt-4", "Fotografías" }
+                div {
+                    class: "grid grid-cols-2 gap-4",
+                    FormInput {
+                        label: "Lado máximo (px)".to_string(),
+                        value: ajustes.read().imagenes.dimension_maxima.to_string(),
+                        on_input: move |v: String| {
+                            if let Some(n) = validation::parsear_numero(&v).filter(|n| *n >= 320.0) {
+                                ajustes.write().imagenes.dimension_maxima = n as u32;
+                            }
+                        },
+                    }
+                    FormInput {
+                        label: "Calidad (%)".to_string(),
+                        value: format!("{:.0}", ajustes.read().imagenes.calidad * 100.0),
+                        on_input: move |v: String| {
+                            if let Some(n) = validation::parsear_numero(&v).filter(|n| (10.0..=100.0).contains(n)) {
+                                ajustes.write().imagenes.calidad = n / 100.0;
+                            }
+                        },
+                    }
+                    FormSelect::<FormatoImagen> {
+                        label: "Formato".to_string(),
+                        value: Some(ajustes.read().imagenes.formato),
+                        on_change: move |v: Option<FormatoImagen>| if let Some(v) = v { ajustes.write().imagenes.formato = v },
+                    }
+                    FormSelect::<PoliticaExif> {
+                        label: "Metadatos EXIF".to_string(),
+                        value: Some(ajustes.read().imagenes.exif),
+                        on_change: move |v: Option<PoliticaExif>| if let Some(v) = v { ajustes.write().imagenes.exif = v },
+                    }
+                }
+                p { class: "text-xs text-gray-500", "El EXIF (fecha, cámara, GPS) solo se conserva en JPEG." }
+                h3 { class: "text-lg font-semibold text-gray-700 border-t pt-4", "Formulario" }
+                FormInput {
+                    label: "Pasos para deshacer".to_string(),
+                    value: ajustes.read().profundidad_deshacer().to_string(),
+                    on_input: move |v: String| {
+                        if let Some(n) = validation::parsear_numero(&v).filter(|n| (1.0..=500.0).contains(n)) {
+                            ajustes.write().pasos_deshacer = Some(n as usize);
+                        }
+                    },
+                }
+                div {
+                    class: "flex flex-col sm:flex-row gap-3 pt-2",
+                    button {
+                        r#type: "button",
+                        class: "flex-1 bg-blue-600 text-white font-semibold py-2 px-4 rounded-lg hover:bg-blue-700 transition",
+                        onclick: move |_| {
+                            config::guardar_ajustes(&ajustes.read());
+                            config::recargar();
+                        },
+                        "Guardar y recargar"
+                    }
+                    button {
+                        r#type: "button",
+                        class: "flex-1 bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition",
+                        onclick: move |_| {
+                            config::borrar_ajustes();
+                            config::recargar();
+                        },
+                        "Restablecer"
+                    }
+                    button {
+                        r#type: "button",
+                        class: "flex-1 bg-white text-gray-700 border border-gray-300 font-semibold py-2 px-4 rounded-lg hover:bg-gray-50 transition",
+                        onclick: move |_| props.on_close.call(()),
+                        "Cancelar"
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+fn LoadingScreen() -> Element {
+    rsx! {
+        div {
+            class: "text-center",
+            h1 { class: "text-3xl font-bold text-gray-700", "Verificando autenticación..." },
+            p { class: "text-gray-500 mt-2", "Por favor, espere un momento." }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct LoginPageProps {
+    login_url: String,
+}
+
+#[allow(non_snake_case)]
+fn LoginPage(props: LoginPageProps) -> Element {
+    let login_url = props.login_url;
+    rsx! {
+        div {
+            class: "container mx-auto p-4 sm:p-8",
+            div {
+                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-md mx-auto border border-gray-200 text-center",
+                header {
+                    class: "mb-8",
+                    h1 {
+                        class: "text-4xl font-extrabold text-gray-800 mb-2",
+                        "Acceso Requerido"
+                    }
+                    p {
+                        class: "text-md text-gray-600",
+                        "Se necesita autorización para subir archivos a Google Drive."
+                    }
+                }
+                div {
+                    a {
+                        href: "{login_url}",
+                        class: "w-full inline-block bg-blue-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-blue-500 transition-transform transform hover:scale-105",
+                        "Iniciar Sesión con Google"
+                    }
+                }
+                 p {
+                    class: "text-xs text-gray-500 mt-6",
+                    "Serás redirigido a la página de inicio de sesión de Google para autorizar la aplicación."
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct SurveyFormProps {
+    encuesta: Signal<Encuesta>,
+    uploaded_files_content: Signal<Vec<Foto>>,
+    on_submit: EventHandler<()>,
+    on_discard: EventHandler<()>,
+    on_show_drafts: EventHandler<()>,
+    // Muestra la encuesta sin permitir cambios (detalle del historial).
+    #[props(default)]
+    solo_lectura: bool,
+    // Sección a mostrar al abrir el formulario (al volver desde el resumen).
+    #[props(default)]
+    paso_inicial: Option<Paso>,
+    // Encuestas de los borradores y la bandeja, para armar la red de pozos.
+    guardadas: Rc<Vec<Encuesta>>,
+}
+
+#[allow(non_snake_case)]
+fn SurveyForm(props: SurveyFormProps) -> Element {
+    let mut encuesta = props.encuesta;
+    let uploaded_files_content = props.uploaded_files_content;
+    // Los errores solo se muestran después del primer intento de envío.
+    let mut mostrar_errores = use_signal(|| false);
+    let errores = if mostrar_errores() {
+        validation::validar(&encuesta.read(), &uploaded_files_content.read())
+    } else {
+        Vec::new()
+    };
+    let advertencias = rules::advertencias(&encuesta.read());
+    // Las demás encuestas del equipo se leen una vez; solo la actual cambia mientras se edita.
+    let guardadas = props.guardadas.clone();
+    let red_pozos = red::Red::construir(&encuesta.read(), &guardadas);
+    let calculos = red_pozos.calculos(&encuesta.read());
+    // Filas de conexiones contraídas (por id) y la última eliminada, para deshacer,
+    // junto con la clave de la encuesta de la que salió.
+    let mut contraidas = use_signal(HashSet::<String>::new);
+    let mut eliminada = use_signal(|| None::<(String, usize, Conexion)>);
+    // El formulario sigue montado al cambiar de encuesta y el deshacer general
+    // puede devolver la fila; en ambos casos ya no hay nada que deshacer aquí.
+    use_effect(move || {
+        let vigente = match &*eliminada.read() {
+            Some((clave, _, quitada)) => {
+                let actual = encuesta.read();
+                *clave == actual.clave_idempotencia() && !actual.lista_conexiones.iter().any(|c| c.id == quitada.id)
+            }
+            None => true,
+        };
+        if !vigente {
+            eliminada.set(None);
+        }
+    });
+    let mut modo_pasos = use_signal(pasos::preferir_pasos);
+    let mut paso = use_signal(|| props.paso_inicial.unwrap_or(Paso::DatosGenerales));
+    use_hook(move || {
+        if let Some(inicial) = props.paso_inicial {
+            // La sección existe recién después del primer dibujo.
+            spawn(async move {
+                TimeoutFuture::new(50).await;
+                desplazar_a(&inicial.id_html());
+            });
+        }
+    });
+    // El detalle del historial siempre se muestra completo.
+    let en_pasos = modo_pasos() && !props.solo_lectura;
+    let visible = move |p: Paso| !en_pasos || paso() == p;
+    let ultimo_paso = paso().siguiente().is_none();
+
+    // Cambia de paso y vuelve al inicio del formulario.
+    let mut ir_a = move |destino: Paso| {
+        paso.set(destino);
+        if let Some(inicio) = web_sys::window().and_then(|w| w.document()).and_then(|d| d.get_element_by_id("pasos-encuesta")) {
+            inicio.scroll_into_view();
+        }
+    };
+
+    // Lleva al campo con error, mostrando antes su paso o su fila si estaban ocultos.
+    let mut enfocar = move |campo: Campo| {
+        let id = campo.id_html();
+        let mut oculto = en_pasos && paso() != Paso::de_campo(campo);
+        if let Campo::Conexion(i, _) = campo {
+            let fila = encuesta.read().lista_conexiones.get(i).map(|c| c.id.clone()).unwrap_or_default();
+            oculto |= contraidas.write().remove(&fila);
+        }
+        if oculto {
+            // El campo aparece cuando se vuelve a dibujar el formulario.
+            paso.set(Paso::de_campo(campo));
+            spawn(async move {
+                TimeoutFuture::new(50).await;
+                desplazar_a(&id);
+            });
+        } else {
+            desplazar_a(&id);
+        }
+    };
+
+    rsx! {
+        div {
+            class: "container mx-auto p-4 sm:p-8",
+            div {
+                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-4xl mx-auto border border-gray-200",
+                header {
+                    class: "mb-8 border-b pb-4 border-blue-200",
+                    h1 {
+                        class: "text-4xl font-extrabold text-gray-800 text-center mb-2",
+                        "Reporte de Inspección de Pozo"
+                    }
+                    p {
+                        class: "text-md text-gray-600 text-center",
+                        if props.solo_lectura { "Vista de solo lectura de una encuesta registrada." } else { "Complete todos los campos para generar el reporte técnico." }
+                    }
+                    if encuesta.read().es_correccion() && !props.solo_lectura {
+                        p {
+                            class: "text-sm text-blue-700 text-center mt-2",
+                            "Corrección de una encuesta enviada (revisión {encuesta.read().revision}). Al enviarla reemplazará el registro anterior."
+                        }
+                    }
+                    if !props.solo_lectura {
+                        div {
+                            class: "mt-3 flex justify-center",
+                            button {
+                                r#type: "button",
+                                class: "text-sm text-blue-600 hover:underline",
+                                onclick: move |_| {
+                                    let nuevo = !modo_pasos();
+                                    modo_pasos.set(nuevo);
+                                    pasos::guardar_preferencia(nuevo);
+                                },
+                                if en_pasos { "Ver formulario completo" } else { "Llenar paso a paso" }
+                            }
+                        }
+                    }
+                }
+                form {
+                    prevent_default: "onsubmit",
+                    onsubmit: move |_| {
+                        let errores = validation::validar(&encuesta.read(), &uploaded_files_content.read());
+                        if let Some(primero) = Paso::primer_error(&errores) {
+                            mostrar_errores.set(true);
+                            enfocar(primero.campo);
+                            return;
+                        }
+                        mostrar_errores.set(false);
+                        props.on_submit.call(());
+                    },
+                    fieldset {
+                        // `fieldset` deshabilitado desactiva todos los controles que contiene.
+                        "disabled": props.solo_lectura.then_some("true"),
+                        class: "space-y-8",
+                        if !errores.is_empty() {
+                            div {
+                                class: "p-4 rounded-lg bg-red-50 border border-red-200 text-sm text-red-700",
+                                "Hay {errores.len()} campo(s) por corregir antes de generar el reporte."
+                            }
+                        }
+                        if en_pasos {
+                            PasosEncuesta {
+                                actual: paso(),
+                                errores: errores.clone(),
+                                on_select: ir_a,
+                            }
+                        }
+                        if visible(Paso::DatosGenerales) {
+                            FormFieldSection {
+                                id: Paso::DatosGenerales.id_html(),
+                                title: "Datos Generales".to_string(),
+                                grid_cols: Some(3),
+                                FormSelect { label: "Tipo de Sistema".to_string(), id: Campo::TipoSistema.id_html(), error: error_de(&errores, Campo::TipoSistema), value: encuesta.read().tipo_sistema, on_change: move |v| encuesta.write().tipo_sistema = v },
+                                FormSelect { label: "Tipo de Pozo".to_string(), id: Campo::TipoPozo.id_html(), error: error_de(&errores, Campo::TipoPozo), value: encuesta.read().tipo_pozo, on_change: move |v| encuesta.write().tipo_pozo = v },
+                                FormInput { label: "Número de Pozo".to_string(), id: Campo::PozoNumero.id_html(), error: error_de(&errores, Campo::PozoNumero), value: encuesta.read().pozo_numero.clone(), on_input: move |v| encuesta.write().pozo_numero = v },
+                            }
+                        }
+                        if visible(Paso::DatosGenerales) {
+                            FormFieldSection {
+                                title: "Ubicación".to_string(),
+                                grid_cols: Some(1),
+                                UbicacionPozo {
+                                    ubicacion: encuesta.read().ubicacion.clone(),
+                                    on_change: move |v| encuesta.write().ubicacion = v,
+                                }
+                            }
+                        }
+                        if visible(Paso::Tapa) {
+                            FormFieldSection {
+                                id: Paso::Tapa.id_html(),
+                                title: "Tapa".to_string(),
+                                FormSelect { label: "Existe".to_string(), id: Campo::TapaExiste.id_html(), error: error_de(&errores, Campo::TapaExiste), value: encuesta.read().tapa_existe, on_change: move |v| { let mut datos = encuesta.write(); datos.tapa_existe = v; rules::aplicar(&mut datos); } },
+                                FormSelect { label: "Tipo".to_string(), id: Campo::TapaTipo.id_html(), error: error_de(&errores, Campo::TapaTipo), disabled: !rules::aplica(&encuesta.read(), Campo::TapaTipo), value: encuesta.read().tapa_tipo, on_change: move |v| encuesta.write().tapa_tipo = v },
+                                FormSelect { label: "Estado".to_string(), id: Campo::TapaEstado.id_html(), error: error_de(&errores, Campo::TapaEstado), disabled: !rules::aplica(&encuesta.read(), Campo::TapaEstado), value: encuesta.read().tapa_estado, on_change: move |v| encuesta.write().tapa_estado = v },
+                                FormSelect { label: "Diagnóstico".to_string(), id: Campo::TapaDiagnostico.id_html(), error: error_de(&errores, Campo::TapaDiagnostico), warning: error_de(&advertencias, Campo::TapaDiagnostico), disabled: !rules::aplica(&encuesta.read(), Campo::TapaDiagnostico), value: encuesta.read().tapa_diagnostico, on_change: move |v| encuesta.write().tapa_diagnostico = v },
+                                FotosComponente { componente: ComponenteFoto::Tapa, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone() },
+                            }
+                        }
+                        if visible(Paso::Cargue) {
+                            FormFieldSection {
+                                id: Paso::Cargue.id_html(),
+                                title: "Cargue".to_string(),
+                                grid_cols: Some(3),
+                                FormSelect { label: "Existe".to_string(), id: Campo::CargueExiste.id_html(), error: error_de(&errores, Campo::CargueExiste), value: encuesta.read().cargue_existe, on_change: move |v| { let mut datos = encuesta.write(); datos.cargue_existe = v; rules::aplicar(&mut datos); } },
+                                FormSelect { label: "Estado".to_string(), id: Campo::CargueEstado.id_html(), error: error_de(&errores, Campo::CargueEstado), disabled: !rules::aplica(&encuesta.read(), Campo::CargueEstado), value: encuesta.read().cargue_estado, on_change: move |v| encuesta.write().cargue_estado = v },
+                                FormSelect { label: "Diagnóstico".to_string(), id: Campo::CargueDiagnostico.id_html(), error: error_de(&errores, Campo::CargueDiagnostico), warning: error_de(&advertencias, Campo::CargueDiagnostico), disabled: !rules::aplica(&encuesta.read(), Campo::CargueDiagnostico), value: encuesta.read().cargue_diagnostico, on_change: move |v| encuesta.write().cargue_diagnostico = v },
+                                FotosComponente { componente: ComponenteFoto::Cargue, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone() },
+                            }
+                        }
+                        if visible(Paso::Cono) {
+                            FormFieldSection {
+                                id: Paso::Cono.id_html(),
+                                title: "Cono".to_string(),
+                                grid_cols: Some(3),
+                                FormSelect { label: "Existe".to_string(), id: Campo::ConoExiste.id_html(), error: error_de(&errores, Campo::ConoExiste), value: encuesta.read().cono_existe, on_change: move |v| { let mut datos = encuesta.write(); datos.cono_existe = v; rules::aplicar(&mut datos); } },
+                                FormSelect { label: "Estado".to_string(), id: Campo::ConoEstado.id_html(), error: error_de(&errores, Campo::ConoEstado), disabled: !rules::aplica(&encuesta.read(), Campo::ConoEstado), value: encuesta.read().cono_estado, on_change: move |v| encuesta.write().cono_estado = v },
+                                FormSelect { label: "Diagnóstico".to_string(), id: Campo::ConoDiagnostico.id_html(), error: error_de(&errores, Campo::ConoDiagnostico), warning: error_de(&advertencias, Campo::ConoDiagnostico), disabled: !rules::aplica(&encuesta.read(), Campo::ConoDiagnostico), value: encuesta.read().cono_diagnostico, on_change: move |v| encuesta.write().cono_diagnostico = v },
+                                FotosComponente { componente: ComponenteFoto::Cono, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone() },
+                            }
+                        }
+                        if visible(Paso::Cilindro) {
+                            FormFieldSection {
+                                id: Paso::Cilindro.id_html(),
+                                title: "Cilindro".to_string(),
+                                grid_cols: Some(3),
+                                FormSelect { label: "Material".to_string(), id: Campo::CilindroMaterial.id_html(), error: error_de(&errores, Campo::CilindroMaterial), value: encuesta.read().cilindro_material, on_change: move |v| encuesta.write().cilindro_material = v },
+                                FormSelect { label: "Estado".to_string(), id: Campo::CilindroEstado.id_html(), error: error_de(&errores, Campo::CilindroEstado), value: encuesta.read().cilindro_estado, on_change: move |v| encuesta.write().cilindro_estado = v },
+                                FormSelect { label: "Diagnóstico".to_string(), id: Campo::CilindroDiagnostico.id_html(), error: error_de(&errores, Campo::CilindroDiagnostico), warning: error_de(&advertencias, Campo::CilindroDiagnostico), value: encuesta.read().cilindro_diagnostico, on_change: move |v| encuesta.write().cilindro_diagnostico = v },
+                                FotosComponente { componente: ComponenteFoto::Cilindro, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone() },
+                            }
+                        }
+                        if visible(Paso::Canuela) {
+                            FormFieldSection {
+                                id: Paso::Canuela.id_html(),
+                                title: "Cañuela".to_string(),
+                                grid_cols: Some(2),
+                                FormSelect { label: "Estado".to_string(), id: Campo::CanuelaEstado.id_html(), error: error_de(&errores, Campo::CanuelaEstado), value: encuesta.read().canuela_estado, on_change: move |v| encuesta.write().canuela_estado = v },
+                                FormSelect { label: "Diagnostico".to_string(), id: Campo::CanuelaDiagnostico.id_html(), error: error_de(&errores, Campo::CanuelaDiagnostico), warning: error_de(&advertencias, Campo::CanuelaDiagnostico), value: encuesta.read().canuela_diagnostico, on_change: move |v| encuesta.write().canuela_diagnostico = v },
+                                FotosComponente { componente: ComponenteFoto::Canuela, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone() },
+                            }
+                        }
+                        if visible(Paso::Escalones) {
+                            FormFieldSection {
+                                id: Paso::Escalones.id_html(),
+                                title: "Escalones".to_string(),
+                                FormSelect { label: "Existen".to_string(), id: Campo::EscalonesExiste.id_html(), error: error_de(&errores, Campo::EscalonesExiste), value: encuesta.read().escalones_existe, on_change: move |v| { let mut datos = encuesta.write(); datos.escalones_existe = v; rules::aplicar(&mut datos); } },
+                                FormSelect { label: "Tipo".to_string(), id: Campo::EscalonesTipo.id_html(), error: error_de(&errores, Campo::EscalonesTipo), disabled: !rules::aplica(&encuesta.read(), Campo::EscalonesTipo), value: encuesta.read().escalones_tipo, on_change: move |v| encuesta.write().escalones_tipo = v },
+                                FormSelect { label: "Estado".to_string(), id: Campo::EscalonesEstado.id_html(), error: error_de(&errores, Campo::EscalonesEstado), disabled: !rules::aplica(&encuesta.read(), Campo::EscalonesEstado), value: encuesta.read().escalones_estado, on_change: move |v| encuesta.write().escalones_estado = v },
+                                FormSelect { label: "Diagnóstico".to_string(), id: Campo::EscalonesDiagnostico.id_html(), error: error_de(&errores, Campo::EscalonesDiagnostico), warning: error_de(&advertencias, Campo::EscalonesDiagnostico), disabled: !rules::aplica(&encuesta.read(), Campo::EscalonesDiagnostico), value: encuesta.read().escalones_diagnostico, on_change: move |v| encuesta.write().escalones_diagnostico = v },
+                                FotosComponente { componente: ComponenteFoto::Escalones, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone() },
+                            }
+                        }
+                        if visible(Paso::EvaluacionFinal) {
+                            FormFieldSection {
+                                id: Paso::EvaluacionFinal.id_html(),
+                                title: "Evaluación Final".to_string(),
+                                grid_cols: Some(1),
+                                FormSelect { label: "Estado General del Pozo".to_string(), id: Campo::EstadoGeneralPozo.id_html(), error: error_de(&errores, Campo::EstadoGeneralPozo), value: encuesta.read().estado_general_pozo, on_change: move |v| encuesta.write().estado_general_pozo = v },
+                                FormTextArea { label: "Observaciones".to_string(), value: encuesta.read().observaciones.clone(), on_input: move |v| encuesta.write().observaciones = v },
+                            }
+                        }
+                        if visible(Paso::Conexiones) {
+                            div {
+                                id: Paso::Conexiones.id_html(),
+                                class: "p-4 border rounded-lg",
+                                h3 { class: "text-lg font-semibold text-gray-700 mb-4", "Conexiones" },
+                                div { class: "space-y-4",
+                                    for (i, conexion) in encuesta.read().lista_conexiones.iter().enumerate() {
+                                        {
+                                            let id = conexion.id.clone();
+                                            // Encuestas antiguas sin id (solo en el detalle del historial).
+                                            let clave = if id.is_empty() { format!("indice-{}", i) } else { id.clone() };
+                                            let contraida = contraidas.read().contains(&id);
+                                            let total = encuesta.read().lista_conexiones.len();
+                                            rsx! {
+                                                div {
+                                                    // Clave estable: al mover o borrar filas cada una conserva su estado.
+                                                    key: "{clave}",
+                                                    class: "p-4 border rounded bg-gray-50 space-y-4",
+                                                    div {
+                                                        class: "flex flex-wrap items-center gap-2",
+                                                        span { class: "font-semibold text-gray-700", "Conexión {i + 1}" }
+                                                        if contraida {
+                                                            span { class: "text-sm text-gray-600 truncate", "{conexion.resumen()}" }
+                                                        }
+                                                        div {
+                                                            class: "ml-auto flex gap-1",
+                                                            button {
+                                                                r#type: "button",
+                                                                title: "Subir",
+                                                                disabled: i == 0,
+                                                                class: "px-2 py-1 rounded bg-white border text-sm hover:bg-gray-100 disabled:opacity-40",
+                                                                onclick: move |_| encuesta.write().lista_conexiones.swap(i, i - 1),
+                                                                "↑"
+                                                            }
+                                                            button {
+                                                                r#type: "button",
+                                                                title: "Bajar",
+                                                                disabled: i + 1 == total,
+                                                                class: "px-2 py-1 rounded bg-white border text-sm hover:bg-gray-100 disabled:opacity-40",
+                                                                onclick: move |_| encuesta.write().lista_conexiones.swap(i, i + 1),
+                                                                "↓"
+                                                            }
+                                                            button {
+                                                                r#type: "button",
+                                                                class: "px-2 py-1 rounded bg-white border text-sm hover:bg-gray-100",
+                                                                onclick: move |_| {
+                                                                    let copia = encuesta.read().lista_conexiones[i].duplicar(outbox::nuevo_uuid());
+                                                                    encuesta.write().lista_conexiones.insert(i + 1, copia);
+                                                                },
+                                                                "Duplicar"
+                                                            }
+                                                            button {
+                                                                r#type: "button",
+                                                                class: "px-2 py-1 rounded bg-white border text-sm hover:bg-gray-100",
+                                                                onclick: {
+                                                                    let id = id.clone();
+                                                                    move |_| {
+                                                                        let mut contraidas = contraidas.write();
+                                                                        if !contraidas.remove(&id) {
+                                                                            contraidas.insert(id.clone());
+                                                                        }
+                                                                    }
+                                                                },
+                                                                if contraida { "Expandir" } else { "Contraer" }
+                                                            }
+                                                            button {
+                                                                r#type: "button",
+                                                                title: "Eliminar",
+                                                                class: "bg-red-500 text-white rounded-full hover:bg-red-600 transition h-8 w-8 flex items-center justify-center",
+                                                                onclick: move |_| {
+                                                                    let quitada = encuesta.write().lista_conexiones.remove(i);
+                                                                    let clave = encuesta.read().clave_idempotencia();
+                                                                    eliminada.set(Some((clave, i, quitada)));
+                                                                },
+                                                                "X"
+                                                            }
+                                                        }
+                                                    }
+                                                    if !contraida {
+                                                        div { class: "grid grid-cols-1 md:grid-cols-4 gap-4",
+                                                            FormSelect { label: "Dirección".to_string(), vacio: "Sin indicar", id: Campo::Conexion(i, CampoConexion::Direccion).id_html(), warning: error_de(&advertencias, Campo::Conexion(i, CampoConexion::Direccion)), value: encuesta.read().lista_conexiones[i].direccion, on_change: move |v| encuesta.write().lista_conexiones[i].direccion = v },
+                                                            SelectorReloj { label: "Posición (hora)".to_string(), id: Campo::Conexion(i, CampoConexion::PosicionReloj).id_html(), error: error_de(&errores, Campo::Conexion(i, CampoConexion::PosicionReloj)), value: encuesta.read().lista_conexiones[i].posicion_reloj, on_change: move |v| encuesta.write().lista_conexiones[i].posicion_reloj = v },
+                                                            FormMedida { label: "Cota Razante (m)".to_string(), id: Campo::Conexion(i, CampoConexion::CotaRazante).id_html(), error: error_de(&errores, Campo::Conexion(i, CampoConexion::CotaRazante)), unidad: Unidad::Metros, valor: encuesta.read().lista_conexiones[i].cota_razante, on_change: move |v| encuesta.write().lista_conexiones[i].cota_razante = v },
+                                                            FormMedida { label: "Cota Clave (m)".to_string(), id: Campo::Conexion(i, CampoConexion::CotaClave).id_html(), error: error_de(&errores, Campo::Conexion(i, CampoConexion::CotaClave)), unidad: Unidad::Metros, valor: encuesta.read().lista_conexiones[i].cota_clave, on_change: move |v| encuesta.write().lista_conexiones[i].cota_clave = v },
+                                                            FormMedida { label: "Diámetro (pulg)".to_string(), id: Campo::Conexion(i, CampoConexion::DiametroPulgadas).id_html(), error: error_de(&errores, Campo::Conexion(i, CampoConexion::DiametroPulgadas)), unidad: Unidad::Pulgadas, valor: encuesta.read().lista_conexiones[i].diametro_pulgadas, on_change: move |v| encuesta.write().lista_conexiones[i].diametro_pulgadas = v },
+                                                            FormSelect { label: "Material".to_string(), vacio: "Sin indicar", value: encuesta.read().lista_conexiones[i].material, on_change: move |v| encuesta.write().lista_conexiones[i].material = v },
+                                                            FormSelect { label: "Estado del tubo".to_string(), vacio: "Sin indicar", value: encuesta.read().lista_conexiones[i].estado, on_change: move |v| encuesta.write().lista_conexiones[i].estado = v },
+                                                            FormInput { label: "Conecta A".to_string(), value: encuesta.read().lista_conexiones[i].conecta_a.clone(), on_input: move |v| encuesta.write().lista_conexiones[i].conecta_a = v },
+                                                        }
+                                                        CalculoConexionVista {
+                                                            calculo: calculos.get(i).cloned().unwrap_or_default(),
+                                                            vecino: conexion.conecta_a.trim().to_string(),
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                                if let Some((_, posicion, quitada)) = eliminada() {
+                                    div {
+                                        class: "mt-4 flex items-center gap-3 p-3 rounded-lg bg-gray-800 text-white text-sm",
+                                        span { class: "flex-1", "Se eliminó la conexión {posicion + 1}." }
+                                        button {
+                                            r#type: "button",
+                                            class: "font-semibold text-yellow-300 hover:underline",
+                                            onclick: move |_| {
+                                                let mut lista = encuesta.write();
+                                                // Nunca dos filas con el mismo id: la clave de cada fila debe ser única.
+                                                if !lista.lista_conexiones.iter().any(|c| c.id == quitada.id) {
+                                                    let posicion = posicion.min(lista.lista_conexiones.len());
+                                                    lista.lista_conexiones.insert(posicion, quitada.clone());
+                                                }
+                                                eliminada.set(None);
+                                            },
+                                            "Deshacer"
+                                        }
+                                        button {
+                                            r#type: "button",
+                                            class: "text-gray-300 hover:text-white",
+                                            onclick: move |_| eliminada.set(None),
+                                            "×"
+                                        }
+                                    }
+                                }
+                                if encuesta.read().lista_conexiones.len() > 1 {
+                                    div {
+                                        class: "mt-4 flex gap-3 text-sm",
+                                        button {
+                                            r#type: "button",
+                                            class: "text-blue-600 hover:underline",
+                                            onclick: move |_| {
+                                                let ids = encuesta.read().lista_conexiones.iter().map(|c| c.id.clone()).collect();
+                                                contraidas.set(ids);
+                                            },
+                                            "Contraer todas"
+                                        }
+                                        button {
+                                            r#type: "button",
+                                            class: "text-blue-600 hover:underline",
+                                            onclick: move |_| contraidas.write().clear(),
+                                            "Expandir todas"
+                                        }
+                                    }
+                                }
+                                button {
+                                    r#type: "button",
+                                    class: "mt-4 bg-blue-500 text-white font-semibold py-2 px-4 rounded-lg hover:bg-blue-600 transition",
+                                    onclick: move |_| encuesta.write().lista_conexiones.push(Conexion::nueva(outbox::nuevo_uuid())),
+                                    "+ Añadir Conexión"
+                                }
+                                if !encuesta.read().lista_conexiones.is_empty() {
+                                    PlantaCamara { conexiones: encuesta.read().lista_conexiones.clone() }
+                                }
+                                EsquemaRed { red: red_pozos.clone(), pozo: red::normalizar(&encuesta.read().pozo_numero) }
+                            }
+                        }
+                        if visible(Paso::Fotografias) {
+                            FormFieldSection {
+                                id: Paso::Fotografias.id_html(),
+                                title: "Fotografías".to_string(),
+                                grid_cols: Some(1),
+                                FotosComponente { componente: ComponenteFoto::General, fotos: uploaded_files_content, ubicacion: encuesta.read().ubicacion.clone(), id: Campo::Fotos.id_html(), error: error_de(&errores, Campo::Fotos) },
+                            }
+                        }
+                        if en_pasos {
+                            div {
+                                class: "flex gap-3",
+                                if let Some(anterior) = paso().anterior() {
+                                    button {
+                                        r#type: "button",
+                                        class: "flex-1 bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition",
+                                        onclick: move |_| ir_a(anterior),
+                                        "← Anterior"
+                                    }
+                                }
+                                if let Some(siguiente) = paso().siguiente() {
+                                    button {
+                                        r#type: "button",
+                                        class: "flex-1 bg-blue-600 text-white font-semibold py-2 px-4 rounded-lg hover:bg-blue-700 transition",
+                                        onclick: move |_| {
+                                            // Solo se exige completar los campos del paso actual.
+                                            let errores = validation::validar(&encuesta.read(), &uploaded_files_content.read());
+                                            if let Some(primero) = paso().errores(&errores).first() {
+                                                mostrar_errores.set(true);
+                                                enfocar(primero.campo);
+                                                return;
+                                            }
+                                            ir_a(siguiente);
+                                        },
+                                        "Siguiente →"
+                                    }
+                                }
+                            }
+                        }
+                        if !props.solo_lectura {
+                            div {
+                                class: "mt-10 pt-6 border-t",
+                                button {
+                                    r#type: "submit",
+                                    hidden: en_pasos && !ultimo_paso,
+                                    class: "w-full bg-green-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-green-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-green-500 transition-transform transform hover:scale-105",
+                                    "Revisar y generar reporte"
+                                }
+                                div {
+                                    class: "mt-4 flex flex-col sm:flex-row gap-3",
+                                    button {
+                                        r#type: "button",
+                                        class: "flex-1 bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition",
+                                        onclick: move |_| props.on_show_drafts.call(()),
+                                        "Mis borradores"
+                                    }
+                                    button {
+                                        r#type: "button",
+                                        class: "flex-1 bg-white text-red-600 border border-red-300 font-semibold py-2 px-4 rounded-lg hover:bg-red-50 transition",
+                                        onclick: move |_| props.on_discard.call(()),
+                                        "Descartar borrador"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+// Barra de progreso y accesos directos a cada paso del formulario.
+#[derive(Props, Clone, PartialEq)]
+struct PasosEncuestaProps {
+    actual: Paso,
+    errores: Vec<validation::ErrorCampo>,
+    on_select: EventHandler<Paso>,
+}
+
+#[allow(non_snake_case)]
+fn PasosEncuesta(props: PasosEncuestaProps) -> Element {
+    let numero = props.actual.indice() + 1;
+    let total = Paso::TODOS.len();
+    let porcentaje = numero * 100 / total;
+    rsx! {
+        div {
+            id: "pasos-encuesta",
+            class: "space-y-3 scroll-mt-4",
+            div {
+                class: "flex justify-between text-sm text-gray-600",
+                span { class: "font-semibold text-gray-800", "{props.actual.titulo()}" }
+                span { "Paso {numero} de {total}" }
+            }
+            div {
+                class: "h-2 bg-gray-200 rounded-full overflow-hidden",
+                div { class: "h-full bg-blue-600 transition-all", style: "width: {porcentaje}%" }
+            }
+            div {
+                class: "flex gap-2 overflow-x-auto pb-1",
+                for p in Paso::TODOS {
+                    button {
+                        key: "{p.titulo()}",
+                        r#type: "button",
+                        class: if p == props.actual {
+                            "shrink-0 px-3 py-1 rounded-full text-xs font-semibold bg-blue-600 text-white"
+                        } else if !p.errores(&props.errores).is_empty() {
+                            "shrink-0 px-3 py-1 rounded-full text-xs font-semibold bg-red-100 text-red-700 border border-red-300"
+                        } else {
+                            "shrink-0 px-3 py-1 rounded-full text-xs font-semibold bg-gray-100 text-gray-700 hover:bg-gray-200"
+                        },
+                        onclick: move |_| props.on_select.call(p),
+                        "{p.indice() + 1}. {p.titulo()}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Selector de la posición de un tubo como la hora de un reloj.
+#[derive(Props, Clone, PartialEq)]
+struct SelectorRelojProps {
+    label: String,
+    value: Option<u8>,
+    on_change: EventHandler<Option<u8>>,
+    id: Option<String>,
+    error: Option<String>,
+}
+
+#[allow(non_snake_case)]
+fn SelectorReloj(props: SelectorRelojProps) -> Element {
+    let borde = if props.error.is_some() { "border-red-500" } else { "border-gray-300" };
+    rsx! {
+        div {
+            class: "flex flex-col",
+            label { r#for: props.id.clone(), class: "text-sm font-medium text-gray-600 mb-1", "{props.label}" },
+            select {
+                id: props.id.clone(),
+                class: "px-3 py-2 border {borde} rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition bg-white text-sm",
+                onchange: move |evt| props.on_change.call(evt.value().parse().ok()),
+                option { selected: props.value.is_none(), value: "", "Sin indicar" },
+                for hora in 1..=12u8 {
+                    option { selected: props.value == Some(hora), value: "{hora}", "{hora} h" }
+                }
+            }
+            if let Some(error) = &props.error {
+                p { class: "text-xs text-red-600 mt-1", "{error}" }
+            }
+        }
+    }
+}
+
+// Vista en planta de la cámara con cada conexión dibujada en su posición de reloj.
+#[derive(Props, Clone, PartialEq)]
+struct PlantaCamaraProps {
+    conexiones: Vec<Conexion>,
+}
+
+#[allow(non_snake_case)]
+fn PlantaCamara(props: PlantaCamaraProps) -> Element {
+    const CENTRO: f64 = 150.0;
+    const RADIO: f64 = 70.0;
+    const LARGO_TUBO: f64 = 60.0;
+    const COLOR_ENTRADA: &str = "#2563eb";
+    const COLOR_SALIDA: &str = "#16a34a";
+    // Ángulo de una hora del reloj medido desde las 12, en sentido horario.
+    let punto = |hora: u8, distancia: f64| {
+        let angulo = (hora as f64 * 30.0).to_radians();
+        (CENTRO + distancia * angulo.sin(), CENTRO - distancia * angulo.cos())
+    };
+    let sin_posicion = props
+        .conexiones
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.posicion_reloj.is_none())
+        .map(|(i, _)| (i + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    rsx! {
+        div {
+            class: "mt-6 space-y-2",
+            h4 { class: "text-sm font-semibold text-gray-700", "Planta de la cámara" }
+            svg {
+                class: "w-full max-w-xs mx-auto block",
+                view_box: "0 0 300 300",
+                defs {
+                    for (id, color) in [("flecha-entrada", COLOR_ENTRADA), ("flecha-salida", COLOR_SALIDA)] {
+                        marker {
+                            key: "{id}",
+                            id: id,
+                            view_box: "0 0 10 10",
+                            ref_x: "10",
+                            ref_y: "5",
+                            marker_width: "3",
+                            marker_height: "3",
+                            orient: "auto",
+                            path { d: "M0,0 L10,5 L0,10 z", fill: color }
+                        }
+                    }
+                }
+                circle { cx: "{CENTRO}", cy: "{CENTRO}", r: "{RADIO}", fill: "#f3f4f6", stroke: "#374151", stroke_width: "3" }
+                for hora in 1..=12u8 {
+                    {
+                        let (x, y) = punto(hora, RADIO - 10.0);
+                        rsx! {
+                            text { key: "hora-{hora}", x: "{x}", y: "{y + 3.0}", text_anchor: "middle", font_size: "8", fill: "#9ca3af", "{hora}" }
+                        }
+                    }
+                }
+                text { x: "{CENTRO}", y: "12", text_anchor: "middle", font_size: "10", fill: "#6b7280", "N" }
+                for (i, conexion) in props.conexiones.iter().enumerate() {
+                    if let Some(hora) = conexion.posicion_reloj {
+                        {
+                            let (borde_x, borde_y) = punto(hora, RADIO);
+                            let (fuera_x, fuera_y) = punto(hora, RADIO + LARGO_TUBO);
+                            let (etiqueta_x, etiqueta_y) = punto(hora, RADIO + LARGO_TUBO + 12.0);
+                            let (color, flecha) = match conexion.direccion {
+                                Some(DireccionConexion::Entrada) => (COLOR_ENTRADA, "url(#flecha-entrada)"),
+                                Some(DireccionConexion::Salida) => (COLOR_SALIDA, "url(#flecha-salida)"),
+                                None => ("#9ca3af", ""),
+                            };
+                            // Las entradas apuntan hacia la cámara y las salidas hacia afuera.
+                            let (x1, y1, x2, y2) = match conexion.direccion {
+                                Some(DireccionConexion::Salida) => (borde_x, borde_y, fuera_x, fuera_y),
+                                _ => (fuera_x, fuera_y, borde_x, borde_y),
+                            };
+                            let grosor = conexion.diametro_pulgadas.map(|d| (d / 2.0).clamp(3.0, 12.0)).unwrap_or(4.0);
+                            rsx! {
+                                g {
+                                    key: "tubo-{i}",
+                                    line {
+                                        x1: "{x1}", y1: "{y1}", x2: "{x2}", y2: "{y2}",
+                                        stroke: color,
+                                        stroke_width: "{grosor}",
+                                        stroke_linecap: "round",
+                                        marker_end: flecha,
+                                    }
+                                    text { x: "{etiqueta_x}", y: "{etiqueta_y + 4.0}", text_anchor: "middle", font_size: "11", font_weight: "bold", fill: color, "{i + 1}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            div {
+                class: "flex justify-center gap-4 text-xs text-gray-600",
+                span { class: "text-blue-600 font-semibold", "● Entrada" }
+                span { class: "text-green-600 font-semibold", "● Salida" }
+            }
+            if !sin_posicion.is_empty() {
+                p {
+                    class: "text-xs text-gray-500 text-center",
+                    "Sin posición indicada: conexión(es) {sin_posicion}."
+                }
+            }
+        }
+    }
+}
+
+// Valores derivados de las cotas, de solo lectura, bajo cada conexión.
+#[derive(Props, Clone, PartialEq)]
+struct CalculoConexionVistaProps {
+    calculo: CalculoConexion,
+    vecino: String,
+}
+
+#[allow(non_snake_case)]
+fn CalculoConexionVista(props: CalculoConexionVistaProps) -> Element {
+    let calculo = props.calculo;
+    let vecino = if props.vecino.is_empty() { "el pozo vecino".to_string() } else { props.vecino };
+    let sentido = match calculo.sentido {
+        Some(SentidoFlujo::Sale) => format!("Sale hacia {}", vecino),
+        Some(SentidoFlujo::Llega) => format!("Llega desde {}", vecino),
+        None => "Sentido sin calcular".to_string(),
+    };
+    rsx! {
+        div {
+            class: "mt-3 flex flex-wrap gap-x-6 gap-y-1 text-xs text-gray-600",
+            span { "Profundidad: {texto_medida(calculo.profundidad_m, Unidad::Metros)}" }
+            span { "Longitud: {texto_medida(calculo.longitud_m, Unidad::Metros)}" }
+            span { "Pendiente: {texto_pendiente(calculo.pendiente_pct)}" }
+            span { "{sentido}" }
+            if calculo.ubicacion_imprecisa {
+                span { class: "text-yellow-700", "Pendiente sin calcular: la ubicación de este pozo o de {vecino} tiene una precisión peor que ±{geo::PRECISION_ACEPTABLE_M:.0} m. Capture de nuevo el GPS." }
+            } else if calculo.pendiente_pct.is_none() {
+                span { class: "text-gray-400", "La pendiente requiere la encuesta de {vecino} con su cota clave y la ubicación de ambos pozos." }
+            }
+        }
+    }
+}
+
+// Vecinos del pozo en la red armada con las encuestas guardadas en el equipo.
+#[derive(Props, Clone, PartialEq)]
+struct EsquemaRedProps {
+    red: red::Red,
+    pozo: String,
+}
+
+#[allow(non_snake_case)]
+fn EsquemaRed(props: EsquemaRedProps) -> Element {
+    let pozo = props.pozo;
+    if pozo.is_empty() {
+        return rsx! {
+            p { class: "mt-6 text-sm text-gray-500", "Ingrese el número de pozo para ver sus conexiones en la red." }
+        };
+    }
+    let red = props.red;
+    let arriba: Vec<red::Tramo> = red.aguas_arriba(&pozo).into_iter().cloned().collect();
+    let abajo: Vec<red::Tramo> = red.aguas_abajo(&pozo).into_iter().cloned().collect();
+    let problemas = red.problemas_de(&pozo);
+
+    const ANCHO: f64 = 600.0;
+    const FILA: f64 = 56.0;
+    let filas = arriba.len().max(abajo.len()).max(1) as f64;
+    let alto = filas * FILA + 24.0;
+    let centro_y = alto / 2.0;
+    let fila_y = |i: usize, total: usize| alto / 2.0 + (i as f64 - (total as f64 - 1.0) / 2.0) * FILA;
+    let inspeccionado = |p: &str| red.inspeccionados.contains(p);
+    let diametro = |t: &red::Tramo| t.diametro_pulgadas.map(|d| format!("{} pulg", medidas::formatear(Some(d)))).unwrap_or_default();
+
+    rsx! {
+        div {
+            class: "mt-6 space-y-3",
+            h4 { class: "text-sm font-semibold text-gray-700", "Esquema de la red" }
+            if arriba.is_empty() && abajo.is_empty() {
+                p { class: "text-sm text-gray-500", "Sin tramos registrados hacia o desde este pozo." }
+            } else {
+                svg {
+                    class: "w-full bg-gray-50 border rounded-lg",
+                    view_box: "0 0 {ANCHO} {alto}",
+                    defs {
+                        marker {
+                            id: "flecha-red",
+                            view_box: "0 0 10 10",
+                            ref_x: "10",
+                            ref_y: "5",
+                            marker_width: "6",
+                            marker_height: "6",
+                            orient: "auto",
+                            path { d: "M0,0 L10,5 L0,10 z", fill: "#6b7280" }
+                        }
+                    }
+                    for (i, tramo) in arriba.iter().enumerate() {
+                        g {
+                            key: "arriba-{i}",
+                            line { x1: "150", y1: "{fila_y(i, arriba.len())}", x2: "240", y2: "{centro_y}", stroke: "#6b7280", stroke_width: "2", marker_end: "url(#flecha-red)" }
+                            text { x: "195", y: "{(fila_y(i, arriba.len()) + centro_y) / 2.0 - 6.0}", text_anchor: "middle", font_size: "11", fill: "#374151", "{diametro(tramo)}" }
+                            NodoRed { x: 90.0, y: fila_y(i, arriba.len()), nombre: tramo.desde.clone(), inspeccionado: inspeccionado(&tramo.desde), actual: false }
+                        }
+                    }
+                    for (i, tramo) in abajo.iter().enumerate() {
+                        g {
+                            key: "abajo-{i}",
+                            line { x1: "360", y1: "{centro_y}", x2: "450", y2: "{fila_y(i, abajo.len())}", stroke: "#6b7280", stroke_width: "2", marker_end: "url(#flecha-red)" }
+                            text { x: "405", y: "{(fila_y(i, abajo.len()) + centro_y) / 2.0 - 6.0}", text_anchor: "middle", font_size: "11", fill: "#374151", "{diametro(tramo)}" }
+                            NodoRed { x: 510.0, y: fila_y(i, abajo.len()), nombre: tramo.hasta.clone(), inspeccionado: inspeccionado(&tramo.hasta), actual: false }
+                        }
+                    }
+                    NodoRed { x: 300.0, y: centro_y, nombre: pozo.clone(), inspeccionado: true, actual: true }
+                }
+                p { class: "text-xs text-gray-500", "Aguas arriba a la izquierda, aguas abajo a la derecha. Los pozos punteados no tienen encuesta." }
+            }
+            if !problemas.is_empty() {
+                ul {
+                    class: "p-3 rounded-lg bg-yellow-50 border border-yellow-200 text-sm text-yellow-800 space-y-1",
+                    for (i, problema) in problemas.iter().enumerate() {
+                        li { key: "problema-{i}", "{problema.descripcion()}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct NodoRedProps {
+    x: f64,
+    y: f64,
+    nombre: String,
+    inspeccionado: bool,
+    actual: bool,
+}
+
+#[allow(non_snake_case)]
+fn NodoRed(props: NodoRedProps) -> Element {
+    let (relleno, borde) = match (props.actual, props.inspeccionado) {
+        (true, _) => ("#2563eb", "#1d4ed8"),
+        (false, true) => ("#dbeafe", "#2563eb"),
+        (false, false) => ("#ffffff", "#9ca3af"),
+    };
+    let texto = if props.actual { "#ffffff" } else { "#1f2937" };
+    rsx! {
+        rect {
+            x: "{props.x - 60.0}",
+            y: "{props.y - 18.0}",
+            width: "120",
+            height: "36",
+            rx: "18",
+            fill: relleno,
+            stroke: borde,
+            stroke_width: "2",
+            stroke_dasharray: if props.inspeccionado { "none" } else { "4 3" },
+        }
+        text {
+            x: "{props.x}",
+            y: "{props.y + 4.0}",
+            text_anchor: "middle",
+            font_size: "12",
+            font_weight: "bold",
+            fill: texto,
+            "{props.nombre}"
+        }
+    }
+}
+
+// --- Fotografías por componente ---
+
+#[derive(Props, Clone, PartialEq)]
+struct FotosComponenteProps {
+    componente: ComponenteFoto,
+    fotos: Signal<Vec<Foto>>,
+    // Ubicación del pozo al momento de tomar las fotos, si ya se capturó.
+    ubicacion: Option<Ubicacion>,
+    id: Option<String>,
+    error: Option<String>,
+}
+
+// Espacio de fotos de una sección. Todas las fotos viven en una sola lista;
+// cada sección muestra y agrega únicamente las de su componente.
+#[allow(non_snake_case)]
+fn FotosComponente(props: FotosComponenteProps) -> Element {
+    let mut fotos = props.fotos;
+    let procesando = use_signal(|| None::<String>);
+    // Índices (en la lista global) de la foto arrastrada y de la abierta en el visor.
+    let mut arrastrando = use_signal(|| None::<usize>);
+    let mut visor = use_signal(|| None::<usize>);
+    let mut mostrar_camara = use_signal(|| false);
+    let mut editor = use_signal(|| None::<usize>);
+    let componente = props.componente;
+    let id_input = format!("fotos-{:?}", componente).to_lowercase();
+    let propias: Vec<(usize, Foto)> = fotos
+        .read()
+        .iter()
+        .enumerate()
+        .filter(|(_, foto)| foto.componente == componente)
+        .map(|(i, foto)| (i, foto.clone()))
+        .collect();
+
+    rsx! {
+        div {
+            class: "col-span-full flex flex-col gap-2 pt-2",
+            div {
+                class: "flex flex-wrap items-center gap-3",
+                label {
+                    id: props.id.clone(),
+                    tabindex: "-1",
+                    r#for: "{id_input}",
+                    class: "bg-gray-600 text-white text-sm font-semibold py-2 px-4 rounded-lg hover:bg-gray-700 transition cursor-pointer",
+                    "+ Fotos de {componente}"
+                }
+                if camara::disponible() {
+                    button {
+                        r#type: "button",
+                        class: "bg-blue-500 text-white text-sm font-semibold py-2 px-4 rounded-lg hover:bg-blue-600 transition",
+                        onclick: move |_| mostrar_camara.set(true),
+                        "Cámara"
+                    }
+                } else {
+                    label {
+                        r#for: "{id_input}-camara",
+                        class: "bg-blue-500 text-white text-sm font-semibold py-2 px-4 rounded-lg hover:bg-blue-600 transition cursor-pointer",
+                        "Cámara"
+                    }
+                }
+                span { class: "text-xs text-gray-500", "{propias.len()} foto(s)" }
+            }
+            input {
+                id: "{id_input}",
+                r#type: "file",
+                multiple: true,
+                accept: "image/*",
+                class: "hidden",
+                onchange: {
+                    let ubicacion = props.ubicacion.clone();
+                    move |evt: FormEvent| {
+                        let ubicacion = ubicacion.clone();
+                        async move {
+                            if let Some(archivos) = evt.files() {
+                                agregar_archivos(archivos, fotos, procesando, componente, ubicacion).await;
+                            }
+                        }
+                    }
+                }
+            }
+            // Abre directamente la cámara trasera del teléfono cuando no hay visor propio.
+            input {
+                id: "{id_input}-camara",
+                r#type: "file",
+                accept: "image/*",
+                capture: "environment",
+                class: "hidden",
+                onchange: {
+                    let ubicacion = props.ubicacion.clone();
+                    move |evt: FormEvent| {
+                        let ubicacion = ubicacion.clone();
+                        async move {
+                            if let Some(archivos) = evt.files() {
+                                agregar_archivos(archivos, fotos, procesando, componente, ubicacion).await;
+                            }
+                        }
+                    }
+                }
+            }
+            if mostrar_camara() {
+                CamaraEnVivo {
+                    componente: componente,
+                    on_capture: {
+                        let ubicacion = props.ubicacion.clone();
+                        move |imagen: ImagenComprimida| {
+                            fotos.write().push(nueva_foto(imagen::nombre_unico(&imagen.nombre), componente, ubicacion.clone(), imagen.contenido, None));
+                        }
+                    },
+                    on_close: move |_| mostrar_camara.set(false),
+                }
+            }
+            if let Some(nombre) = procesando() {
+                p { class: "text-xs text-blue-600", "Comprimiendo {nombre}..." }
+            }
+            if let Some(error) = &props.error {
+                p { class: "text-xs text-red-600", "{error}" }
+            }
+            div {
+                class: "grid grid-cols-1 sm:grid-cols-2 lg:grid-cols-3 gap-3",
+                for (posicion, (i, foto)) in propias.iter().cloned().enumerate() {
+                    div {
+                        key: "foto-{i}-{foto.nombre}",
+                        class: if arrastrando() == Some(i) { "flex flex-col gap-2 p-2 border rounded-lg bg-blue-50 opacity-60" } else { "flex flex-col gap-2 p-2 border rounded-lg bg-white" },
+                        draggable: true,
+                        ondragstart: move |_| arrastrando.set(Some(i)),
+                        ondragend: move |_| arrastrando.set(None),
+                        prevent_default: "ondragover",
+                        ondragover: move |_| {},
+                        ondrop: move |_| {
+                            if let Some(origen) = arrastrando.take() {
+                                mover_foto(fotos, origen, i);
+                            }
+                        },
+                        button {
+                            r#type: "button",
+                            class: "block w-full h-32 bg-gray-100 rounded overflow-hidden",
+                            title: "Ver en pantalla completa",
+                            onclick: move |_| visor.set(Some(i)),
+                            Miniatura { nombre: foto.nombre.clone(), contenido: foto.contenido.clone(), class: "w-full h-full object-cover" }
+                        }
+                        div {
+                            class: "flex flex-col min-w-0 text-sm",
+                            span { class: "text-gray-700 truncate", "{foto.nombre}" }
+                            span {
+                                class: "text-xs text-gray-500",
+                                match foto.tamano_original {
+                                    _ if foto.en_servidor => "Ya enviada".to_string(),
+                                    Some(original) => format!("{} → {}", formatear_tamano(original), formatear_tamano(foto.contenido.len())),
+                                    None => formatear_tamano(foto.contenido.len()),
+                                }
+                            }
+                        }
+                        input {
+                            r#type: "text",
+                            placeholder: "Descripción (opcional)",
+                            class: "px-3 py-1 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm",
+                            value: "{foto.descripcion}",
+                            oninput: move |evt| {
+                                if let Some(foto) = fotos.write().get_mut(i) {
+                                    foto.descripcion = evt.value();
+                                }
+                            },
+                        }
+                        // Botones para ordenar en pantallas táctiles, donde no se puede arrastrar.
+                        div {
+                            class: "flex gap-2",
+                            button {
+                                r#type: "button",
+                                disabled: posicion == 0,
+                                class: "flex-1 bg-gray-200 text-gray-700 text-sm py-1 rounded hover:bg-gray-300 disabled:opacity-40",
+                                title: "Mover antes",
+                                onclick: {
+                                    let anterior = posicion.checked_sub(1).map(|p| propias[p].0);
+                                    move |_| if let Some(destino) = anterior { mover_foto(fotos, i, destino) }
+                                },
+                                "↑"
+                            }
+                            button {
+                                r#type: "button",
+                                disabled: posicion + 1 == propias.len(),
+                                class: "flex-1 bg-gray-200 text-gray-700 text-sm py-1 rounded hover:bg-gray-300 disabled:opacity-40",
+                                title: "Mover después",
+                                onclick: {
+                                    let siguiente = propias.get(posicion + 1).map(|p| p.0);
+                                    move |_| if let Some(destino) = siguiente { mover_foto(fotos, i, destino) }
+                                },
+                                "↓"
+                            }
+                            // De una foto ya enviada no queda la imagen para anotarla.
+                            if !foto.en_servidor {
+                                button {
+                                    r#type: "button",
+                                    class: "flex-1 bg-gray-200 text-gray-700 text-sm py-1 rounded hover:bg-gray-300",
+                                    onclick: move |_| editor.set(Some(i)),
+                                    "Anotar"
+                                }
+                            }
+                            button {
+                                r#type: "button",
+                                class: "flex-1 bg-white text-red-600 border border-red-300 text-sm py-1 rounded hover:bg-red-50",
+                                onclick: move |_| {
+                                    if confirmar("¿Quitar esta foto?") {
+                                        fotos.write().remove(i);
+                                    }
+                                },
+                                "Quitar"
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(i) = editor() {
+                if let Some(foto) = fotos.read().get(i).cloned() {
+                    EditorAnotaciones {
+                        foto: foto.clone(),
+                        on_save: move |imagen: ImagenComprimida| {
+                            // La versión anotada queda junto a la original, que no se modifica.
+                            let mut anotada = nueva_foto(imagen.nombre, foto.componente, foto.ubicacion.clone(), imagen.contenido, None);
+                            anotada.descripcion = foto.descripcion.clone();
+                            anotada.anotacion_de = Some(foto.nombre.clone());
+                            let mut lista = fotos.write();
+                            let posicion = (i + 1).min(lista.len());
+                            lista.insert(posicion, anotada);
+                            editor.set(None);
+                        },
+                        on_close: move |_| editor.set(None),
+                    }
+                }
+            }
+            if let Some(abierta) = visor() {
+                if let Some(posicion) = propias.iter().position(|(i, _)| *i == abierta) {
+                    VisorFoto {
+                        foto: propias[posicion].1.clone(),
+                        anterior: posicion.checked_sub(1).map(|p| propias[p].0),
+                        siguiente: propias.get(posicion + 1).map(|p| p.0),
+                        on_navigate: move |i| visor.set(Some(i)),
+                        on_close: move |_| visor.set(None),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn nueva_foto(nombre: String, componente: ComponenteFoto, ubicacion: Option<Ubicacion>, contenido: Vec<u8>, tamano_original: Option<usize>) -> Foto {
+    Foto {
+        nombre,
+        componente,
+        descripcion: String::new(),
+        capturada_en: geo::fecha_iso(outbox::ahora()),
+        ubicacion,
+        tamano_original,
+        anotacion_de: None,
+        en_servidor: false,
+        contenido,
+    }
+}
+
+/// Comprime y agrega al componente los archivos elegidos en un `input` de fotos.
+async fn agregar_archivos(
+    archivos: std::sync::Arc<dyn dioxus::html::FileEngine>,
+    mut fotos: Signal<Vec<Foto>>,
+    mut procesando: Signal<Option<String>>,
+    componente: ComponenteFoto,
+    ubicacion: Option<Ubicacion>,
+) {
+    let ajustes = config::leer_ajustes().imagenes;
+    for file_name in &archivos.files() {
+        let Some(contents) = archivos.read_file(file_name).await else { continue };
+        procesando.set(Some(file_name.clone()));
+        // Si la compresión falla se guarda el archivo tal cual.
+        let foto = match imagen::comprimir(file_name, &contents, &ajustes).await {
+            Ok(comprimida) => nueva_foto(imagen::nombre_unico(&comprimida.nombre), componente, ubicacion.clone(), comprimida.contenido, Some(contents.len())),
+            Err(e) => {
+                log::error!("No se pudo comprimir {}: {}", file_name, e);
+                nueva_foto(imagen::nombre_unico(file_name), componente, ubicacion.clone(), contents, None)
+            }
+        };
+        fotos.write().push(foto);
+    }
+    procesando.set(None);
+}
+
+/// Mueve la foto `origen` a la posición `destino` de la lista global.
+fn mover_foto(mut fotos: Signal<Vec<Foto>>, origen: usize, destino: usize) {
+    let mut lista = fotos.write();
+    if origen == destino || origen >= lista.len() || destino >= lista.len() {
+        return;
+    }
+    let foto = lista.remove(origen);
+    lista.insert(destino, foto);
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct MiniaturaProps {
+    nombre: String,
+    contenido: Vec<u8>,
+    class: String,
+}
+
+// Componente aparte para que la URL en base64 solo se recalcule cuando cambia la imagen.
+#[allow(non_snake_case)]
+fn Miniatura(props: MiniaturaProps) -> Element {
+    if props.contenido.is_empty() {
+        // Foto ya enviada: la imagen está en el backend y aquí solo queda la referencia.
+        return rsx! {
+            div {
+                class: "{props.class} flex items-center justify-center bg-gray-200 text-xs text-gray-500 text-center p-2",
+                "Foto en el servidor"
+            }
+        };
+    }
+    let src = imagen::url_datos(&props.nombre, &props.contenido);
+    rsx! {
+        img { src: "{src}", alt: "{props.nombre}", class: "{props.class}", draggable: false }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct VisorFotoProps {
+    foto: Foto,
+    anterior: Option<usize>,
+    siguiente: Option<usize>,
+    on_navigate: EventHandler<usize>,
+    on_close: EventHandler<()>,
+}
+
+// Visor a pantalla completa para revisar el enfoque antes de dejar el sitio.
+#[allow(non_snake_case)]
+fn VisorFoto(props: VisorFotoProps) -> Element {
+    let anterior = props.anterior;
+    let siguiente = props.siguiente;
+    rsx! {
+        div {
+            class: "fixed inset-0 z-50 bg-black flex flex-col",
+            div {
+                class: "flex items-center justify-between p-3 text-white text-sm",
+                span { class: "truncate", "{props.foto.componente} · {props.foto.nombre}" }
+                button {
+                    r#type: "button",
+                    class: "bg-white bg-opacity-20 px-4 py-2 rounded-lg hover:bg-opacity-30",
+                    onclick: move |_| props.on_close.call(()),
+                    "Cerrar"
+                }
+            }
+            div {
+                class: "flex-1 min-h-0 flex items-center justify-center",
+                Miniatura { nombre: props.foto.nombre.clone(), contenido: props.foto.contenido.clone(), class: "max-w-full max-h-full object-contain" }
+            }
+            if !props.foto.descripcion.is_empty() {
+                p { class: "text-white text-sm text-center px-3", "{props.foto.descripcion}" }
+            }
+            div {
+                class: "flex gap-3 p-3",
+                button {
+                    r#type: "button",
+                    disabled: anterior.is_none(),
+                    class: "flex-1 bg-white bg-opacity-20 text-white py-2 rounded-lg disabled:opacity-30",
+                    onclick: move |_| if let Some(i) = anterior { props.on_navigate.call(i) },
+                    "← Anterior"
+                }
+                button {
+                    r#type: "button",
+                    disabled: siguiente.is_none(),
+                    class: "flex-1 bg-white bg-opacity-20 text-white py-2 rounded-lg disabled:opacity-30",
+                    onclick: move |_| if let Some(i) = siguiente { props.on_navigate.call(i) },
+                    "Siguiente →"
+                }
+            }
         }
     }
 }
 
-async fn send_survey_request(encuesta: Encuesta, files_content: Vec<(String, Vec<u8>)>) -> Result<(), reqwest::Error> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/ingestar-encuesta", BACKEND_URL);
-    let json_data = serde_json::to_string(&encuesta).expect("No se pudo serializar la encuesta a JSON");
-
-    log::info!("Conexión: Intentando enviar a URL: {}", url);
-    log::info!("Conexión: JSON de la encuesta: {}", json_data);
-
-    let mut form = reqwest::multipart::Form::new().text("data", json_data);
+const ID_LIENZO_ANOTACION: &str = "lienzo-anotacion";
 
-    for (file_name, file_bytes) in files_content {
-        log::info!("Adjuntando archivo: {}", file_name);
-        let part = Part::bytes(file_bytes).file_name(file_name);
-        form = form.part("fotos", part);
-    }
+fn preguntar(mensaje: &str) -> Option<String> {
+    web_sys::window()
+        .and_then(|w| w.prompt_with_message(mensaje).ok().flatten())
+        .filter(|texto| !texto.trim().is_empty())
+}
 
-    let response = client.post(url).multipart(form).send().await?;
-    
-    if response.status() == 401 {
-        log::error!("Error 401: No autorizado. El token puede haber expirado o sido revocado. Se necesita re-autenticación.");
+/// Convierte la posición del puntero en coordenadas de la imagen.
+fn punto_en_lienzo(evt: &PointerEvent, ancho: f64, alto: f64) -> Option<Punto> {
+    let lienzo = web_sys::window()?.document()?.get_element_by_id(ID_LIENZO_ANOTACION)?;
+    let rect = lienzo.get_bounding_client_rect();
+    if rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return None;
     }
-    
-    Ok(())
+    let cliente = evt.client_coordinates();
+    Some(((cliente.x - rect.left()) / rect.width() * ancho, (cliente.y - rect.top()) / rect.height() * alto))
 }
 
-fn main() {
-    wasm_logger::init(wasm_logger::Config::default());
-    launch(App);
+#[derive(Props, Clone, PartialEq)]
+struct EditorAnotacionesProps {
+    foto: Foto,
+    on_save: EventHandler<ImagenComprimida>,
+    on_close: EventHandler<()>,
 }
 
 #[allow(non_snake_case)]
-fn App() -> Element {
-    let auth_status = use_resource(check_auth_status);
-    let encuesta = use_signal(Encuesta::default);
-    let uploaded_files_content = use_signal(Vec::<(String, Vec<u8>)>::new);
-
-    let send_survey = use_coroutine(|mut rx: UnboundedReceiver<(Encuesta, Vec<(String, Vec<u8>)>)>| async move {
-        while let Some((encuesta_data, files)) = rx.next().await {
-            log::info!("Enviando encuesta...");
-            match send_survey_request(encuesta_data, files).await {
-                Ok(_) => log::info!("¡Encuesta enviada con éxito!"),
-                Err(e) => log::error!("Error al enviar la encuesta: {:?}", e),
-            }
+fn EditorAnotaciones(props: EditorAnotacionesProps) -> Element {
+    let contenido = props.foto.contenido.clone();
+    // Tamaño real de la foto: los trazos se guardan en esas coordenadas.
+    let dimensiones = use_resource(move || {
+        let contenido = contenido.clone();
+        async move {
+            let bitmap = imagen::decodificar(&contenido).await?;
+            let dimensiones = (bitmap.width() as f64, bitmap.height() as f64);
+            bitmap.close();
+            Ok::<_, String>(dimensiones)
         }
     });
+    let mut herramienta = use_signal(|| Herramienta::Flecha);
+    let mut trazos = use_signal(Vec::<Trazo>::new);
+    let mut en_curso = use_signal(|| None::<Trazo>);
+    let mut guardando = use_signal(|| false);
+    let mut error = use_signal(|| None::<String>);
 
-    rsx! {
-        style { "{TAILWIND_CSS}" }
+    let (ancho, alto) = match &*dimensiones.read() {
+        Some(Ok(dimensiones)) => *dimensiones,
+        Some(Err(e)) => {
+            let e = e.clone();
+            return rsx! {
+                div {
+                    class: "fixed inset-0 z-50 bg-black flex flex-col items-center justify-center gap-4 text-white",
+                    p { "{e}" }
+                    button { r#type: "button", class: "bg-white bg-opacity-20 px-4 py-2 rounded-lg", onclick: move |_| props.on_close.call(()), "Cerrar" }
+                }
+            };
+        }
+        None => return rsx! { div { class: "fixed inset-0 z-50 bg-black flex items-center justify-center text-white", "Cargando foto..." } },
+    };
+    let grosor = anotacion::grosor(ancho, alto);
+    let tamano_texto = anotacion::tamano_texto(ancho, alto);
+    let visibles: Vec<Trazo> = trazos.read().iter().cloned().chain(en_curso()).collect();
+    let foto = props.foto.clone();
 
-        main {
-            class: "bg-gradient-to-br from-blue-50 to-indigo-100 min-h-screen font-sans flex items-center justify-center py-8",
-            
-            match auth_status.read().as_ref() {
-                Some(AuthStatus::Authenticated) => rsx! {
-                    SurveyForm {
-                        encuesta: encuesta,
-                        uploaded_files_content: uploaded_files_content,
-                        on_submit: move |_| {
-                            let current_survey = encuesta.read().clone();
-                            let files_to_send = uploaded_files_content.read().clone();
-                            send_survey.send((current_survey, files_to_send));
+    rsx! {
+        div {
+            class: "fixed inset-0 z-50 bg-black flex flex-col",
+            div {
+                class: "flex flex-wrap items-center gap-2 p-3",
+                for opcion in Herramienta::TODAS {
+                    button {
+                        r#type: "button",
+                        class: if herramienta() == opcion { "bg-red-500 text-white text-sm px-3 py-2 rounded-lg" } else { "bg-white bg-opacity-20 text-white text-sm px-3 py-2 rounded-lg" },
+                        onclick: move |_| herramienta.set(opcion),
+                        "{opcion.etiqueta()}"
+                    }
+                }
+                button {
+                    r#type: "button",
+                    disabled: trazos.read().is_empty(),
+                    class: "bg-white bg-opacity-20 text-white text-sm px-3 py-2 rounded-lg disabled:opacity-30",
+                    onclick: move |_| { trazos.write().pop(); },
+                    "Deshacer"
+                }
+            }
+            div {
+                class: "flex-1 min-h-0 flex items-center justify-center p-2",
+                div {
+                    class: "relative",
+                    Miniatura { nombre: props.foto.nombre.clone(), contenido: props.foto.contenido.clone(), class: "block max-w-full max-h-[75vh]" }
+                    svg {
+                        id: ID_LIENZO_ANOTACION,
+                        class: "absolute inset-0 w-full h-full cursor-crosshair",
+                        style: "touch-action: none;",
+                        view_box: "0 0 {ancho} {alto}",
+                        onpointerdown: move |evt| {
+                            let Some(p) = punto_en_lienzo(&evt, ancho, alto) else { return };
+                            match herramienta() {
+                                Herramienta::Flecha => en_curso.set(Some(Trazo::Flecha { desde: p, hasta: p })),
+                                Herramienta::Circulo => en_curso.set(Some(Trazo::Circulo { centro: p, radio: 0.0 })),
+                                Herramienta::Libre => en_curso.set(Some(Trazo::Libre(vec![p]))),
+                                Herramienta::Texto => {
+                                    if let Some(texto) = preguntar("Texto de la etiqueta") {
+                                        trazos.write().push(Trazo::Texto { posicion: p, texto });
+                                    }
+                                }
+                            }
+                        },
+                        onpointermove: move |evt| {
+                            if en_curso.peek().is_none() {
+                                return;
+                            }
+                            let Some(p) = punto_en_lienzo(&evt, ancho, alto) else { return };
+                            if let Some(trazo) = en_curso.write().as_mut() {
+                                match trazo {
+                                    Trazo::Flecha { hasta, .. } => *hasta = p,
+                                    Trazo::Circulo { centro, radio } => *radio = anotacion::distancia(*centro, p),
+                                    Trazo::Libre(puntos) => puntos.push(p),
+                                    Trazo::Texto { .. } => {}
+                                }
+                            }
+                        },
+                        onpointerup: move |_| {
+                            // Se descartan los toques accidentales sin movimiento.
+                            let valido = match en_curso.take() {
+                                Some(Trazo::Flecha { desde, hasta }) if anotacion::distancia(desde, hasta) > grosor * 2.0 => Some(Trazo::Flecha { desde, hasta }),
+                                Some(Trazo::Circulo { centro, radio }) if radio > grosor * 2.0 => Some(Trazo::Circulo { centro, radio }),
+                                Some(Trazo::Libre(puntos)) if puntos.len() > 1 => Some(Trazo::Libre(puntos)),
+                                _ => None,
+                            };
+                            if let Some(trazo) = valido {
+                                trazos.write().push(trazo);
+                            }
+                        },
+                        onpointerleave: move |_| en_curso.set(None),
+                        for (n, trazo) in visibles.into_iter().enumerate() {
+                            g { key: "trazo-{n}", {trazo_svg(&trazo, grosor, tamano_texto)} }
                         }
                     }
-                },
-                Some(AuthStatus::Unauthenticated) => rsx! {
-                    LoginPage {}
-                },
-                None => rsx! {
-                     LoadingScreen {}
                 }
             }
+            if let Some(error) = error() {
+                p { class: "text-sm text-red-300 text-center px-3", "{error}" }
+            }
+            div {
+                class: "flex gap-3 p-3",
+                button {
+                    r#type: "button",
+                    class: "flex-1 bg-white bg-opacity-20 text-white py-2 rounded-lg",
+                    onclick: move |_| props.on_close.call(()),
+                    "Cancelar"
+                }
+                button {
+                    r#type: "button",
+                    disabled: trazos.read().is_empty() || guardando(),
+                    class: "flex-1 bg-green-600 text-white font-semibold py-2 rounded-lg disabled:opacity-40",
+                    onclick: move |_| {
+                        let foto = foto.clone();
+                        guardando.set(true);
+                        spawn(async move {
+                            let ajustes = config::leer_ajustes().imagenes;
+                            let dibujados = trazos.read().clone();
+                            match anotacion::exportar(&foto, &dibujados, &ajustes).await {
+                                Ok(imagen) => props.on_save.call(imagen),
+                                Err(e) => error.set(Some(e)),
+                            }
+                            guardando.set(false);
+                        });
+                    },
+                    if guardando() { "Guardando..." } else { "Guardar anotada" }
+                }
+            }
+        }
+    }
+}
+
+fn trazo_svg(trazo: &Trazo, grosor: f64, tamano_texto: f64) -> Element {
+    let color = anotacion::COLOR;
+    match trazo {
+        Trazo::Flecha { desde, hasta } => {
+            let punta = anotacion::punta_flecha(*desde, *hasta, grosor)
+                .iter()
+                .map(|(x, y)| format!("{},{}", x, y))
+                .collect::<Vec<_>>()
+                .join(" ");
+            rsx! {
+                line { x1: "{desde.0}", y1: "{desde.1}", x2: "{hasta.0}", y2: "{hasta.1}", stroke: color, stroke_width: "{grosor}", stroke_linecap: "round" }
+                polygon { points: "{punta}", fill: color }
+            }
+        }
+        Trazo::Circulo { centro, radio } => rsx! {
+            circle { cx: "{centro.0}", cy: "{centro.1}", r: "{radio}", fill: "none", stroke: color, stroke_width: "{grosor}" }
+        },
+        Trazo::Libre(puntos) => {
+            let puntos = puntos.iter().map(|(x, y)| format!("{},{}", x, y)).collect::<Vec<_>>().join(" ");
+            rsx! {
+                polyline { points: "{puntos}", fill: "none", stroke: color, stroke_width: "{grosor}", stroke_linecap: "round", stroke_linejoin: "round" }
+            }
         }
+        Trazo::Texto { posicion, texto } => rsx! {
+            text {
+                x: "{posicion.0}",
+                y: "{posicion.1}",
+                font_size: "{tamano_texto}",
+                font_weight: "bold",
+                font_family: "sans-serif",
+                dominant_baseline: "middle",
+                fill: color,
+                stroke: "#ffffff",
+                stroke_width: "{grosor * 1.5}",
+                paint_order: "stroke",
+                "{texto}"
+            }
+        },
     }
 }
 
+const ID_VISOR_CAMARA: &str = "visor-camara";
+
+#[derive(Props, Clone, PartialEq)]
+struct CamaraEnVivoProps {
+    componente: ComponenteFoto,
+    on_capture: EventHandler<ImagenComprimida>,
+    on_close: EventHandler<()>,
+}
+
+// Visor de cámara a pantalla completa. Cada foto tomada se agrega de
+// inmediato al componente; el visor sigue abierto hasta pulsar "Listo".
 #[allow(non_snake_case)]
-fn LoadingScreen() -> Element {
+fn CamaraEnVivo(props: CamaraEnVivoProps) -> Element {
+    // El stream no vive en una señal para poder detenerlo al desmontar el componente.
+    let activo = use_hook(|| Rc::new(RefCell::new(None::<web_sys::MediaStream>)));
+    let mut error = use_signal(|| None::<String>);
+    let mut con_linterna = use_signal(|| false);
+    let mut linterna = use_signal(|| false);
+    let mut rafaga = use_signal(|| false);
+    let mut capturando = use_signal(|| false);
+    let mut tomadas = use_signal(|| 0usize);
+    {
+        let activo = activo.clone();
+        use_drop(move || {
+            if let Some(stream) = activo.borrow_mut().take() {
+                camara::detener(&stream);
+            }
+        });
+    }
+    let on_capture = props.on_capture;
+
     rsx! {
         div {
-            class: "text-center",
-            h1 { class: "text-3xl font-bold text-gray-700", "Verificando autenticación..." },
-            p { class: "text-gray-500 mt-2", "Por favor, espere un momento." }
+            class: "fixed inset-0 z-50 bg-black flex flex-col",
+            div {
+                class: "flex items-center justify-between p-3 text-white text-sm",
+                span { "{props.componente} · {tomadas} foto(s) tomada(s)" }
+                button {
+                    r#type: "button",
+                    class: "bg-white bg-opacity-20 px-4 py-2 rounded-lg hover:bg-opacity-30",
+                    onclick: move |_| props.on_close.call(()),
+                    "Listo"
+                }
+            }
+            video {
+                id: ID_VISOR_CAMARA,
+                class: "flex-1 min-h-0 w-full object-contain",
+                autoplay: true,
+                playsinline: true,
+                muted: true,
+                onmounted: {
+                    let activo = activo.clone();
+                    move |_| {
+                        let activo = activo.clone();
+                        spawn(async move {
+                            match camara::abrir(ID_VISOR_CAMARA).await {
+                                Ok(stream) => {
+                                    con_linterna.set(camara::tiene_linterna(&stream));
+                                    *activo.borrow_mut() = Some(stream);
+                                }
+                                Err(e) => error.set(Some(e)),
+                            }
+                        });
+                    }
+                },
+            }
+            if let Some(error) = error() {
+                p { class: "text-sm text-red-300 text-center px-3", "{error}" }
+            }
+            div {
+                class: "flex items-center justify-around p-4",
+                button {
+                    r#type: "button",
+                    disabled: !con_linterna(),
+                    class: if linterna() { "w-24 bg-yellow-400 text-black text-sm py-2 rounded-lg disabled:opacity-30" } else { "w-24 bg-white bg-opacity-20 text-white text-sm py-2 rounded-lg disabled:opacity-30" },
+                    onclick: {
+                        let activo = activo.clone();
+                        move |_| {
+                            let Some(stream) = activo.borrow().clone() else { return };
+                            spawn(async move {
+                                match camara::linterna(&stream, !linterna()).await {
+                                    Ok(()) => linterna.toggle(),
+                                    Err(e) => error.set(Some(e)),
+                                }
+                            });
+                        }
+                    },
+                    "Linterna"
+                }
+                button {
+                    r#type: "button",
+                    disabled: capturando(),
+                    class: "w-20 h-20 rounded-full bg-white border-4 border-gray-400 disabled:opacity-50",
+                    title: "Tomar foto",
+                    onclick: move |_| {
+                        capturando.set(true);
+                        spawn(async move {
+                            let ajustes = config::leer_ajustes().imagenes;
+                            let cantidad = if rafaga() { camara::FOTOS_POR_RAFAGA } else { 1 };
+                            for n in 0..cantidad {
+                                if n > 0 {
+                                    TimeoutFuture::new(camara::INTERVALO_RAFAGA_MS).await;
+                                }
+                                match camara::capturar(ID_VISOR_CAMARA, &ajustes).await {
+                                    Ok(imagen) => {
+                                        tomadas += 1;
+                                        on_capture.call(imagen);
+                                    }
+                                    Err(e) => {
+                                        error.set(Some(e));
+                                        break;
+                                    }
+                                }
+                            }
+                            capturando.set(false);
+                        });
+                    },
+                }
+                button {
+                    r#type: "button",
+                    class: if rafaga() { "w-24 bg-blue-500 text-white text-sm py-2 rounded-lg" } else { "w-24 bg-white bg-opacity-20 text-white text-sm py-2 rounded-lg" },
+                    onclick: move |_| rafaga.toggle(),
+                    if rafaga() { "Ráfaga x{camara::FOTOS_POR_RAFAGA}" } else { "Ráfaga" }
+                }
+            }
         }
     }
 }
 
+fn formatear_tamano(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{} KB", bytes.div_ceil(1024))
+    }
+}
+
+// --- Ubicación del pozo ---
+
+const ZOOM_MAPA: u32 = 18;
+const ZOOM_MINIMO: u32 = 3;
+const ZOOM_MAXIMO: u32 = 19;
+// Tamaño en pantalla de cada tesela (las de OpenStreetMap miden 256 px).
+const TESELA_EN_PANTALLA: f64 = 112.0;
+// Centro del mapa cuando el dispositivo nunca registró una ubicación (Bogotá).
+const CENTRO_POR_DEFECTO: (f64, f64) = (4.6097, -74.0817);
+
+#[derive(Props, Clone, PartialEq)]
+struct UbicacionPozoProps {
+    ubicacion: Option<Ubicacion>,
+    on_change: EventHandler<Option<Ubicacion>>,
+}
+
 #[allow(non_snake_case)]
-fn LoginPage() -> Element {
-    let login_url = format!("{}/login", BACKEND_URL);
+fn UbicacionPozo(props: UbicacionPozoProps) -> Element {
+    let mut capturando = use_signal(|| false);
+    let mut error_gps = use_signal(|| None::<String>);
+    let mut mostrar_mapa = use_signal(|| false);
+    let on_change = move |ubicacion: Option<Ubicacion>| {
+        if let Some(ubicacion) = &ubicacion {
+            geo::recordar(ubicacion);
+        }
+        props.on_change.call(ubicacion);
+    };
+
+    let (resumen, precision) = match &props.ubicacion {
+        Some(u) => {
+            let resumen = format!("Lat {:.6}, Lon {:.6} · {} ({})", u.latitud, u.longitud, u.origen, u.capturada_en);
+            let precision = match u.precision_m {
+                Some(m) if m <= geo::PRECISION_BUENA_M => Some(("text-green-700", format!("Precisión buena: ± {:.0} m", m))),
+                Some(m) if m <= geo::PRECISION_ACEPTABLE_M => Some(("text-yellow-700", format!("Precisión aceptable: ± {:.0} m", m))),
+                Some(m) => Some(("text-red-700", format!("Precisión baja: ± {:.0} m. Espere unos segundos y capture de nuevo.", m))),
+                None => None,
+            };
+            (resumen, precision)
+        }
+        None => ("Sin ubicación registrada".to_string(), None),
+    };
+    // Sin ubicación del pozo se centra en la última registrada en el dispositivo.
+    let centro = props
+        .ubicacion
+        .clone()
+        .or_else(geo::ultima_conocida)
+        .map(|u| (u.latitud, u.longitud))
+        .unwrap_or(CENTRO_POR_DEFECTO);
+    let marcador = props.ubicacion.as_ref().map(|u| (u.latitud, u.longitud));
+
     rsx! {
         div {
-            class: "container mx-auto p-4 sm:p-8",
+            class: "flex flex-col gap-3",
             div {
-                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-md mx-auto border border-gray-200 text-center",
-                header {
-                    class: "mb-8",
-                    h1 {
-                        class: "text-4xl font-extrabold text-gray-800 mb-2",
-                        "Acceso Requerido"
-                    }
-                    p {
-                        class: "text-md text-gray-600",
-                        "Se necesita autorización para subir archivos a Google Drive."
-                    }
+                class: "flex flex-wrap gap-3",
+                button {
+                    r#type: "button",
+                    disabled: capturando(),
+                    class: "bg-blue-500 text-white font-semibold py-2 px-4 rounded-lg hover:bg-blue-600 transition disabled:opacity-50",
+                    onclick: move |_| {
+                        capturando.set(true);
+                        error_gps.set(None);
+                        spawn(async move {
+                            match geo::capturar().await {
+                                Ok(ubicacion) => on_change(Some(ubicacion)),
+                                Err(e) => error_gps.set(Some(e)),
+                            }
+                            capturando.set(false);
+                        });
+                    },
+                    if capturando() { "Obteniendo ubicación..." } else { "Capturar ubicación" }
                 }
-                div {
-                    a {
-                        href: "{login_url}",
-                        class: "w-full inline-block bg-blue-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-blue-500 transition-transform transform hover:scale-105",
-                        "Iniciar Sesión con Google"
+                button {
+                    r#type: "button",
+                    class: "bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition",
+                    onclick: move |_| mostrar_mapa.toggle(),
+                    if mostrar_mapa() { "Ocultar mapa" } else { "Marcar en el mapa" }
+                }
+                if props.ubicacion.is_some() {
+                    button {
+                        r#type: "button",
+                        class: "bg-white text-red-600 border border-red-300 font-semibold py-2 px-4 rounded-lg hover:bg-red-50 transition",
+                        onclick: move |_| on_change(None),
+                        "Quitar"
                     }
                 }
-                 p {
-                    class: "text-xs text-gray-500 mt-6",
-                    "Serás redirigido a la página de inicio de sesión de Google para autorizar la aplicación."
+            }
+            p { class: "text-sm text-gray-700", "{resumen}" }
+            if let Some((color, texto)) = precision {
+                p { class: "text-xs {color}", "{texto}" }
+            }
+            if let Some(error) = error_gps() {
+                p { class: "text-xs text-red-600", "{error}" }
+            }
+            if mostrar_mapa() {
+                MapaSelector {
+                    centro: centro,
+                    marcador: marcador,
+                    on_select: move |(latitud, longitud)| on_change(Some(geo::manual(latitud, longitud))),
                 }
             }
         }
@@ -177,191 +3400,155 @@ fn LoginPage() -> Element {
 }
 
 #[derive(Props, Clone, PartialEq)]
-struct SurveyFormProps {
-    encuesta: Signal<Encuesta>,
-    uploaded_files_content: Signal<Vec<(String, Vec<u8>)>>,
-    on_submit: EventHandler<()>,
+struct MapaSelectorProps {
+    centro: (f64, f64),
+    marcador: Option<(f64, f64)>,
+    on_select: EventHandler<(f64, f64)>,
 }
 
+// Mapa mínimo de 3x3 teselas de OpenStreetMap con botones para moverse y
+// acercarse; tocar una tesela marca ese punto. Las coordenadas también se
+// pueden escribir, que es lo único disponible sin conexión.
 #[allow(non_snake_case)]
-fn SurveyForm(props: SurveyFormProps) -> Element {
-    let mut encuesta = props.encuesta;
-    let uploaded_files_content = props.uploaded_files_content;
+fn MapaSelector(props: MapaSelectorProps) -> Element {
+    let mut zoom = use_signal(|| ZOOM_MAPA);
+    // Centro elegido con los botones; `None` sigue al centro recibido.
+    let mut vista = use_signal(|| None::<(f64, f64)>);
+    let mut sin_teselas = use_signal(|| !outbox::navegador_en_linea());
+    let mut coordenadas = use_signal(String::new);
+    let mut error_coordenadas = use_signal(|| None::<String>);
+
+    let z = zoom();
+    let centro = vista().unwrap_or(props.centro);
+    let (centro_x, centro_y) = geo::a_pixeles(centro.0, centro.1, z);
+    let tesela_x = (centro_x / geo::TAMANO_TESELA).floor() as i64;
+    let tesela_y = (centro_y / geo::TAMANO_TESELA).floor() as i64;
+    let teselas_por_lado = 1i64 << z;
+    let escala = geo::TAMANO_TESELA / TESELA_EN_PANTALLA;
+    let lado = TESELA_EN_PANTALLA * 3.0;
+    let teselas: Vec<(i64, i64)> = (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (tesela_x + dx, tesela_y + dy)))
+        .collect();
+    let marcador = props.marcador.map(|(latitud, longitud)| {
+        let (x, y) = geo::a_pixeles(latitud, longitud, z);
+        let origen_x = (tesela_x - 1) as f64 * geo::TAMANO_TESELA;
+        let origen_y = (tesela_y - 1) as f64 * geo::TAMANO_TESELA;
+        ((x - origen_x) / escala, (y - origen_y) / escala)
+    });
+
+    // Mueve la vista una tesela en la dirección indicada.
+    let mut mover = move |dx: f64, dy: f64| {
+        let x = centro_x + dx * geo::TAMANO_TESELA;
+        let y = (centro_y + dy * geo::TAMANO_TESELA).clamp(0.0, teselas_por_lado as f64 * geo::TAMANO_TESELA);
+        vista.set(Some(geo::desde_pixeles(x, y, z)));
+    };
+    let mut acercar = move |paso: i32| {
+        vista.set(Some(centro));
+        zoom.set(z.saturating_add_signed(paso).clamp(ZOOM_MINIMO, ZOOM_MAXIMO));
+    };
+    let mut seleccionar = move |punto: (f64, f64)| {
+        // El mapa vuelve a seguir al marcador, que ahora está en el punto elegido.
+        vista.set(None);
+        props.on_select.call(punto);
+    };
 
     rsx! {
         div {
-            class: "container mx-auto p-4 sm:p-8",
-            div {
-                class: "bg-white p-8 rounded-xl shadow-2xl w-full max-w-4xl mx-auto border border-gray-200",
-                header {
-                    class: "mb-8 border-b pb-4 border-blue-200",
-                    h1 {
-                        class: "text-4xl font-extrabold text-gray-800 text-center mb-2",
-                        "Reporte de Inspección de Pozo"
-                    }
-                    p {
-                        class: "text-md text-gray-600 text-center",
-                        "Complete todos los campos para generar el reporte técnico."
-                    }
+            class: "flex flex-col items-center gap-2",
+            if sin_teselas() {
+                div {
+                    class: "flex items-center justify-center text-center text-sm text-gray-600 bg-gray-100 border rounded-lg p-4",
+                    style: "width: {lado}px; height: {lado}px;",
+                    "El mapa necesita conexión. Escriba las coordenadas abajo o capture la ubicación con el GPS."
                 }
-                form {
-                    prevent_default: "onsubmit",
-                    onsubmit: move |_| props.on_submit.call(()),
+            } else {
+                div {
+                    class: "relative overflow-hidden border rounded-lg cursor-crosshair bg-gray-100",
+                    style: "width: {lado}px; height: {lado}px;",
                     div {
-                        class: "space-y-8",
-                        FormFieldSection {
-                            title: "Datos Generales".to_string(),
-                            grid_cols: Some(3),
-                            FormSelect { label: "Tipo de Sistema".to_string(), value: encuesta.read().tipo_sistema.clone(), options: TIPO_SISTEMA.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().tipo_sistema = v },
-                            FormSelect { label: "Tipo de Pozo".to_string(), value: encuesta.read().tipo_pozo.clone(), options: TIPO_POZO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().tipo_pozo = v },
-                            FormInput { label: "Número de Pozo".to_string(), value: encuesta.read().pozo_numero.clone(), on_input: move |v| encuesta.write().pozo_numero = v },
-                        },
-                        FormFieldSection {
-                            title: "Tapa".to_string(),
-                            FormSelect { label: "Existe".to_string(), value: encuesta.read().tapa_existe.clone(), options: SI_NO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().tapa_existe = v },
-                            FormSelect { label: "Tipo".to_string(), value: encuesta.read().tapa_tipo.clone(), options: TAPA_TIPO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().tapa_tipo = v },
-                            FormSelect { label: "Estado".to_string(), value: encuesta.read().tapa_estado.clone(), options: ESTADO_BUENO_REGULAR_MALO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().tapa_estado = v },
-                            FormSelect { label: "Diagnóstico".to_string(), value: encuesta.read().tapa_diagnostico.clone(), options: DIAGNOSTICO_CAMBIAR_REPARAR.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().tapa_diagnostico = v },
-                        },
-                        FormFieldSection {
-                            title: "Cargue".to_string(),
-                            grid_cols: Some(3),
-                            FormSelect { label: "Existe".to_string(), value: encuesta.read().cargue_existe.clone(), options: SI_NO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cargue_existe = v },
-                            FormSelect { label: "Estado".to_string(), value: encuesta.read().cargue_estado.clone(), options: CARGUE_ESTADO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cargue_estado = v },
-                            FormSelect { label: "Diagnóstico".to_string(), value: encuesta.read().cargue_diagnostico.clone(), options: DIAGNOSTICO_CAMBIAR_REPARAR.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cargue_diagnostico = v },
-                        },
-                        FormFieldSection {
-                            title: "Cono".to_string(),
-                            grid_cols: Some(3),
-                            FormSelect { label: "Existe".to_string(), value: encuesta.read().cono_existe.clone(), options: SI_NO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cono_existe = v },
-                            FormSelect { label: "Estado".to_string(), value: encuesta.read().cono_estado.clone(), options: CARGUE_ESTADO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cono_estado = v },
-                            FormSelect { label: "Diagnóstico".to_string(), value: encuesta.read().cono_diagnostico.clone(), options: DIAGNOSTICO_CAMBIAR_REPARAR.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cono_diagnostico = v },
-                        },
-                        FormFieldSection {
-                            title: "Cilindro".to_string(),
-                            grid_cols: Some(3),
-                            FormSelect { label: "Material".to_string(), value: encuesta.read().cilindro_material.clone(), options: CILINDRO_MATERIAL.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cilindro_material = v },
-                            FormSelect { label: "Estado".to_string(), value: encuesta.read().cilindro_estado.clone(), options: CILINDRO_ESTADO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cilindro_estado = v },
-                            FormSelect { label: "Diagnóstico".to_string(), value: encuesta.read().cilindro_diagnostico.clone(), options: DIAGNOSTICO_CAMBIAR_REPARAR.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().cilindro_diagnostico = v },
-                        },
-                        FormFieldSection {
-                            title: "Cañuela".to_string(),
-                            grid_cols: Some(2),
-                            FormSelect { label: "Estado".to_string(), value: encuesta.read().canuela_estado.clone(), options: CANUELA_ESTADO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().canuela_estado = v },
-                            FormSelect { label: "Diagnostico".to_string(), value: encuesta.read().canuela_diagnostico.clone(), options: DIAGNOSTICO_CAMBIAR_REPARAR.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().canuela_diagnostico = v },
-                        },
-                        FormFieldSection {
-                            title: "Escalones".to_string(),
-                            FormSelect { label: "Existen".to_string(), value: encuesta.read().escalones_existe.clone(), options: SI_NO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().escalones_existe = v },
-                            FormSelect { label: "Tipo".to_string(), value: encuesta.read().escalones_tipo.clone(), options: ESCALONES_TIPO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().escalones_tipo = v },
-                            FormSelect { label: "Estado".to_string(), value: encuesta.read().escalones_estado.clone(), options: ESCALONES_ESTADO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().escalones_estado = v },
-                            FormSelect { label: "Diagnóstico".to_string(), value: encuesta.read().escalones_diagnostico.clone(), options: DIAGNOSTICO_CAMBIAR_REPARAR.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().escalones_diagnostico = v },
-                        },
-                        FormFieldSection {
-                            title: "Evaluación Final".to_string(),
-                            grid_cols: Some(1),
-                            FormSelect { label: "Estado General del Pozo".to_string(), value: encuesta.read().estado_general_pozo.clone(), options: ESTADO_GENERAL_POZO.iter().map(|s| s.to_string()).collect(), on_change: move |v| encuesta.write().estado_general_pozo = v },
-                            FormTextArea { label: "Observaciones".to_string(), value: encuesta.read().observaciones.clone(), on_input: move |v| encuesta.write().observaciones = v },
-                        },
-                        div {
-                            class: "p-4 border rounded-lg",
-                            h3 { class: "text-lg font-semibold text-gray-700 mb-4", "Conexiones" },
-                            div { class: "space-y-4",
-                                for (i, _) in encuesta.read().lista_conexiones.iter().enumerate() {
-                                    div { 
-                                        key: "conexion-{i}",
-                                        class: "grid grid-cols-1 md:grid-cols-6 gap-4 p-4 border rounded bg-gray-50",
-                                        div { class: "md:col-span-5 grid grid-cols-1 md:grid-cols-5 gap-4",
-                                            FormInput { label: "Cota Razante".to_string(), value: encuesta.read().lista_conexiones[i].cota_razante.clone(), on_input: move |v| encuesta.write().lista_conexiones[i].cota_razante = v },
-                                            FormInput { label: "Cota Clave".to_string(), value: encuesta.read().lista_conexiones[i].cota_clave.clone(), on_input: move |v| encuesta.write().lista_conexiones[i].cota_clave = v },
-                                            FormInput { label: "Diámetro (pulg)".to_string(), value: encuesta.read().lista_conexiones[i].diametro_pulgadas.clone(), on_input: move |v| encuesta.write().lista_conexiones[i].diametro_pulgadas = v },
-                                            FormInput { label: "Material".to_string(), value: encuesta.read().lista_conexiones[i].material.clone(), on_input: move |v| encuesta.write().lista_conexiones[i].material = v },
-                                            FormInput { label: "Conecta A".to_string(), value: encuesta.read().lista_conexiones[i].conecta_a.clone(), on_input: move |v| encuesta.write().lista_conexiones[i].conecta_a = v },
-                                        },
-                                        div { class: "flex items-end justify-center",
-                                            button {
-                                                r#type: "button",
-                                                class: "bg-red-500 text-white p-2 rounded-full hover:bg-red-600 transition h-10 w-10 flex items-center justify-center",
-                                                onclick: move |_| { encuesta.write().lista_conexiones.remove(i); },
-                                                "X"
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            button {
-                                r#type: "button",
-                                class: "mt-4 bg-blue-500 text-white font-semibold py-2 px-4 rounded-lg hover:bg-blue-600 transition",
-                                onclick: move |_| encuesta.write().lista_conexiones.push(Conexion::default()),
-                                "+ Añadir Conexión"
-                            }
-                        }
-                        FormFieldSection {
-                            title: "Fotografías".to_string(),
-                            grid_cols: Some(1),
-                            div {
-                                class: "flex flex-col items-center",
-                                label {
-                                    class: "w-full max-w-xs text-center bg-gray-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-gray-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-gray-500 transition-transform transform hover:scale-105 cursor-pointer",
-                                    r#for: "file-upload",
-                                    "Seleccionar Imágenes"
-                                }
-                                input {
-                                    id: "file-upload",
-                                    r#type: "file",
-                                    multiple: true,
-                                    accept: "image/*",
-                                    class: "hidden",
-                                    onchange: move |evt| {
-                                        let mut uploaded_files_content = uploaded_files_content.to_owned();
-                                        async move {
-                                            if let Some(file_engine) = evt.files() {
-                                                let files = file_engine.files();
-                                                let mut new_files_content = Vec::new();
-                                                for file_name in &files {
-                                                    if let Some(contents) = file_engine.read_file(file_name).await {
-                                                        new_files_content.push((file_name.clone(), contents));
-                                                    }
-                                                }
-                                                uploaded_files_content.set(new_files_content);
-                                            }
-                                        }
-                                    }
+                        class: "grid grid-cols-3",
+                        for (x, y) in teselas {
+                            if (0..teselas_por_lado).contains(&y) {
+                                img {
+                                    key: "tesela-{z}-{x}-{y}",
+                                    src: "https://tile.openstreetmap.org/{z}/{x.rem_euclid(teselas_por_lado)}/{y}.png",
+                                    draggable: false,
+                                    style: "width: {TESELA_EN_PANTALLA}px; height: {TESELA_EN_PANTALLA}px;",
+                                    onerror: move |_| sin_teselas.set(true),
+                                    onclick: move |evt| {
+                                        let punto = evt.element_coordinates();
+                                        let global_x = x as f64 * geo::TAMANO_TESELA + punto.x * escala;
+                                        let global_y = y as f64 * geo::TAMANO_TESELA + punto.y * escala;
+                                        seleccionar(geo::desde_pixeles(global_x, global_y, z));
+                                    },
                                 }
-                                if !uploaded_files_content.read().is_empty() {
-                                    div {
-                                        class: "mt-4 text-sm text-gray-600",
-                                        p { class: "font-semibold mb-2", "Archivos seleccionados:" }
-                                        ul { class: "list-disc list-inside",
-                                            for (file_name, _) in uploaded_files_content.read().iter() {
-                                                li { key: "{file_name}", "{file_name}" }
-                                            }
-                                        }
-                                    }
+                            } else {
+                                div {
+                                    key: "tesela-{z}-{x}-{y}",
+                                    style: "width: {TESELA_EN_PANTALLA}px; height: {TESELA_EN_PANTALLA}px;",
                                 }
                             }
                         }
+                    }
+                    if let Some((x, y)) = marcador {
                         div {
-                            class: "mt-10 pt-6 border-t",
-                            button {
-                                r#type: "submit",
-                                class: "w-full bg-green-600 text-white font-bold py-3 px-4 rounded-lg hover:bg-green-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-green-500 transition-transform transform hover:scale-105",
-                                "Generar Reporte"
-                            }
+                            class: "absolute w-4 h-4 -ml-2 -mt-2 rounded-full bg-red-600 border-2 border-white shadow pointer-events-none",
+                            style: "left: {x}px; top: {y}px;",
                         }
                     }
                 }
+                div {
+                    class: "flex flex-wrap justify-center gap-1 text-sm",
+                    button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300", title: "Oeste", onclick: move |_| mover(-1.0, 0.0), "←" }
+                    button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300", title: "Norte", onclick: move |_| mover(0.0, -1.0), "↑" }
+                    button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300", title: "Sur", onclick: move |_| mover(0.0, 1.0), "↓" }
+                    button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300", title: "Este", onclick: move |_| mover(1.0, 0.0), "→" }
+                    button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300 disabled:opacity-40", title: "Acercar", disabled: z >= ZOOM_MAXIMO, onclick: move |_| acercar(1), "+" }
+                    button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300 disabled:opacity-40", title: "Alejar", disabled: z <= ZOOM_MINIMO, onclick: move |_| acercar(-1), "−" }
+                    if vista().is_some() {
+                        button { r#type: "button", class: "px-3 py-1 rounded bg-gray-200 hover:bg-gray-300", onclick: move |_| vista.set(None), "Centrar" }
+                    }
+                }
+                p { class: "text-xs text-gray-500", "Toque el mapa para corregir la ubicación · © OpenStreetMap" }
+            }
+            div {
+                class: "flex flex-wrap items-start gap-2 w-full max-w-md",
+                div {
+                    class: "flex flex-col flex-1 min-w-0",
+                    input {
+                        r#type: "text",
+                        placeholder: "Latitud, longitud (p. ej. 4.6097, -74.0817)",
+                        class: "px-3 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm",
+                        value: "{coordenadas}",
+                        oninput: move |evt| coordenadas.set(evt.value()),
+                    }
+                    if let Some(error) = error_coordenadas() {
+                        p { class: "text-xs text-red-600 mt-1", "{error}" }
+                    }
+                }
+                button {
+                    r#type: "button",
+                    class: "bg-gray-200 text-gray-700 font-semibold py-2 px-4 rounded-lg hover:bg-gray-300 transition text-sm",
+                    onclick: move |_| match geo::parsear_coordenadas(&coordenadas.read()) {
+                        Ok(punto) => {
+                            error_coordenadas.set(None);
+                            seleccionar(punto);
+                        }
+                        Err(e) => error_coordenadas.set(Some(e)),
+                    },
+                    "Usar coordenadas"
+                }
             }
         }
     }
 }
 
-
 // --- Componentes de Formulario (sin cambios) ---
 
 #[derive(Props, Clone, PartialEq)]
 struct FormFieldSectionProps {
     title: String,
+    id: Option<String>,
     children: Element,
     grid_cols: Option<u32>,
 }
@@ -371,6 +3558,7 @@ fn FormFieldSection(props: FormFieldSectionProps) -> Element {
     let grid_class = format!("grid grid-cols-1 md:grid-cols-{}", props.grid_cols.unwrap_or(4));
     rsx! {
         div {
+            id: props.id,
             class: "p-4 border rounded-lg",
             h3 { class: "text-lg font-semibold text-gray-700 mb-4 border-b pb-2", "{props.title}" },
             div { class: "{grid_class} gap-x-6 gap-y-4", {props.children} }
@@ -383,20 +3571,87 @@ struct FormInputProps {
     label: String,
     value: String,
     on_input: EventHandler<String>,
+    id: Option<String>,
+    error: Option<String>,
 }
 
 #[allow(non_snake_case)]
 fn FormInput(props: FormInputProps) -> Element {
+    let borde = if props.error.is_some() { "border-red-500" } else { "border-gray-300" };
     rsx! {
         div {
             class: "flex flex-col",
-            label { class: "text-sm font-medium text-gray-600 mb-1", "{props.label}" },
+            label { r#for: props.id.clone(), class: "text-sm font-medium text-gray-600 mb-1", "{props.label}" },
             input {
+                id: props.id.clone(),
                 r#type: "text",
-                class: "px-3 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition text-sm",
+                class: "px-3 py-2 border {borde} rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition text-sm",
                 oninput: move |evt| props.on_input.call(evt.value()),
                 value: "{props.value}"
             }
+            if let Some(error) = &props.error {
+                p { class: "text-xs text-red-600 mt-1", "{error}" }
+            }
+        }
+    }
+}
+
+// Campo numérico con unidad. Conserva lo que se escribe mientras no sea un
+// número válido y solo actualiza el modelo cuando el texto se puede interpretar.
+#[derive(Props, Clone, PartialEq)]
+struct FormMedidaProps {
+    label: String,
+    id: String,
+    error: Option<String>,
+    unidad: Unidad,
+    valor: Option<f64>,
+    on_change: EventHandler<Option<f64>>,
+}
+
+#[allow(non_snake_case)]
+fn FormMedida(props: FormMedidaProps) -> Element {
+    let mut texto = use_signal(|| medidas::formatear(props.valor));
+    let interpretado = medidas::parsear(&texto(), props.unidad);
+    // Si el valor cambió desde fuera (p. ej. al abrir un borrador) se muestra el nuevo.
+    let mostrado = match &interpretado {
+        Ok(valor) if *valor != props.valor => medidas::formatear(props.valor),
+        _ => texto(),
+    };
+    let error = interpretado.err().or(props.error.clone());
+    let borde = if error.is_some() { "border-red-500" } else { "border-gray-300" };
+    let equivalencia = match (props.unidad, props.valor) {
+        (Unidad::Pulgadas, Some(pulgadas)) => Some(format!("≈ {:.0} mm", medidas::pulgadas_a_mm(pulgadas))),
+        _ => None,
+    };
+    rsx! {
+        div {
+            class: "flex flex-col",
+            label { r#for: "{props.id}", class: "text-sm font-medium text-gray-600 mb-1", "{props.label}" },
+            input {
+                id: "{props.id}",
+                r#type: "text",
+                inputmode: "decimal",
+                placeholder: "{props.unidad.simbolo()}",
+                class: "px-3 py-2 border {borde} rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition text-sm",
+                oninput: move |evt| {
+                    let valor = evt.value();
+                    props.on_change.call(medidas::parsear(&valor, props.unidad).unwrap_or(None));
+                    texto.set(valor);
+                },
+                onblur: move |_| {
+                    // Al salir del campo se normaliza el texto ("1.5m" → "1,5").
+                    if let Ok(valor) = medidas::parsear(&texto(), props.unidad) {
+                        texto.set(medidas::formatear(valor));
+                    }
+                },
+                value: "{mostrado}"
+            }
+            if let Some(equivalencia) = equivalencia {
+                p { class: "text-xs text-gray-500 mt-1", "{equivalencia}" }
+            }
+            if let Some(error) = error {
+                p { class: "text-xs text-red-600 mt-1", "{error}" }
+            }
         }
     }
 }
@@ -424,27 +3679,90 @@ fn FormTextArea(props: FormTextAreaProps) -> Element {
 }
 
 #[derive(Props, Clone, PartialEq)]
-struct FormSelectProps {
+struct FormSelectProps<T: Opcion> {
     label: String,
-    value: String,
-    options: Vec<String>,
-    on_change: EventHandler<String>,
+    value: Option<T>,
+    on_change: EventHandler<Option<T>>,
+    id: Option<String>,
+    error: Option<String>,
+    warning: Option<String>,
+    #[props(default)]
+    disabled: bool,
+    // Texto de una opción vacía que sí se puede elegir (p. ej. "Todos" en un filtro).
+    vacio: Option<String>,
 }
 
+// Las opciones se generan a partir de `T::TODAS`, así que no es posible
+// enviar al backend un valor que no pertenezca al enum.
 #[allow(non_snake_case)]
-fn FormSelect(props: FormSelectProps) -> Element {
+fn FormSelect<T: Opcion>(props: FormSelectProps<T>) -> Element {
+    let borde = if props.error.is_some() { "border-red-500" } else { "border-gray-300" };
     rsx! {
         div {
             class: "flex flex-col",
-            label { class: "text-sm font-medium text-gray-600 mb-1", "{props.label}" },
+            label { r#for: props.id.clone(), class: "text-sm font-medium text-gray-600 mb-1", "{props.label}" },
             select {
-                class: "px-3 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition bg-white text-sm",
-                onchange: move |evt| props.on_change.call(evt.value()),
-                option { selected: props.value.is_empty(), disabled: true, value: "", "Seleccione una opción" },
-                for option_str in props.options.iter() {
-                    option { selected: props.value == *option_str, "{option_str}" }
+                id: props.id.clone(),
+                disabled: props.disabled,
+                class: "px-3 py-2 border {borde} rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 transition bg-white text-sm disabled:bg-gray-100 disabled:text-gray-400",
+                onchange: move |evt| props.on_change.call(T::desde_etiqueta(&evt.value())),
+                option {
+                    selected: props.value.is_none(),
+                    disabled: props.vacio.is_none(),
+                    value: "",
+                    match &props.vacio {
+                        _ if props.disabled => "No aplica",
+                        Some(vacio) => vacio.as_str(),
+                        None => "Seleccione una opción",
+                    }
+                },
+                for opcion in T::TODAS.iter() {
+                    option { selected: props.value == Some(*opcion), value: opcion.etiqueta(), "{opcion.etiqueta()}" }
                 }
             }
+            if let Some(error) = &props.error {
+                p { class: "text-xs text-red-600 mt-1", "{error}" }
+            }
+            if let Some(warning) = &props.warning {
+                p { class: "text-xs text-yellow-700 mt-1", "{warning}" }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dias_desde_epoca, espera_retry_after, fecha_http_ms};
+
+    #[test]
+    fn retry_after_en_segundos() {
+        assert_eq!(espera_retry_after("120", 0.0), Some(120_000.0));
+        assert_eq!(espera_retry_after(" 0 ", 5.0), Some(0.0));
+    }
+
+    #[test]
+    fn retry_after_invalido_se_ignora() {
+        for valor in ["", "-5", "1.5", "1e3", "NaN", "inf", "mañana"] {
+            assert_eq!(espera_retry_after(valor, 0.0), None, "{:?}", valor);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn retry_after_como_fecha_http() {
+        let fecha = fecha_http_ms("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(fecha, 784_111_777_000.0);
+        assert_eq!(espera_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", fecha - 30_000.0), Some(30_000.0));
+        // Una fecha ya pasada no genera espera negativa.
+        assert_eq!(espera_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", fecha + 1.0), Some(0.0));
+        assert_eq!(fecha_http_ms("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(fecha_http_ms("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(fecha_http_ms("Sun, 06 Nov 1994 25:00:00 GMT"), None);
+    }
+
+    #[test]
+    fn dias_del_calendario() {
+        assert_eq!(dias_desde_epoca(1970, 1, 1), 0);
+        assert_eq!(dias_desde_epoca(2000, 3, 1), 11_017);
+        assert_eq!(dias_desde_epoca(2024, 2, 29), 19_782);
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::validation::interpretar_numero;
+
+// --- Medidas de las conexiones ---
+// Las cotas se guardan en metros y los diámetros en pulgadas, siempre como
+// números. En campo se escriben de muchas formas ("1,5", "1.5m", "6 in",
+// "150 mm"); aquí se interpretan y se convierten a la unidad del modelo.
+
+pub const MM_POR_PULGADA: f64 = 25.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unidad {
+    Metros,
+    Pulgadas,
+}
+
+impl Unidad {
+    pub fn simbolo(&self) -> &'static str {
+        match self {
+            Unidad::Metros => "m",
+            Unidad::Pulgadas => "pulg",
+        }
+    }
+
+    /// Factor para pasar de la unidad escrita a la del modelo; `None` si no se reconoce.
+    fn factor(&self, sufijo: &str) -> Option<f64> {
+        match (self, sufijo) {
+            (_, "") => Some(1.0),
+            (Unidad::Metros, "m" | "mt" | "mts" | "metros") => Some(1.0),
+            (Unidad::Metros, "cm") => Some(0.01),
+            (Unidad::Metros, "mm") => Some(0.001),
+            (Unidad::Pulgadas, "in" | "\"" | "pulg" | "pulgadas" | "plg") => Some(1.0),
+            (Unidad::Pulgadas, "mm") => Some(1.0 / MM_POR_PULGADA),
+            (Unidad::Pulgadas, "cm") => Some(10.0 / MM_POR_PULGADA),
+            _ => None,
+        }
+    }
+}
+
+/// Interpreta una medida escrita a mano. Un texto vacío es `Ok(None)`.
+pub fn parsear(texto: &str, unidad: Unidad) -> Result<Option<f64>, String> {
+    parsear_con(texto, unidad, separador_decimal())
+}
+
+fn parsear_con(texto: &str, unidad: Unidad, decimal: char) -> Result<Option<f64>, String> {
+    let texto = texto.trim().to_lowercase();
+    if texto.is_empty() {
+        return Ok(None);
+    }
+    let fin_numero = texto
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, ',' | '.' | '-' | '+')))
+        .unwrap_or(texto.len());
+    let (numero, sufijo) = texto.split_at(fin_numero);
+    let numero = interpretar_numero(numero, decimal)?;
+    let factor = unidad
+        .factor(sufijo.trim())
+        .ok_or_else(|| format!("Unidad no reconocida; use {}", unidad.simbolo()))?;
+    Ok(Some(numero * factor))
+}
+
+/// Separador decimal según el idioma del navegador (coma salvo en inglés).
+pub fn separador_decimal() -> char {
+    let idioma = web_sys::window().and_then(|w| w.navigator().language()).unwrap_or_default();
+    if idioma.starts_with("en") { '.' } else { ',' }
+}
+
+/// Texto para mostrar un valor en un campo, sin ceros sobrantes.
+pub fn formatear(valor: Option<f64>) -> String {
+    let Some(valor) = valor else { return String::new() };
+    let texto = format!("{:.3}", valor);
+    let texto = texto.trim_end_matches('0').trim_end_matches('.');
+    texto.replace('.', &separador_decimal().to_string())
+}
+
+pub fn pulgadas_a_mm(pulgadas: f64) -> f64 {
+    pulgadas * MM_POR_PULGADA
+}
+
+// Los borradores y la bandeja de salida anteriores guardaban estas medidas como
+// texto; se aceptan ambos formatos al leerlos.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumeroOTexto {
+    Numero(f64),
+    Texto(String),
+}
+
+fn deserializar<'de, D: Deserializer<'de>>(deserializer: D, unidad: Unidad) -> Result<Option<f64>, D::Error> {
+    Ok(match Option::<NumeroOTexto>::deserialize(deserializer)? {
+        Some(NumeroOTexto::Numero(n)) => Some(n),
+        Some(NumeroOTexto::Texto(texto)) => parsear(&texto, unidad).ok().flatten(),
+        None => None,
+    })
+}
+
+pub fn metros<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    deserializar(deserializer, Unidad::Metros)
+}
+
+pub fn pulgadas<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    deserializar(deserializer, Unidad::Pulgadas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parsear_con, Unidad};
+
+    #[test]
+    fn interpreta_cotas_con_coma_decimal() {
+        assert_eq!(parsear_con("2612,45", Unidad::Metros, ','), Ok(Some(2612.45)));
+        assert_eq!(parsear_con("1,5m", Unidad::Metros, ','), Ok(Some(1.5)));
+        assert_eq!(parsear_con("150 cm", Unidad::Metros, ','), Ok(Some(1.5)));
+        assert_eq!(parsear_con("  ", Unidad::Metros, ','), Ok(None));
+    }
+
+    #[test]
+    fn no_confunde_miles_con_decimales() {
+        // En es-CO "2.600" es una cota de Bogotá, no 2,6 m.
+        assert!(parsear_con("2.600", Unidad::Metros, ',').is_err());
+        assert!(parsear_con("2.600 m", Unidad::Metros, ',').is_err());
+        assert!(parsear_con("2,600", Unidad::Metros, '.').is_err());
+        assert_eq!(parsear_con("2600", Unidad::Metros, ','), Ok(Some(2600.0)));
+    }
+
+    #[test]
+    fn convierte_diametros_a_pulgadas() {
+        assert_eq!(parsear_con("6 in", Unidad::Pulgadas, ','), Ok(Some(6.0)));
+        assert_eq!(parsear_con("254 mm", Unidad::Pulgadas, ','), Ok(Some(10.0)));
+        assert!(parsear_con("6 m", Unidad::Pulgadas, ',').is_err());
+    }
+}
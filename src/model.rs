@@ -1,59 +1,483 @@
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
+
+// Trait común a todas las listas de opciones del formulario.
+// Permite que `FormSelect` genere sus opciones a partir del enum
+// y convierta de vuelta el texto seleccionado en el valor tipado.
+pub trait Opcion: Copy + PartialEq + 'static {
+    const TODAS: &'static [Self];
+
+    fn etiqueta(&self) -> &'static str;
+
+    fn desde_etiqueta(texto: &str) -> Option<Self> {
+        Self::TODAS.iter().copied().find(|o| o.etiqueta() == texto)
+    }
+}
+
+// Declara un enum de opciones cuyo nombre en JSON coincide con la etiqueta
+// que se muestra en pantalla (y que el backend ya espera).
+macro_rules! opciones {
+    ($(#[$meta:meta])* $nombre:ident { $($variante:ident => $etiqueta:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum $nombre {
+            $(#[serde(rename = $etiqueta)] $variante),+
+        }
+
+        impl Opcion for $nombre {
+            const TODAS: &'static [Self] = &[$(Self::$variante),+];
+
+            fn etiqueta(&self) -> &'static str {
+                match self {
+                    $(Self::$variante => $etiqueta),+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $nombre {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.etiqueta())
+            }
+        }
+    };
+}
+pub(crate) use opciones;
+
+opciones!(TipoSistema {
+    AguasLluvia => "Aguas Lluvia",
+    AguasResiduales => "Aguas Residuales",
+    Combinado => "Combinado",
+});
+
+opciones!(TipoPozo {
+    Pozo => "Pozo",
+    Camara => "Camara",
+    Alivio => "Alivio",
+});
+
+opciones!(SiNo {
+    Si => "Si",
+    No => "No",
+});
+
+opciones!(TapaTipo {
+    Ferroconcreto => "Ferroconcreto",
+    Concreto => "Concreto",
+    HierroSinBisagra => "Hierro sin Bisagra",
+    HierroConBisagra => "Hierro con bisagra",
+    TapaSeguridad => "Tapa Seguridad",
+    TapaEnFibra => "Tapa en fibra",
+});
+
+opciones!(
+    /// Estado simple usado por la tapa.
+    EstadoBasico {
+        Bueno => "Bueno",
+        Regular => "Regular",
+        Malo => "Malo",
+    }
+);
+
+opciones!(Diagnostico {
+    Cambiar => "Cambiar",
+    Reparar => "Reparar",
+    NoRequiere => "No Requiere",
+});
+
+opciones!(
+    /// Estado usado por el cargue y el cono.
+    EstadoCargue {
+        Bueno => "Bueno",
+        Regular => "Regular",
+        Malo => "Malo",
+        Grietas => "Grietas",
+        Partido => "Partido",
+        Hundido => "Hundido",
+    }
+);
+
+opciones!(MaterialCilindro {
+    Mamposteria => "Mamposteria",
+    Concreto => "Concreto",
+    Grp => "GRP",
+});
+
+opciones!(EstadoCilindro {
+    Bueno => "Bueno",
+    Regular => "Regular",
+    Malo => "Malo",
+    Grietas => "Grietas",
+    Partido => "Partido",
+    Huecos => "Huecos",
+    SinPanete => "Sin Pañete",
+    Otro => "Otro",
+});
+
+opciones!(EstadoCanuela {
+    Bueno => "Bueno",
+    Regular => "Regular",
+    Malo => "Malo",
+    Sedimentada => "Sedimentada",
+    Desgastada => "Desgastada",
+    Socavacion => "Socavacion",
+});
+
+opciones!(TipoEscalones {
+    Escalones => "Escalones",
+    Ladrillos => "Ladrillos",
+});
+
+opciones!(EstadoEscalones {
+    Bueno => "Bueno",
+    Regular => "Regular",
+    Malo => "Malo",
+    Doblados => "Doblados",
+    Faltan => "Faltan",
+    Corroidos => "Corroidos",
+});
+
+opciones!(EstadoGeneralPozo {
+    Infiltracion => "Infiltracion",
+    Represado => "Represado",
+    ConBasura => "Con basura",
+    Raices => "Raices",
+    FueraDeServicio => "Fuera de Servicio",
+    LlenoDeTierra => "Lleno de tierra",
+});
+
+opciones!(OrigenUbicacion {
+    Gps => "gps",
+    Manual => "manual",
+});
+
+// Coordenadas del pozo (WGS84). `precision_m` es el radio de incertidumbre
+// reportado por el GPS; no existe cuando la ubicación se marcó a mano.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ubicacion {
+    pub latitud: f64,
+    pub longitud: f64,
+    pub precision_m: Option<f64>,
+    pub capturada_en: String, // Fecha ISO 8601
+    pub origen: OrigenUbicacion,
+}
+
+opciones!(
+    /// Parte del pozo que documenta una fotografía.
+    ComponenteFoto {
+        General => "General",
+        Tapa => "Tapa",
+        Cargue => "Cargue",
+        Cono => "Cono",
+        Cilindro => "Cilindro",
+        Canuela => "Cañuela",
+        Escalones => "Escalones",
+    }
+);
+
+fn componente_por_defecto() -> ComponenteFoto {
+    ComponenteFoto::General
+}
+
+// Fotografía de la inspección. Los campos con `default` permiten leer las
+// fotos guardadas antes de que se asociaran a un componente.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Foto {
+    pub nombre: String,
+    #[serde(default = "componente_por_defecto")]
+    pub componente: ComponenteFoto,
+    #[serde(default)]
+    pub descripcion: String,
+    #[serde(default)]
+    pub capturada_en: String, // Fecha ISO 8601
+    #[serde(default)]
+    pub ubicacion: Option<Ubicacion>,
+    // Tamaño en bytes antes de comprimir; `None` si se guardó sin cambios.
+    #[serde(default)]
+    pub tamano_original: Option<usize>,
+    // Nombre de la foto original cuando esta es su versión anotada.
+    #[serde(default)]
+    pub anotacion_de: Option<String>,
+    // La foto ya está en el backend y solo se conserva la referencia, sin la imagen.
+    #[serde(default)]
+    pub en_servidor: bool,
+    // La imagen se guarda en IndexedDB (`fotos_db`), no junto a los metadatos;
+    // solo se lee aquí de datos guardados por versiones anteriores.
+    #[serde(default, skip_serializing, deserialize_with = "crate::storage::base64::deserialize")]
+    pub contenido: Vec<u8>,
+}
+
+impl Foto {
+    /// Compara todo salvo la imagen: una foto nunca cambia de imagen sin
+    /// cambiar de nombre, así que no hace falta recorrer los bytes.
+    pub fn mismos_metadatos(&self, otra: &Foto) -> bool {
+        self.nombre == otra.nombre
+            && self.componente == otra.componente
+            && self.descripcion == otra.descripcion
+            && self.capturada_en == otra.capturada_en
+            && self.ubicacion == otra.ubicacion
+            && self.tamano_original == otra.tamano_original
+            && self.anotacion_de == otra.anotacion_de
+            && self.en_servidor == otra.en_servidor
+            && self.contenido.len() == otra.contenido.len()
+    }
+
+    /// Copia de la foto sin la imagen, para guardar solo los metadatos.
+    pub fn sin_contenido(&self) -> Foto {
+        Foto {
+            nombre: self.nombre.clone(),
+            componente: self.componente,
+            descripcion: self.descripcion.clone(),
+            capturada_en: self.capturada_en.clone(),
+            ubicacion: self.ubicacion.clone(),
+            tamano_original: self.tamano_original,
+            anotacion_de: self.anotacion_de.clone(),
+            en_servidor: self.en_servidor,
+            contenido: Vec::new(),
+        }
+    }
+}
 
 // Usamos Default para poder crear una instancia vacía fácilmente.
 // Usamos Clone para poder duplicar el estado.
-#[derive(Clone, Default, Serialize, Debug)]
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Conexion {
-    pub cota_razante: String,
-    pub cota_clave: String,
-    pub diametro_pulgadas: String,
-    pub material: String,
+    /// Identificador estable de la fila en el formulario.
+    #[serde(default)]
+    pub id: String,
+    // Cotas en metros y diámetro en pulgadas; se envían como números JSON.
+    #[serde(default, deserialize_with = "crate::medidas::metros")]
+    pub cota_razante: Option<f64>,
+    #[serde(default, deserialize_with = "crate::medidas::metros")]
+    pub cota_clave: Option<f64>,
+    #[serde(default, deserialize_with = "crate::medidas::pulgadas")]
+    pub diametro_pulgadas: Option<f64>,
+    #[serde(default)]
+    pub direccion: Option<DireccionConexion>,
+    /// Posición en la cámara como la hora de un reloj (1 a 12) vista desde arriba,
+    /// con las 12 hacia el norte.
+    #[serde(default)]
+    pub posicion_reloj: Option<u8>,
+    #[serde(default, deserialize_with = "material_flexible")]
+    pub material: Option<MaterialTubo>,
+    #[serde(default)]
+    pub estado: Option<EstadoTubo>,
     pub conecta_a: String,
+    // Se recalcula al encolar la encuesta; el formulario lo muestra en vivo.
+    #[serde(default)]
+    pub calculo: CalculoConexion,
+}
+
+opciones!(
+    /// Si el tubo trae agua a la cámara o se la lleva.
+    DireccionConexion {
+        Entrada => "Entrada",
+        Salida => "Salida",
+    }
+);
+
+opciones!(MaterialTubo {
+    Pvc => "PVC",
+    Concreto => "Concreto",
+    Gres => "Gres",
+    Grp => "GRP",
+    Hierro => "Hierro",
+});
+
+opciones!(EstadoTubo {
+    Bueno => "Bueno",
+    Regular => "Regular",
+    Malo => "Malo",
+    Fisurado => "Fisurado",
+    Obstruido => "Obstruido",
+    Colapsado => "Colapsado",
+});
+
+// Antes el material era texto libre; al leer borradores viejos se conserva
+// solo si coincide con una opción del catálogo.
+fn material_flexible<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<MaterialTubo>, D::Error> {
+    let texto = Option::<String>::deserialize(deserializer)?.unwrap_or_default();
+    Ok(MaterialTubo::TODAS.iter().copied().find(|m| m.etiqueta().eq_ignore_ascii_case(texto.trim())))
 }
 
-#[derive(Clone, Default, Serialize, Debug)]
+opciones!(
+    /// Sentido del agua en el tubo visto desde el pozo inspeccionado.
+    SentidoFlujo {
+        Sale => "Sale",
+        Llega => "Llega",
+    }
+);
+
+/// Valores derivados de las cotas; no los escribe el inspector.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalculoConexion {
+    pub profundidad_m: Option<f64>,
+    /// Distancia horizontal entre los dos pozos del tramo.
+    pub longitud_m: Option<f64>,
+    /// Positiva cuando el tubo baja desde este pozo hacia `conecta_a`.
+    pub pendiente_pct: Option<f64>,
+    pub sentido: Option<SentidoFlujo>,
+    /// La pendiente no se calculó porque la ubicación de alguno de los pozos es imprecisa.
+    #[serde(default)]
+    pub ubicacion_imprecisa: bool,
+}
+
+/// Pendiente en porcentaje de un tubo entre dos cotas clave separadas `longitud_m`.
+pub fn pendiente_pct(clave_origen: f64, clave_destino: f64, longitud_m: f64) -> Option<f64> {
+    (longitud_m > 0.0).then(|| (clave_origen - clave_destino) / longitud_m * 100.0)
+}
+
+impl Conexion {
+    pub fn nueva(id: String) -> Conexion {
+        Conexion { id, ..Default::default() }
+    }
+
+    /// Copia de la conexión con otro identificador.
+    pub fn duplicar(&self, id: String) -> Conexion {
+        Conexion { id, ..self.clone() }
+    }
+
+    /// Texto corto para la fila contraída.
+    pub fn resumen(&self) -> String {
+        let mut partes: Vec<String> = Vec::new();
+        partes.extend(self.direccion.map(|d| d.etiqueta().to_string()));
+        partes.extend(self.posicion_reloj.map(|h| format!("{} h", h)));
+        partes.extend(self.diametro_pulgadas.map(|d| format!("{} pulg", d)));
+        partes.extend(self.material.map(|m| m.etiqueta().to_string()));
+        if !self.conecta_a.trim().is_empty() {
+            partes.push(format!("→ {}", self.conecta_a.trim()));
+        }
+        if partes.is_empty() { "Sin datos".to_string() } else { partes.join(" · ") }
+    }
+
+    /// Profundidad de la clave del tubo bajo la rasante del pozo.
+    pub fn profundidad_m(&self) -> Option<f64> {
+        Some(self.cota_razante? - self.cota_clave?)
+    }
+
+    /// `otro_extremo`: cota clave del mismo tubo en el pozo vecino y distancia hasta él.
+    pub fn calcular(&self, otro_extremo: Option<(f64, f64)>) -> CalculoConexion {
+        let pendiente = match (self.cota_clave, otro_extremo) {
+            (Some(clave), Some((clave_vecino, longitud))) => pendiente_pct(clave, clave_vecino, longitud).map(|p| (longitud, p)),
+            _ => None,
+        };
+        CalculoConexion {
+            profundidad_m: self.profundidad_m(),
+            longitud_m: pendiente.map(|(longitud, _)| longitud),
+            pendiente_pct: pendiente.map(|(_, p)| p),
+            sentido: pendiente.map(|(_, p)| if p >= 0.0 { SentidoFlujo::Sale } else { SentidoFlujo::Llega }),
+            ubicacion_imprecisa: false,
+        }
+    }
+}
+
+// Los campos de selección son `Option`: `None` significa que el inspector
+// todavía no ha respondido esa pregunta. Los campos que dependen de un
+// "Existe" se omiten del JSON cuando están vacíos (ver `rules`).
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Encuesta {
+    // --- Identidad ---
+    // El backend usa el UUID para reconocer una encuesta corregida y
+    // reemplazar el registro anterior en lugar de duplicarlo.
+    #[serde(default)]
+    pub uuid: String,
+    #[serde(default)]
+    pub revision: u32,
+
     // --- Datos Generales ---
     pub pozo_numero: String, // Campo que añadimos nosotros
-    pub tipo_sistema: String,
-    pub tipo_pozo: String,
+    pub tipo_sistema: Option<TipoSistema>,
+    pub tipo_pozo: Option<TipoPozo>,
+    pub ubicacion: Option<Ubicacion>,
 
     // --- Tapa ---
-    pub tapa_existe: String,
-    pub tapa_tipo: String,
-    pub tapa_estado: String,
-    pub tapa_diagnostico: String,
+    pub tapa_existe: Option<SiNo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tapa_tipo: Option<TapaTipo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tapa_estado: Option<EstadoBasico>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tapa_diagnostico: Option<Diagnostico>,
 
     // --- Cargue ---
-    pub cargue_existe: String,
-    pub cargue_estado: String,
-    pub cargue_diagnostico: String,
+    pub cargue_existe: Option<SiNo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cargue_estado: Option<EstadoCargue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cargue_diagnostico: Option<Diagnostico>,
 
     // --- Cono ---
-    pub cono_existe: String,
-    pub cono_estado: String,
-    pub cono_diagnostico: String,
+    pub cono_existe: Option<SiNo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cono_estado: Option<EstadoCargue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cono_diagnostico: Option<Diagnostico>,
 
     // --- Cilindro ---
-    pub cilindro_material: String,
-    pub cilindro_estado: String,
-    pub cilindro_diagnostico: String,
+    pub cilindro_material: Option<MaterialCilindro>,
+    pub cilindro_estado: Option<EstadoCilindro>,
+    pub cilindro_diagnostico: Option<Diagnostico>,
 
     // --- Cañuela ---
-    pub canuela_estado: String,
-    pub canuela_diagnostico: String,
+    pub canuela_estado: Option<EstadoCanuela>,
+    pub canuela_diagnostico: Option<Diagnostico>,
 
     // --- Escalones ---
-    pub escalones_existe: String,
-    pub escalones_tipo: String,
-    pub escalones_estado: String,
-    pub escalones_diagnostico: String,
+    pub escalones_existe: Option<SiNo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalones_tipo: Option<TipoEscalones>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalones_estado: Option<EstadoEscalones>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalones_diagnostico: Option<Diagnostico>,
 
     // --- Datos Finales ---
-    pub estado_general_pozo: String,
+    pub estado_general_pozo: Option<EstadoGeneralPozo>,
     pub observaciones: String, // Campo lógico para añadir notas
 
     // --- Conexiones (lista de tamaño variable) ---
     #[serde(rename = "conexiones")] // Asegura que en JSON el campo se llame "conexiones"
     pub lista_conexiones: Vec<Conexion>,
-}
\ No newline at end of file
+}
+
+impl Encuesta {
+    /// Formulario en blanco con su identidad ya asignada.
+    pub fn nueva(uuid: String) -> Encuesta {
+        Encuesta { uuid, revision: 1, ..Default::default() }
+    }
+
+    /// Indica si el inspector todavía no ha llenado nada (la identidad no cuenta).
+    pub fn esta_vacia(&self) -> bool {
+        Encuesta { uuid: String::new(), revision: 0, ..self.clone() } == Encuesta::default()
+    }
+
+    /// Los reintentos de una misma revisión comparten clave; una corrección no.
+    pub fn clave_idempotencia(&self) -> String {
+        format!("{}-r{}", self.uuid, self.revision)
+    }
+
+    /// Identidad para encuestas creadas antes de que se asignara al abrir el formulario.
+    pub fn asignar_identidad(&mut self, uuid: impl FnOnce() -> String) {
+        if self.uuid.is_empty() {
+            self.uuid = uuid();
+            self.revision = 1;
+        }
+    }
+
+    /// Copia editable que reemplazará a esta encuesta en el backend. Sigue a
+    /// `ultima_revision`, la más alta conocida para el mismo UUID.
+    pub fn nueva_revision(&self, ultima_revision: u32) -> Encuesta {
+        let mut revision = self.clone();
+        revision.revision = self.revision.max(ultima_revision) + 1;
+        revision
+    }
+
+    pub fn es_correccion(&self) -> bool {
+        self.revision > 1
+    }
+
+    /// Asigna identificador a las conexiones guardadas antes de que existieran.
+    pub fn completar_ids_conexiones(&mut self, mut nuevo_id: impl FnMut() -> String) {
+        for conexion in self.lista_conexiones.iter_mut().filter(|c| c.id.is_empty()) {
+            conexion.id = nuevo_id();
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast};
+
+use crate::fotos_db;
+use crate::model::{Foto, Encuesta};
+use crate::storage;
+
+// --- Bandeja de salida persistente ---
+// Cada encuesta se guarda aquí antes de enviarse, de modo que no se pierde
+// si el inspector no tiene señal. Los envíos fallidos se reintentan con
+// espera exponencial; solo los errores del servidor agotan `MAX_INTENTOS`,
+// los cortes de red se reintentan sin límite. Los metadatos de las fotos de
+// cada entrada van en su propia clave, como en los borradores, y las
+// imágenes en IndexedDB (ver `fotos_db`), de donde se leen al enviar.
+
+const CLAVE_OUTBOX: &str = "ac-pwa/outbox";
+const MAX_INTENTOS: u32 = 8;
+const ESPERA_BASE_MS: f64 = 5_000.0;
+const ESPERA_MAXIMA_MS: f64 = 5.0 * 60_000.0;
+
+fn clave_fotos(id: u64) -> String {
+    format!("ac-pwa/outbox/{}/fotos", id)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EstadoEnvio {
+    Pendiente,
+    Enviado,
+    Fallido,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntradaOutbox {
+    pub id: u64,
+    pub creada_en: f64,
+    pub encuesta: Encuesta,
+    // Solo metadatos: la imagen está en IndexedDB. Se guardan aparte en
+    // `clave_fotos`; el campo solo se lee de bandejas anteriores, que
+    // guardaban todo en una sola clave.
+    #[serde(default, skip_serializing)]
+    pub adjuntos: Vec<Foto>,
+    pub estado: EstadoEnvio,
+    pub intentos: u32,
+    // Errores del servidor acumulados; los cortes de red no cuentan.
+    #[serde(default)]
+    pub fallos_servidor: u32,
+    pub proximo_intento: f64,
+    pub ultimo_error: Option<String>,
+    #[serde(default)]
+    pub enviada_en: Option<f64>,
+    /// Cuerpo de la respuesta del backend al aceptar la encuesta.
+    #[serde(default)]
+    pub respuesta: Option<String>,
+    // Las fotos se borran al enviarse; se conserva cuántas eran para el historial.
+    #[serde(default)]
+    pub fotos_enviadas: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConteoOutbox {
+    pub pendientes: usize,
+    pub enviadas: usize,
+    pub fallidas: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Outbox {
+    entradas: Vec<EntradaOutbox>,
+    siguiente_id: u64,
+    // Entradas cuyas fotos cambiaron desde el último `guardar`.
+    #[serde(skip)]
+    fotos_sin_guardar: BTreeSet<u64>,
+}
+
+impl Outbox {
+    pub fn cargar() -> Self {
+        let mut outbox: Outbox = storage::leer(CLAVE_OUTBOX).unwrap_or_default();
+        for entrada in outbox.entradas.iter_mut() {
+            // En formatos anteriores las fotos podían venir en el índice.
+            if entrada.adjuntos.is_empty() {
+                entrada.adjuntos = storage::leer(&clave_fotos(entrada.id)).unwrap_or_default();
+            }
+            fotos_db::renombrar_anteriores(&mut entrada.adjuntos);
+        }
+        outbox
+    }
+
+    /// Fotos que aún traen la imagen en memoria porque se leyeron de un
+    /// formato anterior; hay que pasarlas a IndexedDB y luego `liberar_contenido`.
+    pub fn fotos_sin_migrar(&self) -> Vec<Foto> {
+        self.entradas.iter().flat_map(|e| e.adjuntos.iter()).filter(|f| !f.contenido.is_empty()).cloned().collect()
+    }
+
+    /// Suelta las imágenes ya escritas en IndexedDB y marca sus entradas
+    /// para que el próximo `guardar` reescriba solo los metadatos.
+    pub fn liberar_contenido(&mut self) {
+        for entrada in self.entradas.iter_mut().filter(|e| e.adjuntos.iter().any(|f| !f.contenido.is_empty())) {
+            for foto in entrada.adjuntos.iter_mut() {
+                foto.contenido = Vec::new();
+            }
+            self.fotos_sin_guardar.insert(entrada.id);
+        }
+    }
+
+    /// Nombres de las imágenes que todavía hacen falta para enviar la bandeja.
+    pub fn fotos_referenciadas(&self) -> BTreeSet<String> {
+        self.entradas
+            .iter()
+            .filter(|e| e.estado != EstadoEnvio::Enviado)
+            .flat_map(|e| e.adjuntos.iter())
+            .filter(|f| !f.en_servidor)
+            .map(|f| f.nombre.clone())
+            .collect()
+    }
+
+    /// Persiste la bandeja. Si falla (p. ej. por la cuota del navegador), las
+    /// entradas afectadas solo existen en memoria y el llamador debe avisarlo.
+    pub fn guardar(&mut self) -> Result<(), String> {
+        // Primero las fotos: el índice nunca debe apuntar a fotos que no se guardaron.
+        for id in self.fotos_sin_guardar.clone() {
+            match self.entrada(id) {
+                Some(entrada) if !entrada.adjuntos.is_empty() => storage::guardar(&clave_fotos(id), &entrada.adjuntos)
+                    .map_err(|e| format!("No se pudieron guardar las fotos de la encuesta {}: {}", id, e))?,
+                _ => storage::borrar(&clave_fotos(id)),
+            }
+            self.fotos_sin_guardar.remove(&id);
+        }
+        storage::guardar(CLAVE_OUTBOX, self).map_err(|e| format!("No se pudo guardar la bandeja de salida: {}", e))
+    }
+
+    /// Quita una entrada que no llegó a guardarse; sus datos siguen en el borrador.
+    pub fn retirar(&mut self, id: u64) {
+        self.entradas.retain(|e| e.id != id);
+        self.fotos_sin_guardar.remove(&id);
+        storage::borrar(&clave_fotos(id));
+    }
+
+    /// Encola la encuesta. La misma revisión no se encola dos veces: el backend
+    /// descartaría el segundo envío por su clave de idempotencia.
+    pub fn encolar(&mut self, encuesta: Encuesta, adjuntos: Vec<Foto>, ahora: f64) -> Result<u64, String> {
+        let clave = encuesta.clave_idempotencia();
+        if self.entradas.iter().any(|e| e.encuesta.clave_idempotencia() == clave) {
+            return Err(format!("La revisión {} del pozo {} ya está en la bandeja de salida", encuesta.revision, encuesta.pozo_numero));
+        }
+        let id = self.siguiente_id;
+        self.siguiente_id += 1;
+        self.entradas.push(EntradaOutbox {
+            id,
+            creada_en: ahora,
+            encuesta,
+            adjuntos,
+            estado: EstadoEnvio::Pendiente,
+            intentos: 0,
+            fallos_servidor: 0,
+            proximo_intento: ahora,
+            ultimo_error: None,
+            enviada_en: None,
+            respuesta: None,
+            fotos_enviadas: 0,
+        });
+        self.fotos_sin_guardar.insert(id);
+        Ok(id)
+    }
+
+    /// Revisión más alta registrada para el UUID, enviada o no.
+    pub fn ultima_revision(&self, uuid: &str) -> u32 {
+        self.entradas.iter().filter(|e| e.encuesta.uuid == uuid).map(|e| e.encuesta.revision).max().unwrap_or(0)
+    }
+
+    pub fn entrada(&self, id: u64) -> Option<&EntradaOutbox> {
+        self.entradas.iter().find(|e| e.id == id)
+    }
+
+    fn entrada_mut(&mut self, id: u64) -> Option<&mut EntradaOutbox> {
+        self.entradas.iter_mut().find(|e| e.id == id)
+    }
+
+    fn pendientes(&self) -> impl Iterator<Item = &EntradaOutbox> {
+        self.entradas.iter().filter(|e| e.estado == EstadoEnvio::Pendiente)
+    }
+
+    /// Primera entrada pendiente cuyo tiempo de espera ya se cumplió.
+    pub fn siguiente_lista(&self, ahora: f64) -> Option<u64> {
+        self.pendientes().find(|e| e.proximo_intento <= ahora).map(|e| e.id)
+    }
+
+    /// Milisegundos hasta el próximo reintento programado, si hay alguno.
+    pub fn proxima_espera(&self, ahora: f64) -> Option<f64> {
+        self.pendientes()
+            .map(|e| (e.proximo_intento - ahora).max(0.0))
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    pub fn marcar_enviada(&mut self, id: u64, respuesta: String, ahora: f64) {
+        if let Some(entrada) = self.entradas.iter_mut().find(|e| e.id == id) {
+            entrada.estado = EstadoEnvio::Enviado;
+            entrada.intentos += 1;
+            entrada.ultimo_error = None;
+            entrada.enviada_en = Some(ahora);
+            entrada.respuesta = Some(respuesta).filter(|r| !r.trim().is_empty());
+            // Las fotos ya están en el backend; se liberan las imágenes y se
+            // conservan las referencias para poder corregir la encuesta.
+            entrada.fotos_enviadas = entrada.adjuntos.len();
+            for foto in entrada.adjuntos.iter_mut() {
+                foto.en_servidor = true;
+                foto.contenido = Vec::new();
+            }
+            self.fotos_sin_guardar.insert(id);
+        }
+    }
+
+    /// Error del servidor: programa el reintento con espera exponencial, o después
+    /// de `espera_minima` si el servidor indicó cuánto esperar.
+    pub fn marcar_fallo(&mut self, id: u64, error: String, ahora: f64, espera_minima: Option<f64>) {
+        if let Some(entrada) = self.entrada_mut(id) {
+            entrada.intentos += 1;
+            entrada.fallos_servidor += 1;
+            entrada.ultimo_error = Some(error);
+            if entrada.fallos_servidor >= MAX_INTENTOS {
+                entrada.estado = EstadoEnvio::Fallido;
+            } else {
+                entrada.proximo_intento = ahora + espera_reintento(entrada.intentos).max(espera_minima.unwrap_or(0.0));
+            }
+        }
+    }
+
+    /// No se llegó al servidor: la entrada sigue pendiente hasta que haya señal,
+    /// por débil que sea la conexión.
+    pub fn marcar_fallo_de_red(&mut self, id: u64, error: String, ahora: f64) {
+        if let Some(entrada) = self.entrada_mut(id) {
+            entrada.intentos += 1;
+            entrada.ultimo_error = Some(error);
+            entrada.proximo_intento = ahora + espera_reintento(entrada.intentos);
+        }
+    }
+
+    /// El servidor rechazó los datos: no tiene sentido reintentar automáticamente.
+    pub fn marcar_rechazada(&mut self, id: u64, error: String) {
+        if let Some(entrada) = self.entrada_mut(id) {
+            entrada.intentos += 1;
+            entrada.ultimo_error = Some(error);
+            entrada.estado = EstadoEnvio::Fallido;
+        }
+    }
+
+    /// Adelanta todos los reintentos pendientes (p. ej. al recuperar la conexión);
+    /// devuelve `false` si no había nada que adelantar.
+    pub fn adelantar_pendientes(&mut self, ahora: f64) -> bool {
+        let mut cambio = false;
+        for entrada in self.entradas.iter_mut().filter(|e| e.estado == EstadoEnvio::Pendiente && e.proximo_intento > ahora) {
+            entrada.proximo_intento = ahora;
+            cambio = true;
+        }
+        cambio
+    }
+
+    /// Devuelve las entradas fallidas a la cola con el contador reiniciado;
+    /// devuelve `false` si no había ninguna.
+    pub fn reactivar_fallidas(&mut self, ahora: f64) -> bool {
+        let mut cambio = false;
+        for entrada in self.entradas.iter_mut().filter(|e| e.estado == EstadoEnvio::Fallido) {
+            entrada.estado = EstadoEnvio::Pendiente;
+            entrada.intentos = 0;
+            entrada.fallos_servidor = 0;
+            entrada.proximo_intento = ahora;
+            cambio = true;
+        }
+        cambio
+    }
+
+    /// Todas las encuestas registradas, de la más reciente a la más antigua.
+    pub fn historial(&self) -> Vec<EntradaOutbox> {
+        let mut entradas = self.entradas.clone();
+        entradas.sort_by(|a, b| b.creada_en.total_cmp(&a.creada_en));
+        entradas
+    }
+
+    pub fn conteo(&self) -> ConteoOutbox {
+        let mut conteo = ConteoOutbox::default();
+        for entrada in &self.entradas {
+            match entrada.estado {
+                EstadoEnvio::Pendiente => conteo.pendientes += 1,
+                EstadoEnvio::Enviado => conteo.enviadas += 1,
+                EstadoEnvio::Fallido => conteo.fallidas += 1,
+            }
+        }
+        conteo
+    }
+}
+
+fn espera_reintento(intentos: u32) -> f64 {
+    (ESPERA_BASE_MS * 2f64.powi(intentos as i32 - 1)).min(ESPERA_MAXIMA_MS)
+}
+
+pub fn ahora() -> f64 {
+    web_sys::js_sys::Date::now()
+}
+
+/// UUID v4 aleatorio. `crypto.randomUUID` solo existe en contextos seguros,
+/// así que se arma a partir de `getRandomValues`; si tampoco está disponible
+/// se recurre a `Math.random` para no repetir nunca el mismo identificador.
+pub fn nuevo_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    let aleatorio = web_sys::window()
+        .ok_or_else(|| "sin ventana".to_string())
+        .and_then(|w| w.crypto().map_err(|e| format!("{:?}", e)))
+        .and_then(|crypto| crypto.get_random_values_with_u8_array(&mut bytes).map(|_| ()).map_err(|e| format!("{:?}", e)));
+    if let Err(e) = aleatorio {
+        log::warn!("crypto.getRandomValues no disponible ({}); se usa Math.random", e);
+        for byte in bytes.iter_mut() {
+            *byte = (web_sys::js_sys::Math::random() * 256.0) as u8;
+        }
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+pub fn navegador_en_linea() -> bool {
+    web_sys::window().map(|w| w.navigator().on_line()).unwrap_or(true)
+}
+
+/// Ejecuta `callback` cada vez que el navegador dispara el evento `online`.
+pub fn al_recuperar_conexion(callback: impl FnMut() + 'static) {
+    let Some(window) = web_sys::window() else { return };
+    let closure = Closure::<dyn FnMut()>::new(callback);
+    if let Err(e) = window.add_event_listener_with_callback("online", closure.as_ref().unchecked_ref()) {
+        log::error!("No se pudo registrar el evento 'online': {:?}", e);
+    }
+    // El oyente vive durante toda la aplicación.
+    closure.forget();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{espera_reintento, EstadoEnvio, Outbox, ESPERA_BASE_MS, ESPERA_MAXIMA_MS, MAX_INTENTOS};
+    use crate::model::Encuesta;
+
+    fn bandeja_con_una() -> (Outbox, u64) {
+        let mut outbox = Outbox::default();
+        let id = outbox.encolar(Encuesta::nueva("u-1".to_string()), Vec::new(), 0.0).unwrap();
+        (outbox, id)
+    }
+
+    #[test]
+    fn la_espera_se_duplica_hasta_el_maximo() {
+        assert_eq!(espera_reintento(1), ESPERA_BASE_MS);
+        assert_eq!(espera_reintento(2), 2.0 * ESPERA_BASE_MS);
+        assert_eq!(espera_reintento(4), 8.0 * ESPERA_BASE_MS);
+        assert_eq!(espera_reintento(30), ESPERA_MAXIMA_MS);
+    }
+
+    #[test]
+    fn los_errores_del_servidor_agotan_los_intentos() {
+        let (mut outbox, id) = bandeja_con_una();
+        for _ in 1..MAX_INTENTOS {
+            outbox.marcar_fallo(id, "500".to_string(), 0.0, None);
+        }
+        assert_eq!(outbox.entrada(id).unwrap().estado, EstadoEnvio::Pendiente);
+        outbox.marcar_fallo(id, "500".to_string(), 0.0, None);
+        let entrada = outbox.entrada(id).unwrap();
+        assert_eq!(entrada.estado, EstadoEnvio::Fallido);
+        assert_eq!(entrada.ultimo_error.as_deref(), Some("500"));
+    }
+
+    #[test]
+    fn los_cortes_de_red_no_agotan_los_intentos() {
+        let (mut outbox, id) = bandeja_con_una();
+        for _ in 0..MAX_INTENTOS * 3 {
+            outbox.marcar_fallo_de_red(id, "sin red".to_string(), 1_000.0);
+        }
+        let entrada = outbox.entrada(id).unwrap();
+        assert_eq!(entrada.estado, EstadoEnvio::Pendiente);
+        assert_eq!(entrada.fallos_servidor, 0);
+        assert_eq!(entrada.proximo_intento, 1_000.0 + ESPERA_MAXIMA_MS);
+
+        // Tras los cortes, el servidor todavía tiene su presupuesto completo.
+        for _ in 1..MAX_INTENTOS {
+            outbox.marcar_fallo(id, "503".to_string(), 0.0, None);
+        }
+        assert_eq!(outbox.entrada(id).unwrap().estado, EstadoEnvio::Pendiente);
+    }
+
+    #[test]
+    fn retry_after_alarga_la_espera() {
+        let (mut outbox, id) = bandeja_con_una();
+        outbox.marcar_fallo(id, "429".to_string(), 100.0, Some(60_000.0));
+        assert_eq!(outbox.entrada(id).unwrap().proximo_intento, 100.0 + 60_000.0);
+
+        // Un Retry-After más corto que la espera exponencial no la acorta.
+        outbox.marcar_fallo(id, "429".to_string(), 100.0, Some(1.0));
+        assert_eq!(outbox.entrada(id).unwrap().proximo_intento, 100.0 + espera_reintento(2));
+    }
+
+    #[test]
+    fn la_espera_marca_la_siguiente_lista() {
+        let (mut outbox, id) = bandeja_con_una();
+        outbox.marcar_fallo(id, "500".to_string(), 0.0, None);
+        assert_eq!(outbox.siguiente_lista(1.0), None);
+        assert_eq!(outbox.proxima_espera(1.0), Some(ESPERA_BASE_MS - 1.0));
+        assert_eq!(outbox.siguiente_lista(ESPERA_BASE_MS), Some(id));
+
+        assert!(outbox.adelantar_pendientes(1.0));
+        assert!(!outbox.adelantar_pendientes(1.0));
+        assert_eq!(outbox.siguiente_lista(1.0), Some(id));
+    }
+
+    #[test]
+    fn reactivar_fallidas_reinicia_los_contadores() {
+        let (mut outbox, id) = bandeja_con_una();
+        assert!(!outbox.reactivar_fallidas(0.0));
+        outbox.marcar_rechazada(id, "400".to_string());
+        assert!(outbox.reactivar_fallidas(5.0));
+        let entrada = outbox.entrada(id).unwrap();
+        assert_eq!((entrada.estado, entrada.intentos, entrada.fallos_servidor), (EstadoEnvio::Pendiente, 0, 0));
+        assert_eq!(entrada.proximo_intento, 5.0);
+    }
+
+    #[test]
+    fn la_misma_revision_no_se_encola_dos_veces() {
+        let (mut outbox, _) = bandeja_con_una();
+        assert!(outbox.encolar(Encuesta::nueva("u-1".to_string()), Vec::new(), 1.0).is_err());
+        let correccion = Encuesta::nueva("u-1".to_string()).nueva_revision(outbox.ultima_revision("u-1"));
+        assert!(outbox.encolar(correccion, Vec::new(), 2.0).is_ok());
+        assert_eq!(outbox.ultima_revision("u-1"), 2);
+    }
+}
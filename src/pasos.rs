@@ -0,0 +1,135 @@
+use crate::storage;
+use crate::validation::{Campo, ErrorCampo};
+
+// --- Formulario paso a paso ---
+// En el teléfono el formulario completo es muy largo; el modo por pasos muestra
+// una sección a la vez y valida solo los campos de esa sección antes de avanzar.
+// En escritorio se mantiene la página completa para quien revisa.
+
+const CLAVE_MODO: &str = "ac-pwa/modo-pasos";
+// Ancho a partir del cual se prefiere la página completa si no hay preferencia guardada.
+const ANCHO_ESCRITORIO: f64 = 1024.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paso {
+    DatosGenerales,
+    Tapa,
+    Cargue,
+    Cono,
+    Cilindro,
+    Canuela,
+    Escalones,
+    Conexiones,
+    Fotografias,
+    EvaluacionFinal,
+}
+
+impl Paso {
+    pub const TODOS: [Paso; 10] = [
+        Paso::DatosGenerales,
+        Paso::Tapa,
+        Paso::Cargue,
+        Paso::Cono,
+        Paso::Cilindro,
+        Paso::Canuela,
+        Paso::Escalones,
+        Paso::Conexiones,
+        Paso::Fotografias,
+        Paso::EvaluacionFinal,
+    ];
+
+    pub fn titulo(&self) -> &'static str {
+        match self {
+            Paso::DatosGenerales => "Datos Generales",
+            Paso::Tapa => "Tapa",
+            Paso::Cargue => "Cargue",
+            Paso::Cono => "Cono",
+            Paso::Cilindro => "Cilindro",
+            Paso::Canuela => "Cañuela",
+            Paso::Escalones => "Escalones",
+            Paso::Conexiones => "Conexiones",
+            Paso::Fotografias => "Fotografías",
+            Paso::EvaluacionFinal => "Evaluación Final",
+        }
+    }
+
+    /// Id del primer bloque del paso en el formulario, para volver a él desde el resumen.
+    pub fn id_html(&self) -> String {
+        format!("seccion-{}", self.indice())
+    }
+
+    pub fn indice(&self) -> usize {
+        Paso::TODOS.iter().position(|p| p == self).unwrap_or(0)
+    }
+
+    pub fn anterior(&self) -> Option<Paso> {
+        self.indice().checked_sub(1).map(|i| Paso::TODOS[i])
+    }
+
+    pub fn siguiente(&self) -> Option<Paso> {
+        Paso::TODOS.get(self.indice() + 1).copied()
+    }
+
+    /// Paso en el que se muestra el campo.
+    pub fn de_campo(campo: Campo) -> Paso {
+        match campo {
+            Campo::PozoNumero | Campo::TipoSistema | Campo::TipoPozo => Paso::DatosGenerales,
+            Campo::TapaExiste | Campo::TapaTipo | Campo::TapaEstado | Campo::TapaDiagnostico => Paso::Tapa,
+            Campo::CargueExiste | Campo::CargueEstado | Campo::CargueDiagnostico => Paso::Cargue,
+            Campo::ConoExiste | Campo::ConoEstado | Campo::ConoDiagnostico => Paso::Cono,
+            Campo::CilindroMaterial | Campo::CilindroEstado | Campo::CilindroDiagnostico => Paso::Cilindro,
+            Campo::CanuelaEstado | Campo::CanuelaDiagnostico => Paso::Canuela,
+            Campo::EscalonesExiste | Campo::EscalonesTipo | Campo::EscalonesEstado | Campo::EscalonesDiagnostico => Paso::Escalones,
+            Campo::Conexion(..) => Paso::Conexiones,
+            Campo::Fotos => Paso::Fotografias,
+            Campo::EstadoGeneralPozo => Paso::EvaluacionFinal,
+        }
+    }
+
+    /// Errores que pertenecen a este paso.
+    pub fn errores<'a>(&self, errores: &'a [ErrorCampo]) -> Vec<&'a ErrorCampo> {
+        errores.iter().filter(|e| Paso::de_campo(e.campo) == *self).collect()
+    }
+
+    /// Error que recibe el foco al enviar: el del paso más temprano, porque
+    /// `validation::validar` no recorre los campos en el orden de los pasos.
+    pub fn primer_error(errores: &[ErrorCampo]) -> Option<&ErrorCampo> {
+        errores.iter().min_by_key(|e| Paso::de_campo(e.campo).indice())
+    }
+}
+
+/// Modo guardado por el usuario; sin preferencia se usan pasos en pantallas angostas.
+pub fn preferir_pasos() -> bool {
+    storage::leer(CLAVE_MODO).unwrap_or_else(|| {
+        web_sys::window()
+            .and_then(|w| w.inner_width().ok())
+            .and_then(|ancho| ancho.as_f64())
+            .map(|ancho| ancho < ANCHO_ESCRITORIO)
+            .unwrap_or(false)
+    })
+}
+
+pub fn guardar_preferencia(pasos: bool) {
+    if let Err(e) = storage::guardar(CLAVE_MODO, &pasos) {
+        log::error!("No se pudo guardar el modo del formulario: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Paso;
+    use crate::validation::{Campo, ErrorCampo};
+
+    fn error(campo: Campo) -> ErrorCampo {
+        ErrorCampo { campo, mensaje: String::new() }
+    }
+
+    #[test]
+    fn el_foco_va_al_error_del_primer_paso() {
+        let errores = [error(Campo::EstadoGeneralPozo), error(Campo::TapaEstado), error(Campo::TapaTipo), error(Campo::PozoNumero)];
+        assert_eq!(Paso::primer_error(&errores).map(|e| e.campo), Some(Campo::PozoNumero));
+        // Dentro del mismo paso se respeta el orden de la validación.
+        assert_eq!(Paso::primer_error(&errores[..3]).map(|e| e.campo), Some(Campo::TapaEstado));
+        assert!(Paso::primer_error(&[]).is_none());
+    }
+}
@@ -0,0 +1,78 @@
+use std::rc::Rc;
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ServiceWorkerRegistration, ServiceWorkerState};
+
+// --- Service worker y actualizaciones de la aplicación ---
+// El worker (`public/sw.js`) precarga el app shell para abrir sin conexión.
+// Cuando se despliega una versión nueva, el worker nuevo queda "en espera"
+// y se avisa a la interfaz para que el usuario decida cuándo actualizar.
+
+// Cambia con cada compilación; ver build.rs.
+const VERSION_COMPILACION: &str = env!("AC_PWA_BUILD");
+
+fn ruta_service_worker() -> String {
+    format!("sw.js?v={}", VERSION_COMPILACION)
+}
+
+fn hay_controlador() -> bool {
+    web_sys::window()
+        .map(|w| w.navigator().service_worker().controller().is_some())
+        .unwrap_or(false)
+}
+
+/// Registra el service worker y llama a `on_update` cuando hay una versión nueva lista.
+pub async fn registrar(on_update: impl Fn(ServiceWorkerRegistration) + 'static) {
+    let Some(window) = web_sys::window() else { return };
+    let contenedor = window.navigator().service_worker();
+    let registro = match JsFuture::from(contenedor.register(&ruta_service_worker())).await {
+        Ok(registro) => registro.unchecked_into::<ServiceWorkerRegistration>(),
+        Err(e) => {
+            log::error!("No se pudo registrar el service worker: {:?}", e);
+            return;
+        }
+    };
+    log::info!("Service worker registrado.");
+    let on_update = Rc::new(on_update);
+
+    // Una versión nueva ya pudo quedar en espera en una visita anterior.
+    if registro.waiting().is_some() && hay_controlador() {
+        on_update(registro.clone());
+    }
+
+    let registro_actual = registro.clone();
+    let al_encontrar = Closure::<dyn FnMut()>::new(move || {
+        let Some(nuevo) = registro_actual.installing() else { return };
+        let registro = registro_actual.clone();
+        let on_update = on_update.clone();
+        let worker = nuevo.clone();
+        let al_cambiar = Closure::<dyn FnMut()>::new(move || {
+            // Sin controlador es la primera instalación, no una actualización.
+            if worker.state() == ServiceWorkerState::Installed && hay_controlador() {
+                on_update(registro.clone());
+            }
+        });
+        nuevo.set_onstatechange(Some(al_cambiar.as_ref().unchecked_ref()));
+        al_cambiar.forget();
+    });
+    registro.set_onupdatefound(Some(al_encontrar.as_ref().unchecked_ref()));
+    al_encontrar.forget();
+}
+
+/// Activa el worker en espera y recarga la página cuando toma el control.
+pub fn activar_actualizacion(registro: &ServiceWorkerRegistration) {
+    let Some(window) = web_sys::window() else { return };
+    let Some(en_espera) = registro.waiting() else { return };
+
+    let al_cambiar_controlador = Closure::<dyn FnMut()>::new(crate::config::recargar);
+    window
+        .navigator()
+        .service_worker()
+        .set_oncontrollerchange(Some(al_cambiar_controlador.as_ref().unchecked_ref()));
+    al_cambiar_controlador.forget();
+
+    if let Err(e) = en_espera.post_message(&"SKIP_WAITING".into()) {
+        log::error!("No se pudo activar la nueva versión: {:?}", e);
+    }
+}
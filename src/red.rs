@@ -0,0 +1,454 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::drafts::Borradores;
+use crate::geo;
+use crate::model::{self, CalculoConexion, DireccionConexion, Encuesta, Ubicacion};
+use crate::outbox::Outbox;
+
+// --- Red de alcantarillado ---
+// Cada conexión de una encuesta apunta con `conecta_a` al pozo del otro
+// extremo del tubo. Con todas las encuestas guardadas en el equipo se arma un
+// grafo dirigido de pozos y tramos. Un mismo tubo suele aparecer en las
+// encuestas de sus dos pozos; el sentido del tramo lo da la dirección que
+// marcó el inspector (entrada o salida), si no las cotas clave de ambos
+// extremos y, si falta alguna, se supone que el agua va hacia `conecta_a`.
+
+// Pendientes fuera de este rango (en %) probablemente son cotas mal tomadas.
+const PENDIENTE_MINIMA_PCT: f64 = 0.1;
+const PENDIENTE_MAXIMA_PCT: f64 = 30.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tramo {
+    pub desde: String,
+    pub hasta: String,
+    pub diametro_pulgadas: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problema {
+    /// El tramo apunta a un pozo del que no hay encuesta.
+    SinInspeccionar { desde: String, hasta: String },
+    /// Recorrido que vuelve al pozo de partida.
+    Ciclo(Vec<String>),
+    /// Sale del pozo un tubo más angosto que el mayor que le llega.
+    DiametroMenor { pozo: String, entrada: f64, salida: f64 },
+    /// Una salida del pozo sube hacia el pozo siguiente.
+    PendienteNegativa { pozo: String, hacia: String, pendiente: f64 },
+    /// Pendiente casi nula o demasiado fuerte para un alcantarillado.
+    PendienteAtipica { desde: String, hasta: String, pendiente: f64 },
+}
+
+impl Problema {
+    pub fn descripcion(&self) -> String {
+        match self {
+            Problema::SinInspeccionar { desde, hasta } => {
+                format!("El pozo {} conecta a {}, que no tiene encuesta registrada.", desde, hasta)
+            }
+            Problema::Ciclo(pozos) => format!("Ciclo en la red: {} → {}.", pozos.join(" → "), pozos[0]),
+            Problema::DiametroMenor { pozo, entrada, salida } => format!(
+                "En el pozo {} entra un tubo de {} pulg y sale uno de {} pulg.",
+                pozo, entrada, salida
+            ),
+            Problema::PendienteNegativa { pozo, hacia, pendiente } => format!(
+                "La salida del pozo {} sube hacia {} (pendiente {:.2} %); revise las cotas.",
+                pozo, hacia, pendiente
+            ),
+            Problema::PendienteAtipica { desde, hasta, pendiente } => format!(
+                "La pendiente del tramo {} → {} es de {:.2} %, fuera de lo esperado ({} % a {} %).",
+                desde, hasta, pendiente, PENDIENTE_MINIMA_PCT, PENDIENTE_MAXIMA_PCT
+            ),
+        }
+    }
+
+    fn involucra(&self, pozo: &str) -> bool {
+        match self {
+            Problema::SinInspeccionar { desde, hasta } => desde == pozo || hasta == pozo,
+            Problema::Ciclo(pozos) => pozos.iter().any(|p| p == pozo),
+            Problema::DiametroMenor { pozo: p, .. } | Problema::PendienteNegativa { pozo: p, .. } => p == pozo,
+            Problema::PendienteAtipica { desde, hasta, .. } => desde == pozo || hasta == pozo,
+        }
+    }
+}
+
+// Un tubo tal como lo registró la encuesta de `desde`.
+#[derive(Clone, Debug, PartialEq)]
+struct Enlace {
+    desde: String,
+    hasta: String,
+    diametro_pulgadas: Option<f64>,
+    cota_clave: Option<f64>,
+    direccion: Option<DireccionConexion>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Red {
+    /// Pozos con encuesta.
+    pub inspeccionados: BTreeSet<String>,
+    pub tramos: Vec<Tramo>,
+    enlaces: Vec<Enlace>,
+    ubicaciones: BTreeMap<String, Ubicacion>,
+}
+
+/// Los números de pozo se escriben a mano; se comparan sin espacios ni mayúsculas.
+pub fn normalizar(pozo: &str) -> String {
+    pozo.split_whitespace().collect::<String>().to_uppercase()
+}
+
+/// Encuestas guardadas en el equipo: borradores y bandeja de salida (incluye
+/// el historial), de la modificada más recientemente a la más antigua.
+pub fn encuestas_guardadas(borradores: &Borradores, outbox: &Outbox) -> Vec<Encuesta> {
+    let mut encuestas = borradores.encuestas();
+    encuestas.extend(outbox.historial().into_iter().map(|e| (e.creada_en, e.encuesta)));
+    encuestas.sort_by(|a, b| b.0.total_cmp(&a.0));
+    encuestas.into_iter().map(|(_, encuesta)| encuesta).collect()
+}
+
+impl Red {
+    /// Arma la red con la encuesta en edición y las demás guardadas, que llegan
+    /// de la más reciente a la más antigua. Ante varias encuestas del mismo pozo
+    /// manda la primera: la actual y luego las más recientes. De una misma
+    /// encuesta solo cuenta su revisión más alta, esté en un borrador o en la bandeja.
+    pub fn construir(actual: &Encuesta, guardadas: &[Encuesta]) -> Red {
+        let mut ultimas: HashMap<&str, u32> = HashMap::new();
+        for encuesta in guardadas.iter().filter(|e| !e.uuid.is_empty()) {
+            let revision = ultimas.entry(&encuesta.uuid).or_default();
+            *revision = (*revision).max(encuesta.revision);
+        }
+        let vigentes = guardadas.iter().filter(|e| ultimas.get(e.uuid.as_str()).is_none_or(|r| *r == e.revision));
+
+        let mut red = Red::default();
+        let mut uuids = HashSet::new();
+        for encuesta in std::iter::once(actual).chain(vigentes) {
+            // Las demás revisiones de una misma encuesta no aportan nada nuevo.
+            if !encuesta.uuid.is_empty() && !uuids.insert(encuesta.uuid.clone()) {
+                continue;
+            }
+            let pozo = normalizar(&encuesta.pozo_numero);
+            if pozo.is_empty() || !red.inspeccionados.insert(pozo.clone()) {
+                continue;
+            }
+            if let Some(ubicacion) = &encuesta.ubicacion {
+                red.ubicaciones.insert(pozo.clone(), ubicacion.clone());
+            }
+            for conexion in &encuesta.lista_conexiones {
+                let hasta = normalizar(&conexion.conecta_a);
+                if hasta.is_empty() {
+                    continue;
+                }
+                red.enlaces.push(Enlace {
+                    desde: pozo.clone(),
+                    hasta,
+                    diametro_pulgadas: conexion.diametro_pulgadas,
+                    cota_clave: conexion.cota_clave,
+                    direccion: conexion.direccion,
+                });
+            }
+        }
+
+        // Un tramo por cada par de pozos, orientado según las cotas.
+        let mut tramos: Vec<Tramo> = Vec::new();
+        for enlace in &red.enlaces {
+            let existe = tramos.iter().any(|t| {
+                (t.desde == enlace.desde && t.hasta == enlace.hasta) || (t.desde == enlace.hasta && t.hasta == enlace.desde)
+            });
+            if existe {
+                continue;
+            }
+            let inverso = red.enlace(&enlace.hasta, &enlace.desde);
+            let direccion = enlace.direccion.or(inverso.and_then(|e| e.direccion).map(|d| match d {
+                DireccionConexion::Entrada => DireccionConexion::Salida,
+                DireccionConexion::Salida => DireccionConexion::Entrada,
+            }));
+            let invertido = match (direccion, enlace.cota_clave, inverso.and_then(|e| e.cota_clave)) {
+                (Some(direccion), _, _) => direccion == DireccionConexion::Entrada,
+                (None, Some(aqui), Some(alla)) => alla > aqui,
+                _ => false,
+            };
+            let (desde, hasta) = if invertido { (&enlace.hasta, &enlace.desde) } else { (&enlace.desde, &enlace.hasta) };
+            tramos.push(Tramo {
+                desde: desde.clone(),
+                hasta: hasta.clone(),
+                diametro_pulgadas: enlace.diametro_pulgadas.or(inverso.and_then(|e| e.diametro_pulgadas)),
+            });
+        }
+        red.tramos = tramos;
+        red
+    }
+
+    fn enlace(&self, desde: &str, hasta: &str) -> Option<&Enlace> {
+        self.enlaces.iter().find(|e| e.desde == desde && e.hasta == hasta)
+    }
+
+    /// Cota clave del tubo en el pozo vecino y distancia entre ambos pozos.
+    /// Con una ubicación imprecisa la distancia no sirve para la pendiente: en
+    /// tramos cortos el error del GPS es del orden de la longitud del tubo.
+    pub fn otro_extremo(&self, pozo: &str, vecino: &str) -> Option<(f64, f64)> {
+        let clave = self.enlace(vecino, pozo)?.cota_clave?;
+        if self.ubicacion_imprecisa(pozo, vecino) {
+            return None;
+        }
+        let longitud = geo::distancia_m(self.ubicaciones.get(pozo)?, self.ubicaciones.get(vecino)?);
+        Some((clave, longitud))
+    }
+
+    /// Alguno de los dos pozos se ubicó con precisión peor que `PRECISION_ACEPTABLE_M`.
+    pub fn ubicacion_imprecisa(&self, pozo: &str, vecino: &str) -> bool {
+        [pozo, vecino]
+            .iter()
+            .filter_map(|p| self.ubicaciones.get(*p)?.precision_m)
+            .any(|precision| precision > geo::PRECISION_ACEPTABLE_M)
+    }
+
+    /// Pendiente del tubo desde `desde` hacia `hasta`, positiva si baja.
+    pub fn pendiente_pct(&self, desde: &str, hasta: &str) -> Option<f64> {
+        let clave = self.enlace(desde, hasta)?.cota_clave?;
+        let (clave_vecino, longitud) = self.otro_extremo(desde, hasta)?;
+        model::pendiente_pct(clave, clave_vecino, longitud)
+    }
+
+    /// Valores calculados de cada conexión de la encuesta, en el mismo orden.
+    pub fn calculos(&self, encuesta: &Encuesta) -> Vec<CalculoConexion> {
+        let pozo = normalizar(&encuesta.pozo_numero);
+        encuesta
+            .lista_conexiones
+            .iter()
+            .map(|c| {
+                let vecino = normalizar(&c.conecta_a);
+                CalculoConexion {
+                    ubicacion_imprecisa: self.ubicacion_imprecisa(&pozo, &vecino),
+                    ..c.calcular(self.otro_extremo(&pozo, &vecino))
+                }
+            })
+            .collect()
+    }
+
+    pub fn aguas_arriba(&self, pozo: &str) -> Vec<&Tramo> {
+        self.tramos.iter().filter(|t| t.hasta == pozo).collect()
+    }
+
+    pub fn aguas_abajo(&self, pozo: &str) -> Vec<&Tramo> {
+        self.tramos.iter().filter(|t| t.desde == pozo).collect()
+    }
+
+    pub fn problemas(&self) -> Vec<Problema> {
+        let mut problemas = Vec::new();
+
+        // Se revisan los enlaces y no los tramos: un tramo de entrada queda
+        // orientado hacia el pozo inspeccionado y el vecino sin encuesta
+        // aparecería en `desde`.
+        let mut colgantes = BTreeSet::new();
+        for enlace in &self.enlaces {
+            if !self.inspeccionados.contains(&enlace.hasta) && colgantes.insert((&enlace.desde, &enlace.hasta)) {
+                problemas.push(Problema::SinInspeccionar { desde: enlace.desde.clone(), hasta: enlace.hasta.clone() });
+            }
+        }
+
+        problemas.extend(self.ciclos().into_iter().map(Problema::Ciclo));
+
+        for pozo in &self.inspeccionados {
+            let maximo = |tramos: Vec<&Tramo>| tramos.iter().filter_map(|t| t.diametro_pulgadas).reduce(f64::max);
+            let entrada = maximo(self.aguas_arriba(pozo));
+            let salida = self.aguas_abajo(pozo).iter().filter_map(|t| t.diametro_pulgadas).reduce(f64::min);
+            if let (Some(entrada), Some(salida)) = (entrada, salida) {
+                if salida < entrada {
+                    problemas.push(Problema::DiametroMenor { pozo: pozo.clone(), entrada, salida });
+                }
+            }
+
+            // Las salidas son las que marcó el inspector; si no marcó ninguna se
+            // toma el tubo más bajo del pozo, siempre que haya más de uno.
+            let propios: Vec<&Enlace> = self.enlaces.iter().filter(|e| &e.desde == pozo).collect();
+            let mut salidas: Vec<&Enlace> = propios.iter().copied().filter(|e| e.direccion == Some(DireccionConexion::Salida)).collect();
+            if salidas.is_empty() && propios.iter().filter(|e| e.cota_clave.is_some()).count() > 1 {
+                salidas.extend(
+                    propios
+                        .iter()
+                        .filter(|e| e.cota_clave.is_some())
+                        .min_by(|a, b| a.cota_clave.unwrap_or_default().total_cmp(&b.cota_clave.unwrap_or_default())),
+                );
+            }
+            for salida in salidas {
+                if let Some(pendiente) = self.pendiente_pct(pozo, &salida.hasta).filter(|p| *p < 0.0) {
+                    problemas.push(Problema::PendienteNegativa { pozo: pozo.clone(), hacia: salida.hasta.clone(), pendiente });
+                }
+            }
+        }
+
+        for tramo in &self.tramos {
+            let Some(pendiente) = self.pendiente_pct(&tramo.desde, &tramo.hasta) else { continue };
+            // Una pendiente negativa ya reportada como tal no se repite como atípica.
+            let ya_negativa = problemas.iter().any(|p| match p {
+                Problema::PendienteNegativa { pozo, hacia, .. } => {
+                    (pozo == &tramo.desde && hacia == &tramo.hasta) || (pozo == &tramo.hasta && hacia == &tramo.desde)
+                }
+                _ => false,
+            });
+            if !ya_negativa && !(PENDIENTE_MINIMA_PCT..=PENDIENTE_MAXIMA_PCT).contains(&pendiente.abs()) {
+                problemas.push(Problema::PendienteAtipica { desde: tramo.desde.clone(), hasta: tramo.hasta.clone(), pendiente });
+            }
+        }
+        problemas
+    }
+
+    pub fn problemas_de(&self, pozo: &str) -> Vec<Problema> {
+        self.problemas().into_iter().filter(|p| p.involucra(pozo)).collect()
+    }
+
+    // Búsqueda en profundidad; cada arista que vuelve a un pozo de la pila
+    // actual cierra un ciclo.
+    fn ciclos(&self) -> Vec<Vec<String>> {
+        let mut siguientes: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for tramo in &self.tramos {
+            siguientes.entry(&tramo.desde).or_default().push(&tramo.hasta);
+        }
+
+        fn visitar<'a>(
+            pozo: &'a str,
+            siguientes: &BTreeMap<&'a str, Vec<&'a str>>,
+            pila: &mut Vec<&'a str>,
+            terminados: &mut HashSet<&'a str>,
+            ciclos: &mut Vec<Vec<String>>,
+        ) {
+            pila.push(pozo);
+            for &siguiente in siguientes.get(pozo).into_iter().flatten() {
+                if let Some(inicio) = pila.iter().position(|&p| p == siguiente) {
+                    ciclos.push(pila[inicio..].iter().map(|p| p.to_string()).collect());
+                } else if !terminados.contains(siguiente) {
+                    visitar(siguiente, siguientes, pila, terminados, ciclos);
+                }
+            }
+            pila.pop();
+            terminados.insert(pozo);
+        }
+
+        let mut ciclos = Vec::new();
+        let mut terminados = HashSet::new();
+        for &pozo in siguientes.keys() {
+            if !terminados.contains(pozo) {
+                visitar(pozo, &siguientes, &mut Vec::new(), &mut terminados, &mut ciclos);
+            }
+        }
+        ciclos
+    }
+}
+
+/// Guarda en cada conexión sus valores calculados, para enviarlos con la encuesta.
+pub fn calcular_conexiones(encuesta: &mut Encuesta, guardadas: &[Encuesta]) {
+    let calculos = Red::construir(encuesta, guardadas).calculos(encuesta);
+    for (conexion, calculo) in encuesta.lista_conexiones.iter_mut().zip(calculos) {
+        conexion.calculo = calculo;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Problema, Red, Tramo};
+    use crate::model::{Conexion, DireccionConexion, Encuesta, OrigenUbicacion, Ubicacion};
+
+    fn tubo(hacia: &str, direccion: Option<DireccionConexion>, cota_clave: Option<f64>, diametro: Option<f64>) -> Conexion {
+        Conexion { conecta_a: hacia.to_string(), direccion, cota_clave, diametro_pulgadas: diametro, ..Default::default() }
+    }
+
+    fn pozo(numero: &str, conexiones: Vec<Conexion>) -> Encuesta {
+        Encuesta { uuid: format!("uuid-{}", numero), pozo_numero: numero.to_string(), lista_conexiones: conexiones, ..Encuesta::nueva(String::new()) }
+    }
+
+    // Un milésimo de grado de latitud son unos 111 m.
+    fn en(encuesta: Encuesta, milesimas_norte: f64, precision_m: Option<f64>) -> Encuesta {
+        let ubicacion = Ubicacion {
+            latitud: 4.6 + milesimas_norte / 1_000.0,
+            longitud: -74.08,
+            precision_m,
+            capturada_en: String::new(),
+            origen: OrigenUbicacion::Gps,
+        };
+        Encuesta { ubicacion: Some(ubicacion), ..encuesta }
+    }
+
+    fn tramo(desde: &str, hasta: &str) -> (String, String) {
+        (desde.to_string(), hasta.to_string())
+    }
+
+    fn tramos(red: &Red) -> Vec<(String, String)> {
+        red.tramos.iter().map(|t: &Tramo| (t.desde.clone(), t.hasta.clone())).collect()
+    }
+
+    #[test]
+    fn los_tramos_siguen_la_direccion_y_luego_las_cotas() {
+        let actual = pozo("p 1", vec![tubo("P2", Some(DireccionConexion::Salida), None, None), tubo("p3", Some(DireccionConexion::Entrada), None, None)]);
+        let guardadas = vec![
+            pozo("P4", vec![tubo("P5", None, Some(9.0), None)]),
+            pozo("P5", vec![tubo("P4", None, Some(10.0), None)]),
+        ];
+        let red = Red::construir(&actual, &guardadas);
+        assert_eq!(tramos(&red), vec![tramo("P1", "P2"), tramo("P3", "P1"), tramo("P5", "P4")]);
+        assert_eq!(red.aguas_arriba("P1").len(), 1);
+        assert_eq!(red.aguas_abajo("P1").len(), 1);
+    }
+
+    #[test]
+    fn de_una_encuesta_cuenta_su_ultima_revision() {
+        let actual = pozo("P9", Vec::new());
+        let borrador_viejo = pozo("P1", vec![tubo("P2", None, None, None)]);
+        let enviada = Encuesta { revision: 2, ..pozo("P1", vec![tubo("P3", None, None, None)]) };
+        // El borrador es más reciente en la lista, pero su revisión es anterior.
+        let red = Red::construir(&actual, &[borrador_viejo, enviada]);
+        assert_eq!(tramos(&red), vec![tramo("P1", "P3")]);
+    }
+
+    #[test]
+    fn la_encuesta_actual_manda_sobre_las_guardadas() {
+        let actual = Encuesta { uuid: "otra".to_string(), ..pozo("P1", vec![tubo("P2", None, None, None)]) };
+        let red = Red::construir(&actual, &[pozo("P1", vec![tubo("P3", None, None, None)])]);
+        assert_eq!(tramos(&red), vec![tramo("P1", "P2")]);
+    }
+
+    #[test]
+    fn vecinos_sin_encuesta_en_ambos_sentidos() {
+        let actual = pozo("P1", vec![tubo("X", Some(DireccionConexion::Entrada), None, None), tubo("Y", Some(DireccionConexion::Salida), None, None)]);
+        let problemas = Red::construir(&actual, &[]).problemas();
+        assert!(problemas.contains(&Problema::SinInspeccionar { desde: "P1".to_string(), hasta: "X".to_string() }));
+        assert!(problemas.contains(&Problema::SinInspeccionar { desde: "P1".to_string(), hasta: "Y".to_string() }));
+    }
+
+    #[test]
+    fn detecta_ciclos() {
+        let salida = |hacia| tubo(hacia, Some(DireccionConexion::Salida), None, None);
+        let actual = pozo("A", vec![salida("B")]);
+        let red = Red::construir(&actual, &[pozo("B", vec![salida("C")]), pozo("C", vec![salida("A")])]);
+        let ciclos: Vec<_> = red.problemas().into_iter().filter(|p| matches!(p, Problema::Ciclo(_))).collect();
+        assert_eq!(ciclos, vec![Problema::Ciclo(vec!["A".to_string(), "B".to_string(), "C".to_string()])]);
+
+        let sin_ciclo = Red::construir(&pozo("A", vec![salida("B")]), &[pozo("B", vec![salida("C")])]);
+        assert!(!sin_ciclo.problemas().iter().any(|p| matches!(p, Problema::Ciclo(_))));
+    }
+
+    #[test]
+    fn sale_un_tubo_mas_angosto_que_el_que_entra() {
+        let actual = pozo("B", vec![tubo("A", Some(DireccionConexion::Entrada), None, Some(12.0)), tubo("C", Some(DireccionConexion::Salida), None, Some(8.0))]);
+        let problemas = Red::construir(&actual, &[]).problemas_de("B");
+        assert!(problemas.contains(&Problema::DiametroMenor { pozo: "B".to_string(), entrada: 12.0, salida: 8.0 }));
+    }
+
+    #[test]
+    fn pendiente_negativa_no_se_repite_como_atipica() {
+        let actual = en(pozo("A", vec![tubo("B", Some(DireccionConexion::Salida), Some(10.0), None)]), 0.0, Some(5.0));
+        let vecino = en(pozo("B", vec![tubo("A", Some(DireccionConexion::Entrada), Some(60.0), None)]), 1.0, Some(5.0));
+        let problemas = Red::construir(&actual, &[vecino]).problemas();
+        assert!(matches!(problemas.as_slice(), [Problema::PendienteNegativa { pozo, hacia, pendiente }] if pozo == "A" && hacia == "B" && *pendiente < -30.0));
+    }
+
+    #[test]
+    fn pendiente_atipica_y_ubicacion_imprecisa() {
+        let actual = en(pozo("A", vec![tubo("B", Some(DireccionConexion::Salida), Some(10.0), None)]), 0.0, Some(5.0));
+        let vecino = en(pozo("B", vec![tubo("A", Some(DireccionConexion::Entrada), Some(9.99), None)]), 1.0, Some(5.0));
+        let red = Red::construir(&actual, std::slice::from_ref(&vecino));
+        assert!(matches!(red.problemas().as_slice(), [Problema::PendienteAtipica { .. }]));
+
+        // Con un GPS impreciso no se calcula la pendiente.
+        let impreciso = en(vecino, 1.0, Some(50.0));
+        let red = Red::construir(&actual, &[impreciso]);
+        assert_eq!(red.pendiente_pct("A", "B"), None);
+        assert!(red.problemas().is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::model::{Diagnostico, Encuesta, Opcion, SiNo};
+use crate::validation::{Campo, CampoConexion, ErrorCampo};
+
+// --- Reglas entre campos de la encuesta ---
+// Cuando un componente no existe ("Existe = No") sus campos dependientes
+// no aplican: se deshabilitan en el formulario, se vacían en la encuesta
+// y no se envían al backend.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Componente {
+    Tapa,
+    Cargue,
+    Cono,
+    Escalones,
+}
+
+impl Componente {
+    pub const TODOS: [Componente; 4] = [Componente::Tapa, Componente::Cargue, Componente::Cono, Componente::Escalones];
+
+    fn existe(&self, encuesta: &Encuesta) -> Option<SiNo> {
+        match self {
+            Componente::Tapa => encuesta.tapa_existe,
+            Componente::Cargue => encuesta.cargue_existe,
+            Componente::Cono => encuesta.cono_existe,
+            Componente::Escalones => encuesta.escalones_existe,
+        }
+    }
+
+    pub fn ausente(&self, encuesta: &Encuesta) -> bool {
+        self.existe(encuesta) == Some(SiNo::No)
+    }
+
+    /// Campos que solo tienen sentido si el componente existe.
+    pub fn dependientes(&self) -> &'static [Campo] {
+        match self {
+            Componente::Tapa => &[Campo::TapaTipo, Campo::TapaEstado, Campo::TapaDiagnostico],
+            Componente::Cargue => &[Campo::CargueEstado, Campo::CargueDiagnostico],
+            Componente::Cono => &[Campo::ConoEstado, Campo::ConoDiagnostico],
+            Componente::Escalones => &[Campo::EscalonesTipo, Campo::EscalonesEstado, Campo::EscalonesDiagnostico],
+        }
+    }
+
+    fn limpiar_dependientes(&self, encuesta: &mut Encuesta) {
+        match self {
+            Componente::Tapa => {
+                encuesta.tapa_tipo = None;
+                encuesta.tapa_estado = None;
+                encuesta.tapa_diagnostico = None;
+            }
+            Componente::Cargue => {
+                encuesta.cargue_estado = None;
+                encuesta.cargue_diagnostico = None;
+            }
+            Componente::Cono => {
+                encuesta.cono_estado = None;
+                encuesta.cono_diagnostico = None;
+            }
+            Componente::Escalones => {
+                encuesta.escalones_tipo = None;
+                encuesta.escalones_estado = None;
+                encuesta.escalones_diagnostico = None;
+            }
+        }
+    }
+}
+
+/// Vacía los campos dependientes de los componentes que no existen.
+/// Se llama después de cambiar cualquiera de las respuestas "Existe".
+pub fn aplicar(encuesta: &mut Encuesta) {
+    for componente in Componente::TODOS {
+        if componente.ausente(encuesta) {
+            componente.limpiar_dependientes(encuesta);
+        }
+    }
+}
+
+/// Indica si el campo aplica con las respuestas actuales de la encuesta.
+pub fn aplica(encuesta: &Encuesta, campo: Campo) -> bool {
+    !Componente::TODOS
+        .iter()
+        .any(|c| c.ausente(encuesta) && c.dependientes().contains(&campo))
+}
+
+/// Advertencias de consistencia entre estado y diagnóstico, y conexiones sin
+/// dirección. No bloquean el envío; solo se muestran junto al campo.
+pub fn advertencias(encuesta: &Encuesta) -> Vec<ErrorCampo> {
+    let mut advertencias = Vec::new();
+    let pares = [
+        (Campo::TapaDiagnostico, encuesta.tapa_estado.map(|e| e.etiqueta()), encuesta.tapa_diagnostico),
+        (Campo::CargueDiagnostico, encuesta.cargue_estado.map(|e| e.etiqueta()), encuesta.cargue_diagnostico),
+        (Campo::ConoDiagnostico, encuesta.cono_estado.map(|e| e.etiqueta()), encuesta.cono_diagnostico),
+        (Campo::CilindroDiagnostico, encuesta.cilindro_estado.map(|e| e.etiqueta()), encuesta.cilindro_diagnostico),
+        (Campo::CanuelaDiagnostico, encuesta.canuela_estado.map(|e| e.etiqueta()), encuesta.canuela_diagnostico),
+        (Campo::EscalonesDiagnostico, encuesta.escalones_estado.map(|e| e.etiqueta()), encuesta.escalones_diagnostico),
+    ];
+
+    for (campo, estado, diagnostico) in pares {
+        let (Some(estado), Some(diagnostico)) = (estado, diagnostico) else { continue };
+        // Todas las listas de estado comparten "Bueno" y "Regular"; el resto indica daño.
+        let sin_dano = estado == "Bueno" || estado == "Regular";
+        let inconsistente = match diagnostico {
+            Diagnostico::Cambiar => estado == "Bueno",
+            Diagnostico::Reparar => false,
+            Diagnostico::NoRequiere => !sin_dano,
+        };
+        if inconsistente {
+            advertencias.push(ErrorCampo {
+                campo,
+                mensaje: format!("Estado \"{}\" con diagnóstico \"{}\": verifique", estado, diagnostico),
+            });
+        }
+    }
+
+    // Las encuestas enviadas antes de registrar la dirección no la tienen; sin
+    // ella el sentido del flujo se deduce de las cotas.
+    for (i, conexion) in encuesta.lista_conexiones.iter().enumerate() {
+        if conexion.direccion.is_none() {
+            advertencias.push(ErrorCampo {
+                campo: Campo::Conexion(i, CampoConexion::Direccion),
+                mensaje: "Sin dirección: el sentido del flujo se deducirá de las cotas".to_string(),
+            });
+        }
+    }
+
+    advertencias
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{advertencias, aplica, aplicar};
+    use crate::model::{Conexion, DireccionConexion, Diagnostico, EstadoBasico, EstadoCargue, EstadoCilindro, Encuesta, SiNo, TapaTipo};
+    use crate::validation::{Campo, CampoConexion};
+
+    fn campos(encuesta: &Encuesta) -> Vec<Campo> {
+        advertencias(encuesta).into_iter().map(|a| a.campo).collect()
+    }
+
+    #[test]
+    fn un_componente_ausente_vacia_sus_dependientes() {
+        let mut encuesta = Encuesta {
+            tapa_existe: Some(SiNo::No),
+            tapa_tipo: Some(TapaTipo::Concreto),
+            tapa_estado: Some(EstadoBasico::Malo),
+            tapa_diagnostico: Some(Diagnostico::Cambiar),
+            cono_existe: Some(SiNo::Si),
+            cono_estado: Some(EstadoCargue::Grietas),
+            ..Default::default()
+        };
+        aplicar(&mut encuesta);
+        assert_eq!((encuesta.tapa_tipo, encuesta.tapa_estado, encuesta.tapa_diagnostico), (None, None, None));
+        assert_eq!(encuesta.cono_estado, Some(EstadoCargue::Grietas));
+        assert!(!aplica(&encuesta, Campo::TapaEstado));
+        assert!(aplica(&encuesta, Campo::ConoEstado));
+        // Sin responder "Existe" el campo sigue aplicando.
+        assert!(aplica(&encuesta, Campo::EscalonesTipo));
+    }
+
+    #[test]
+    fn estado_y_diagnostico_inconsistentes() {
+        let encuesta = Encuesta {
+            tapa_estado: Some(EstadoBasico::Bueno),
+            tapa_diagnostico: Some(Diagnostico::Cambiar),
+            cono_estado: Some(EstadoCargue::Partido),
+            cono_diagnostico: Some(Diagnostico::NoRequiere),
+            cilindro_estado: Some(EstadoCilindro::Regular),
+            cilindro_diagnostico: Some(Diagnostico::NoRequiere),
+            ..Default::default()
+        };
+        assert_eq!(campos(&encuesta), vec![Campo::TapaDiagnostico, Campo::ConoDiagnostico]);
+    }
+
+    #[test]
+    fn reparar_nunca_es_inconsistente() {
+        let encuesta = Encuesta {
+            tapa_estado: Some(EstadoBasico::Bueno),
+            tapa_diagnostico: Some(Diagnostico::Reparar),
+            cono_estado: Some(EstadoCargue::Hundido),
+            cono_diagnostico: Some(Diagnostico::Reparar),
+            ..Default::default()
+        };
+        assert!(campos(&encuesta).is_empty());
+    }
+
+    #[test]
+    fn conexion_sin_direccion_es_advertencia() {
+        let encuesta = Encuesta {
+            lista_conexiones: vec![
+                Conexion { direccion: Some(DireccionConexion::Entrada), ..Default::default() },
+                Conexion::default(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(campos(&encuesta), vec![Campo::Conexion(1, CampoConexion::Direccion)]);
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+// Persistencia local sobre `localStorage`. Todos los valores se guardan como JSON.
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+pub fn leer<T: DeserializeOwned>(clave: &str) -> Option<T> {
+    let texto = local_storage()?.get_item(clave).ok().flatten()?;
+    match serde_json::from_str(&texto) {
+        Ok(valor) => Some(valor),
+        Err(e) => {
+            log::error!("No se pudo leer '{}' del almacenamiento local: {}", clave, e);
+            None
+        }
+    }
+}
+
+pub fn guardar<T: Serialize>(clave: &str, valor: &T) -> Result<(), String> {
+    let storage = local_storage().ok_or("Almacenamiento local no disponible")?;
+    let texto = serde_json::to_string(valor).map_err(|e| e.to_string())?;
+    // `set_item` falla cuando se supera la cuota del navegador.
+    storage.set_item(clave, &texto).map_err(|e| format!("{:?}", e))
+}
+
+pub fn borrar(clave: &str) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(clave);
+    }
+}
+
+// Lee los bytes que versiones anteriores guardaban como texto base64; hoy las
+// imágenes van en IndexedDB (ver `fotos_db`).
+pub mod base64 {
+    use ::base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let texto = String::deserialize(deserializer)?;
+        STANDARD.decode(texto).map_err(serde::de::Error::custom)
+    }
+}
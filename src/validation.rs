@@ -0,0 +1,267 @@
+use crate::medidas;
+use crate::model::{Foto, Conexion, Encuesta};
+use crate::rules;
+
+// --- Validación de la encuesta antes de enviarla ---
+// `validar` devuelve la lista de errores por campo en el orden en que
+// aparecen en el formulario, de modo que el primero es el más arriba.
+
+const DIAMETRO_MINIMO_PULGADAS: f64 = 2.0;
+const DIAMETRO_MAXIMO_PULGADAS: f64 = 120.0;
+// Cotas sobre el nivel del mar, en metros.
+const COTA_MINIMA_M: f64 = -100.0;
+const COTA_MAXIMA_M: f64 = 5000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CampoConexion {
+    CotaRazante,
+    CotaClave,
+    DiametroPulgadas,
+    Direccion,
+    PosicionReloj,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Campo {
+    PozoNumero,
+    TipoSistema,
+    TipoPozo,
+    TapaExiste,
+    TapaTipo,
+    TapaEstado,
+    TapaDiagnostico,
+    CargueExiste,
+    CargueEstado,
+    CargueDiagnostico,
+    ConoExiste,
+    ConoEstado,
+    ConoDiagnostico,
+    CilindroMaterial,
+    CilindroEstado,
+    CilindroDiagnostico,
+    CanuelaEstado,
+    CanuelaDiagnostico,
+    EscalonesExiste,
+    EscalonesTipo,
+    EscalonesEstado,
+    EscalonesDiagnostico,
+    EstadoGeneralPozo,
+    Conexion(usize, CampoConexion),
+    Fotos,
+}
+
+impl Campo {
+    /// Id del elemento HTML del campo, usado para desplazarse hasta él.
+    pub fn id_html(&self) -> String {
+        let nombre = match self {
+            Campo::PozoNumero => "pozo_numero",
+            Campo::TipoSistema => "tipo_sistema",
+            Campo::TipoPozo => "tipo_pozo",
+            Campo::TapaExiste => "tapa_existe",
+            Campo::TapaTipo => "tapa_tipo",
+            Campo::TapaEstado => "tapa_estado",
+            Campo::TapaDiagnostico => "tapa_diagnostico",
+            Campo::CargueExiste => "cargue_existe",
+            Campo::CargueEstado => "cargue_estado",
+            Campo::CargueDiagnostico => "cargue_diagnostico",
+            Campo::ConoExiste => "cono_existe",
+            Campo::ConoEstado => "cono_estado",
+            Campo::ConoDiagnostico => "cono_diagnostico",
+            Campo::CilindroMaterial => "cilindro_material",
+            Campo::CilindroEstado => "cilindro_estado",
+            Campo::CilindroDiagnostico => "cilindro_diagnostico",
+            Campo::CanuelaEstado => "canuela_estado",
+            Campo::CanuelaDiagnostico => "canuela_diagnostico",
+            Campo::EscalonesExiste => "escalones_existe",
+            Campo::EscalonesTipo => "escalones_tipo",
+            Campo::EscalonesEstado => "escalones_estado",
+            Campo::EscalonesDiagnostico => "escalones_diagnostico",
+            Campo::EstadoGeneralPozo => "estado_general_pozo",
+            Campo::Fotos => "fotos",
+            Campo::Conexion(i, campo) => {
+                let nombre = match campo {
+                    CampoConexion::CotaRazante => "cota_razante",
+                    CampoConexion::CotaClave => "cota_clave",
+                    CampoConexion::DiametroPulgadas => "diametro_pulgadas",
+                    CampoConexion::Direccion => "direccion",
+                    CampoConexion::PosicionReloj => "posicion_reloj",
+                };
+                return format!("campo-conexion-{}-{}", i, nombre);
+            }
+        };
+        format!("campo-{}", nombre)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorCampo {
+    pub campo: Campo,
+    pub mensaje: String,
+}
+
+/// Mensaje de error del campo, si lo tiene.
+pub fn error_de(errores: &[ErrorCampo], campo: Campo) -> Option<String> {
+    errores.iter().find(|e| e.campo == campo).map(|e| e.mensaje.clone())
+}
+
+/// Interpreta un número escrito a mano con el separador decimal del navegador.
+pub fn parsear_numero(texto: &str) -> Option<f64> {
+    interpretar_numero(texto, medidas::separador_decimal()).ok()
+}
+
+/// Interpreta un número con el separador decimal `decimal`. El otro separador
+/// solo se acepta como decimal cuando no puede ser de miles: en es-CO "1.5"
+/// es 1,5, pero "2.600" puede ser 2600 o 2,6 y se rechaza en vez de adivinar.
+pub fn interpretar_numero(texto: &str, decimal: char) -> Result<f64, String> {
+    let texto = texto.trim();
+    let otro = if decimal == ',' { '.' } else { ',' };
+    let separadores = texto.matches([',', '.']).count();
+    let separador = match (texto.contains(decimal), texto.contains(otro)) {
+        (true, false) if separadores == 1 => Some(decimal),
+        (false, true) if separadores == 1 => {
+            let decimales = texto.rsplit(otro).next().unwrap_or_default();
+            (decimales.len() != 3).then_some(otro)
+        }
+        (false, false) => None,
+        _ => return Err("Escriba el número sin separador de miles".to_string()),
+    };
+    let texto = match separador {
+        Some(separador) => texto.replace(separador, "."),
+        None if texto.contains(otro) => return Err(format!("Use '{}' como separador decimal, sin separador de miles", decimal)),
+        None => texto.to_string(),
+    };
+    texto.parse::<f64>().ok().filter(|n| n.is_finite()).ok_or_else(|| "Ingrese un número válido".to_string())
+}
+
+pub fn validar(encuesta: &Encuesta, adjuntos: &[Foto]) -> Vec<ErrorCampo> {
+    let mut errores = Vec::new();
+    if encuesta.pozo_numero.trim().is_empty() {
+        errores.push(ErrorCampo { campo: Campo::PozoNumero, mensaje: "Ingrese el número del pozo".to_string() });
+    }
+
+    // Los campos de un componente inexistente no se exigen.
+    let mut requerido = |campo: Campo, respondido: bool| {
+        if !respondido && rules::aplica(encuesta, campo) {
+            errores.push(ErrorCampo { campo, mensaje: "Seleccione una opción".to_string() });
+        }
+    };
+    requerido(Campo::TipoSistema, encuesta.tipo_sistema.is_some());
+    requerido(Campo::TipoPozo, encuesta.tipo_pozo.is_some());
+
+    requerido(Campo::TapaExiste, encuesta.tapa_existe.is_some());
+    requerido(Campo::TapaTipo, encuesta.tapa_tipo.is_some());
+    requerido(Campo::TapaEstado, encuesta.tapa_estado.is_some());
+    requerido(Campo::TapaDiagnostico, encuesta.tapa_diagnostico.is_some());
+
+    requerido(Campo::CargueExiste, encuesta.cargue_existe.is_some());
+    requerido(Campo::CargueEstado, encuesta.cargue_estado.is_some());
+    requerido(Campo::CargueDiagnostico, encuesta.cargue_diagnostico.is_some());
+
+    requerido(Campo::ConoExiste, encuesta.cono_existe.is_some());
+    requerido(Campo::ConoEstado, encuesta.cono_estado.is_some());
+    requerido(Campo::ConoDiagnostico, encuesta.cono_diagnostico.is_some());
+
+    requerido(Campo::CilindroMaterial, encuesta.cilindro_material.is_some());
+    requerido(Campo::CilindroEstado, encuesta.cilindro_estado.is_some());
+    requerido(Campo::CilindroDiagnostico, encuesta.cilindro_diagnostico.is_some());
+
+    requerido(Campo::CanuelaEstado, encuesta.canuela_estado.is_some());
+    requerido(Campo::CanuelaDiagnostico, encuesta.canuela_diagnostico.is_some());
+
+    requerido(Campo::EscalonesExiste, encuesta.escalones_existe.is_some());
+    requerido(Campo::EscalonesTipo, encuesta.escalones_tipo.is_some());
+    requerido(Campo::EscalonesEstado, encuesta.escalones_estado.is_some());
+    requerido(Campo::EscalonesDiagnostico, encuesta.escalones_diagnostico.is_some());
+
+    requerido(Campo::EstadoGeneralPozo, encuesta.estado_general_pozo.is_some());
+
+    for (i, conexion) in encuesta.lista_conexiones.iter().enumerate() {
+        validar_conexion(i, conexion, &mut errores);
+    }
+
+    if adjuntos.is_empty() {
+        errores.push(ErrorCampo { campo: Campo::Fotos, mensaje: "Adjunte al menos una fotografía".to_string() });
+    }
+
+    errores
+}
+
+fn validar_conexion(i: usize, conexion: &Conexion, errores: &mut Vec<ErrorCampo>) {
+    let mut requerido = |campo: CampoConexion, valor: Option<f64>| {
+        if valor.is_none() {
+            errores.push(ErrorCampo { campo: Campo::Conexion(i, campo), mensaje: "Campo requerido".to_string() });
+        }
+        valor
+    };
+
+    let razante = requerido(CampoConexion::CotaRazante, conexion.cota_razante);
+    let clave = requerido(CampoConexion::CotaClave, conexion.cota_clave);
+    let diametro = requerido(CampoConexion::DiametroPulgadas, conexion.diametro_pulgadas);
+    // La hora llega de un selector, pero una encuesta guardada puede traer cualquier número.
+    if conexion.posicion_reloj.is_some_and(|hora| !(1..=12).contains(&hora)) {
+        errores.push(ErrorCampo { campo: Campo::Conexion(i, CampoConexion::PosicionReloj), mensaje: "La hora debe estar entre 1 y 12".to_string() });
+    }
+
+    for (campo, cota) in [(CampoConexion::CotaRazante, razante), (CampoConexion::CotaClave, clave)] {
+        if let Some(cota) = cota.filter(|c| !(COTA_MINIMA_M..=COTA_MAXIMA_M).contains(c)) {
+            errores.push(ErrorCampo {
+                campo: Campo::Conexion(i, campo),
+                mensaje: format!("La cota {} m está fuera del rango esperado ({} a {} m)", cota, COTA_MINIMA_M, COTA_MAXIMA_M),
+            });
+        }
+    }
+
+    // La clave (parte superior del tubo) siempre está por debajo de la rasante.
+    if let (Some(razante), Some(clave)) = (razante, clave) {
+        if clave >= razante {
+            errores.push(ErrorCampo {
+                campo: Campo::Conexion(i, CampoConexion::CotaClave),
+                mensaje: "La cota clave debe ser menor que la cota rasante".to_string(),
+            });
+        }
+    }
+
+    if let Some(diametro) = diametro {
+        if !(DIAMETRO_MINIMO_PULGADAS..=DIAMETRO_MAXIMO_PULGADAS).contains(&diametro) {
+            errores.push(ErrorCampo {
+                campo: Campo::Conexion(i, CampoConexion::DiametroPulgadas),
+                mensaje: format!("El diámetro debe estar entre {} y {} pulgadas", DIAMETRO_MINIMO_PULGADAS, DIAMETRO_MAXIMO_PULGADAS),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interpretar_numero;
+
+    #[test]
+    fn usa_el_separador_decimal_del_idioma() {
+        assert_eq!(interpretar_numero("2,6", ','), Ok(2.6));
+        assert_eq!(interpretar_numero("2.6", '.'), Ok(2.6));
+        assert_eq!(interpretar_numero(" -0,25 ", ','), Ok(-0.25));
+        assert_eq!(interpretar_numero("2600", ','), Ok(2600.0));
+    }
+
+    #[test]
+    fn acepta_el_otro_separador_cuando_no_es_ambiguo() {
+        assert_eq!(interpretar_numero("1.5", ','), Ok(1.5));
+        assert_eq!(interpretar_numero("2612.45", ','), Ok(2612.45));
+        assert_eq!(interpretar_numero("1,5", '.'), Ok(1.5));
+    }
+
+    #[test]
+    fn rechaza_numeros_con_separador_de_miles() {
+        assert!(interpretar_numero("2.600", ',').is_err());
+        assert!(interpretar_numero("2,600", '.').is_err());
+        assert!(interpretar_numero("2.600,5", ',').is_err());
+        assert!(interpretar_numero("1.234.567", ',').is_err());
+        assert!(interpretar_numero("1,5,2", ',').is_err());
+    }
+
+    #[test]
+    fn rechaza_texto_que_no_es_numero() {
+        assert!(interpretar_numero("abc", ',').is_err());
+        assert!(interpretar_numero("", ',').is_err());
+    }
+}